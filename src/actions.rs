@@ -1,20 +1,53 @@
-use crate::models::{Dir, ObjectsType, Player, Position};
+use crate::models::{Dir, Door, DoorState, ObjectsType, Player, Position, Stats, Surface, P2};
 use log::debug;
 
+use crate::area::{Area, Cell, Tile};
+use crate::combat::{self, Faction};
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::Energy;
 use crate::gridref::GridRef;
+use crate::ship::ModuleHealth;
+use crate::view::Viewshed;
+use auto_walk_system::AutoWalk;
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
+use specs::storage::{GenericReadStorage, MaskedStorage};
 use specs_derive::*;
+use std::ops::Deref;
 
 pub mod actions_system;
+pub mod auto_walk_system;
 pub mod avatar_actions_system;
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Action {
     Interact,
     Move(Dir),
+    /// open the closed door at the adjacent cell
+    Open(P2),
+    /// close the open door at the adjacent cell
+    Close(P2),
+    /// pressurize or depressurize the compartment of the airlock
+    CycleAirlock,
+    /// melee attack whoever is at the adjacent cell
+    Attack(P2),
+    /// ranged attack whoever is at the cell
+    Fire(P2),
+    /// pick the item at the cell
+    Pickup,
+    /// drop the item at the inventory index
+    Drop(usize),
+    /// use the item at the inventory index
+    Use(usize),
+    /// go down the stairs at the cell
+    Descend,
+    /// go up the stairs at the cell
+    Ascend,
+    /// repair a damaged ship module or hull breach around
+    Repair,
 }
 
-#[derive(Debug, Clone, Component)]
+#[derive(Debug, Clone, Component, Serialize, Deserialize)]
 pub struct EntityActions {
     /// list of actions that a entity can do
     pub actions: Vec<Action>,
@@ -31,39 +64,253 @@ impl EntityActions {
     }
 }
 
+/// move the controlled avatars, moving into a hostile attack it
 pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+    let entities = ecs.entities();
     let mut positions = ecs.write_storage::<Position>();
     let grids = ecs.read_storage::<GridRef>();
-    let player = ecs.fetch::<Player>();
+    let stats = ecs.read_storage::<Stats>();
+    let factions = ecs.read_storage::<Faction>();
+    let mut energies = ecs.write_storage::<Energy>();
+    let mut actions = ecs.write_storage::<EntityActions>();
+    let mut viewsheds = ecs.write_storage::<Viewshed>();
+    let surfaces = ecs.read_storage::<Surface>();
+    let controlled = ecs.fetch::<Player>().get_controlled();
+
+    // move one at time, so each avatar sees the cells the previous ones moved into
+    let avatars = (&entities, &controlled)
+        .join()
+        .map(|(avatar_id, _)| avatar_id)
+        .collect::<Vec<_>>();
+
+    for avatar_id in avatars {
+        if stats.get(avatar_id).map(|s| s.is_dead()).unwrap_or(false) {
+            continue;
+        }
+
+        if energies
+            .get(avatar_id)
+            .map(|e| !e.is_ready())
+            .unwrap_or(false)
+        {
+            debug!("{:?} has no energy to move", avatar_id);
+            continue;
+        }
 
-    for (avatar_id, pos) in (player.get_avatarset(), &mut positions).join() {
+        let pos = match positions.get(avatar_id) {
+            Some(pos) => pos.clone(),
+            None => continue,
+        };
         let map = GridRef::find_area(&grids, pos.grid_id).unwrap();
 
         let new_pos = pos.point.translate(delta_x, delta_y);
-        match map.get_grid().get_at(&new_pos) {
-            Some(cell) if !cell.tile.is_opaque() => {
-                debug!("{:?} move to position {:?}", avatar_id, new_pos);
-                pos.point = new_pos;
+        let target = step_position(&surfaces, map, &pos, new_pos);
+
+        let hostile = combat::find_living_at(&entities, &stats, &positions, &target)
+            .filter(|_| target.grid_id == pos.grid_id)
+            .filter(
+                |other_id| match (factions.get(avatar_id), factions.get(*other_id)) {
+                    (Some(a), Some(b)) => a.is_hostile(b),
+                    _ => false,
+                },
+            );
+        if let (Some(_), Some(actions)) = (hostile, actions.get_mut(avatar_id)) {
+            debug!("{:?} attack {:?}", avatar_id, new_pos);
+            actions.current = Some(Action::Attack(new_pos));
+            continue;
+        }
+
+        let area = GridRef::find_area(&grids, target.grid_id);
+        if is_walkable_step(area, &entities, &stats, &positions, &target) {
+            debug!("{:?} move to position {:?}", avatar_id, target);
+            if target.grid_id != pos.grid_id {
+                if let Some(viewshed) = viewsheds.get_mut(avatar_id) {
+                    viewshed.visible_tiles.clear();
+                    viewshed.know_tiles.clear();
+                }
             }
-            _ => {
-                debug!(
-                    "{:?} try to move to invalid position {:?}",
-                    avatar_id, new_pos
-                );
+            positions.insert(avatar_id, target).unwrap();
+            if let Some(energy) = energies.get_mut(avatar_id) {
+                energy.spend();
             }
+        } else {
+            debug!(
+                "{:?} try to move to invalid position {:?}",
+                avatar_id, new_pos
+            );
+        }
+    }
+}
+
+/// position reached by a step from pos into the next point of its area, beyond the zone border
+/// it continues into the neighbour zone
+pub fn step_position(
+    surfaces: &ReadStorage<Surface>,
+    area: &Area,
+    pos: &Position,
+    next: P2,
+) -> Position {
+    let zone_size = area.get_grid().get_size();
+    Surface::cross_border(surfaces, pos.grid_id, zone_size, next).unwrap_or(Position {
+        grid_id: pos.grid_id,
+        point: next,
+    })
+}
+
+/// a step can only end in a walkable cell of the target area that no living actor takes, the same
+/// rule for the avatars, the crew and any other actor
+pub fn is_walkable_step<'a, DS, DP>(
+    area: Option<&Area>,
+    entities: &Entities<'a>,
+    stats: &Storage<'a, Stats, DS>,
+    positions: &Storage<'a, Position, DP>,
+    target: &Position,
+) -> bool
+where
+    DS: Deref<Target = MaskedStorage<Stats>>,
+    DP: Deref<Target = MaskedStorage<Position>>,
+{
+    area.map(|area| area.is_walkable_at(&target.point))
+        .unwrap_or(false)
+        && combat::find_living_at(entities, stats, positions, target).is_none()
+}
+
+/// start the controlled avatars walking until the target, return false if there is no path
+pub fn set_auto_walk(ecs: &mut World, target: P2) -> bool {
+    let avatar_id = ecs.fetch::<Player>().get_avatar_id();
+    let paths = {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let grids = ecs.read_storage::<GridRef>();
+        let controlled = ecs.fetch::<Player>().get_controlled();
+        (&entities, &controlled, &positions)
+            .join()
+            .filter_map(|(e, _, pos)| {
+                GridRef::find_area(&grids, pos.grid_id)
+                    .and_then(|area| area.find_path(&pos.point, &target))
+                    .filter(|path| !path.is_empty())
+                    .map(|path| (e, path))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if paths.is_empty() {
+        ecs.write_resource::<EntitiesEvents>()
+            .push(Event::ActionFailed {
+                actor_id: avatar_id,
+                reason: "no path to target".to_string(),
+            });
+        return false;
+    }
+
+    let mut walks = ecs.write_storage::<AutoWalk>();
+    for (e, path) in paths {
+        walks.insert(e, AutoWalk::new(path)).unwrap();
+    }
+    true
+}
+
+pub fn cancel_auto_walk(ecs: &mut World) {
+    let avatars = ecs.fetch::<Player>().get_avatars().clone();
+    let mut walks = ecs.write_storage::<AutoWalk>();
+    for avatar_id in avatars {
+        walks.remove(avatar_id);
+    }
+}
+
+/// fire the focused avatar weapon at the closest hostile in view, return false if there is none
+pub fn fire_at_nearest(ecs: &mut World) -> bool {
+    let avatar_id = ecs.fetch::<Player>().get_avatar_id();
+    let target = {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let stats = ecs.read_storage::<Stats>();
+        let factions = ecs.read_storage::<Faction>();
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        combat::find_nearest_hostile(
+            &entities, &positions, &stats, &factions, &viewsheds, avatar_id,
+        )
+    };
+
+    match target {
+        Some(target) => {
+            set_current_action(ecs, Action::Fire(target));
+            true
+        }
+        None => {
+            ecs.write_resource::<EntitiesEvents>()
+                .push(Event::ActionFailed {
+                    actor_id: avatar_id,
+                    reason: "no target in view".to_string(),
+                });
+            false
+        }
+    }
+}
+
+/// set the action only to the focused avatar, ignoring group mode
+pub fn set_focused_action(ecs: &mut World, action: Action) {
+    let avatar_id = ecs.fetch::<Player>().get_avatar_id();
+    if let Some(entity_actions) = ecs.write_storage::<EntityActions>().get_mut(avatar_id) {
+        entity_actions.current = Some(action);
+    }
+}
+
+/// open or close the first door around each controlled avatar
+pub fn set_door_action(ecs: &mut World, open: bool) {
+    let controlled = ecs.fetch::<Player>().get_controlled();
+    let mut actions = ecs.write_storage::<EntityActions>();
+    for (_, entity_actions) in (&controlled, &mut actions).join() {
+        let action = entity_actions.actions.iter().find(|action| match action {
+            Action::Open(_) => open,
+            Action::Close(_) => !open,
+            _ => false,
+        });
+        if let Some(action) = action.cloned() {
+            entity_actions.current = Some(action);
         }
     }
 }
 
 pub fn set_current_action(ecs: &mut World, action: Action) {
-    let player = ecs.fetch::<Player>();
+    let controlled = ecs.fetch::<Player>().get_controlled();
     let mut actions = ecs.write_storage::<EntityActions>();
-    for (_, entity_action) in (player.get_avatarset(), &mut actions).join() {
+    for (_, entity_action) in (&controlled, &mut actions).join() {
         entity_action.current = Some(action.clone());
     }
 }
 
-pub fn get_available_actions(objects_at_cell: &Vec<(Entity, ObjectsType)>) -> Vec<Action> {
+/// action to walk from a cell into a neighbour one, opening the door on the way. Diagonals are
+/// only walkable when both sides are free, so they are walked one axis at time
+pub fn step_towards(area: &Area, from: &P2, next: &P2) -> Action {
+    let (dx, dy) = (next.x - from.x, next.y - from.y);
+    let (dir, step) = if dx != 0 {
+        let dir = if dx > 0 { Dir::E } else { Dir::W };
+        (dir, from.translate(dx.signum(), 0))
+    } else {
+        let dir = if dy > 0 { Dir::S } else { Dir::N };
+        (dir, from.translate(0, dy.signum()))
+    };
+
+    match area.get_grid().get_at(&step).map(|cell| cell.tile) {
+        Some(Tile::Door { open: false }) => Action::Open(step),
+        _ => Action::Move(dir),
+    }
+}
+
+pub fn get_available_actions<P, D, H>(
+    objects_at_cell: &[(Entity, ObjectsType)],
+    objects_around: &[(Entity, ObjectsType)],
+    positions: &P,
+    doors: &D,
+    healths: &H,
+    damaged_hull_id: Option<Entity>,
+) -> Vec<Action>
+where
+    P: GenericReadStorage<Component = Position>,
+    D: GenericReadStorage<Component = Door>,
+    H: GenericReadStorage<Component = ModuleHealth>,
+{
     let mut actions = vec![];
 
     for (_, kind) in objects_at_cell {
@@ -71,9 +318,367 @@ pub fn get_available_actions(objects_at_cell: &Vec<(Entity, ObjectsType)>) -> Ve
             ObjectsType::Cockpit => {
                 actions.push(Action::Interact);
             }
+            ObjectsType::Airlock => {
+                actions.push(Action::CycleAirlock);
+            }
+            ObjectsType::Item if !actions.contains(&Action::Pickup) => {
+                actions.push(Action::Pickup);
+            }
+            ObjectsType::Stairs { down: true } => {
+                actions.push(Action::Descend);
+            }
+            ObjectsType::Stairs { down: false } => {
+                actions.push(Action::Ascend);
+            }
             _ => {}
         }
     }
 
+    for (id, kind) in objects_around {
+        let point = match positions.get(*id) {
+            Some(pos) => pos.point,
+            None => continue,
+        };
+        match (kind, doors.get(*id).map(|door| door.state)) {
+            (ObjectsType::Door { .. }, Some(DoorState::Open)) => actions.push(Action::Close(point)),
+            // trying a locked door tells it is locked
+            (ObjectsType::Door { .. }, Some(DoorState::Closed))
+            | (ObjectsType::Door { .. }, Some(DoorState::Locked)) => {
+                actions.push(Action::Open(point))
+            }
+            _ => {}
+        }
+    }
+
+    let repairable = damaged_hull_id.is_some()
+        || objects_at_cell
+            .iter()
+            .chain(objects_around.iter())
+            .any(|(id, kind)| {
+                *kind == ObjectsType::Breach
+                    || healths.get(*id).map(|h| h.is_damaged()).unwrap_or(false)
+            });
+    if repairable {
+        actions.push(Action::Repair);
+    }
+
     actions
 }
+
+/// change door state and update the tile of the area it belongs
+pub fn set_door_state(
+    grids: &mut WriteStorage<GridRef>,
+    door: &mut Door,
+    pos: &Position,
+    state: DoorState,
+) {
+    door.state = state;
+    update_door_tile(grids, door, pos);
+}
+
+/// write the cell tile derived from the door state
+pub fn update_door_tile(grids: &mut WriteStorage<GridRef>, door: &Door, pos: &Position) {
+    match GridRef::find_gmap_mut(grids, pos.grid_id) {
+        Some(area) => {
+            let mut cell: Cell = area
+                .get_grid()
+                .get_at(&pos.point)
+                .copied()
+                .unwrap_or_default();
+            cell.tile = Tile::Door {
+                open: door.is_open(),
+            };
+            area.set_cell_at(&pos.point, cell);
+        }
+        None => {
+            log::warn!("door at {:?} has no area", pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::Event;
+    use crate::loader;
+    use crate::models::{Dir, ObjectsType, Position, Stats, P2};
+    use crate::simulation::Simulation;
+    use crate::test_utils::{
+        get_avatar_position, is_opaque_at, new_state_basic_scenery, new_state_default_scenery,
+        walk, walk_into_airlock,
+    };
+
+    #[test]
+    fn test_actors_should_not_walk_into_each_other() {
+        let (mut sim, _) = new_state_basic_scenery();
+        let avatar_pos = get_avatar_position(&sim);
+        let actor_pos = Position {
+            grid_id: avatar_pos.grid_id,
+            point: avatar_pos.point.translate(1, 0),
+        };
+        let actor_id = sim
+            .ecs_mut()
+            .create_entity()
+            .with(actor_pos.clone())
+            .with(Stats::new(100, 100))
+            .with(EntityActions::new())
+            .build();
+
+        // the avatar can not step into the actor cell
+        sim.move_avatar(1, 0);
+        assert_eq!(avatar_pos, get_avatar_position(&sim));
+
+        // neither the actor into the avatar cell
+        sim.ecs_mut()
+            .write_storage::<EntityActions>()
+            .get_mut(actor_id)
+            .unwrap()
+            .current = Some(Action::Move(Dir::W));
+        sim.tick();
+        assert_eq!(
+            Some(&actor_pos),
+            sim.ecs().read_storage::<Position>().get(actor_id)
+        );
+        assert!(sim.events().contains(&Event::ActionFailed {
+            actor_id,
+            reason: "path is blocked".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_doors_should_block_movement_until_open() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let door_pos = P2::new(12, 8);
+        // other door next to the same cell, its tile comes from the door state
+        let other_door_pos = P2::new(11, 7);
+        loader::create_door(
+            sim.ecs_mut(),
+            Position {
+                grid_id: scenery.ships["ship"],
+                point: other_door_pos,
+            },
+            false,
+            DoorState::Closed,
+        );
+        assert!(is_opaque_at(&sim, other_door_pos));
+
+        // walk until the closed door
+        sim.move_avatar(1, 0);
+        sim.move_avatar(1, 0);
+        sim.move_avatar(1, 0);
+        sim.move_avatar(1, 0);
+        assert_eq!(P2::new(11, 8), get_avatar_position(&sim).point);
+        assert!(is_opaque_at(&sim, door_pos));
+
+        // open and walk through
+        sim.set_action(Action::Open(door_pos));
+        sim.tick();
+        assert!(!is_opaque_at(&sim, door_pos));
+        assert!(is_opaque_at(&sim, other_door_pos));
+        assert!(matches!(
+            sim.events().last(),
+            Some(Event::DoorOpened { .. })
+        ));
+        sim.move_avatar(1, 0);
+        assert_eq!(door_pos, get_avatar_position(&sim).point);
+
+        // can not close while standing in the door
+        sim.set_action(Action::Close(door_pos));
+        sim.tick();
+        assert!(!is_opaque_at(&sim, door_pos));
+        assert!(matches!(
+            sim.events().last(),
+            Some(Event::ActionFailed { .. })
+        ));
+
+        sim.move_avatar(1, 0);
+        sim.set_action(Action::Close(door_pos));
+        sim.tick();
+        assert!(is_opaque_at(&sim, door_pos));
+    }
+
+    #[test]
+    fn test_locked_doors_should_not_open() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let avatar_id = scenery.avatar_id;
+        let door_pos = P2::new(8, 7);
+        loader::create_door(
+            sim.ecs_mut(),
+            Position {
+                grid_id: scenery.ships["ship"],
+                point: door_pos,
+            },
+            false,
+            DoorState::Locked,
+        );
+        assert!(is_opaque_at(&sim, door_pos));
+
+        sim.set_action(Action::Open(door_pos));
+        sim.tick();
+        assert!(is_opaque_at(&sim, door_pos));
+        assert!(sim.events().contains(&Event::ActionFailed {
+            actor_id: avatar_id,
+            reason: "door is locked".to_string(),
+        }));
+
+        sim.move_avatar(0, -1);
+        assert_eq!(P2::new(8, 8), get_avatar_position(&sim).point);
+    }
+
+    #[test]
+    fn test_move_action_should_walk_the_same_cells_as_the_avatar() {
+        let (mut sim, _) = new_state_basic_scenery();
+        walk_into_airlock(&mut sim);
+        walk(&mut sim, 1, 0, 1);
+        sim.set_action(Action::Open(P2::new(21, 8)));
+        sim.tick();
+        walk(&mut sim, 1, 0, 1);
+        assert_eq!(P2::new(21, 8), get_avatar_position(&sim).point);
+
+        // out of the ship into the space with a move action and with the avatar input
+        sim.set_action(Action::Move(Dir::E));
+        sim.tick();
+        assert_eq!(P2::new(22, 8), get_avatar_position(&sim).point);
+        sim.move_avatar(1, 0);
+        assert_eq!(P2::new(23, 8), get_avatar_position(&sim).point);
+    }
+
+    #[test]
+    fn test_avatar_should_walk_across_zone_borders() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let avatar_id = scenery.avatar_id;
+        let zones = scenery.zones["Planet X"].clone();
+        sim.ecs_mut()
+            .write_storage::<Position>()
+            .insert(
+                avatar_id,
+                Position {
+                    grid_id: zones[0],
+                    point: P2::new(99, 50),
+                },
+            )
+            .unwrap();
+
+        // east into the next zone and back
+        sim.move_avatar(1, 0);
+        assert_eq!(
+            Position {
+                grid_id: zones[1],
+                point: P2::new(0, 50)
+            },
+            get_avatar_position(&sim)
+        );
+        sim.set_action(Action::Move(Dir::W));
+        sim.tick();
+        assert_eq!(
+            Position {
+                grid_id: zones[0],
+                point: P2::new(99, 50)
+            },
+            get_avatar_position(&sim)
+        );
+
+        // south into the mountains, through the path from its landing area
+        walk(&mut sim, -1, 0, 49);
+        walk(&mut sim, 0, 1, 49);
+        assert_eq!(P2::new(50, 99), get_avatar_position(&sim).point);
+        sim.move_avatar(0, 1);
+        assert_eq!(
+            Position {
+                grid_id: zones[2],
+                point: P2::new(50, 0)
+            },
+            get_avatar_position(&sim)
+        );
+
+        // there is nothing beyond the planet surface border
+        sim.move_avatar(0, -1);
+        assert_eq!(zones[0], get_avatar_position(&sim).grid_id);
+        walk(&mut sim, 0, -1, 99);
+        assert_eq!(P2::new(50, 0), get_avatar_position(&sim).point);
+        sim.move_avatar(0, -1);
+        assert_eq!(
+            Position {
+                grid_id: zones[0],
+                point: P2::new(50, 0)
+            },
+            get_avatar_position(&sim)
+        );
+    }
+
+    #[test]
+    fn test_avatar_should_descend_and_ascend_dungeon_levels() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let avatar_id = scenery.avatar_id;
+        let zone_id = scenery.zones["Planet X"][3];
+        let levels = scenery.dungeons[&zone_id].clone();
+        assert_eq!(2, levels.len());
+
+        let find_stairs = |sim: &Simulation, grid_id: Entity, down: bool| -> P2 {
+            let objects = sim.ecs().read_storage::<ObjectsType>();
+            let positions = sim.ecs().read_storage::<Position>();
+            (&objects, &positions)
+                .join()
+                .find(|(kind, pos)| {
+                    **kind == ObjectsType::Stairs { down } && pos.grid_id == grid_id
+                })
+                .map(|(_, pos)| pos.point)
+                .unwrap()
+        };
+        let teleport = |sim: &mut Simulation, grid_id: Entity, point: P2| {
+            sim.ecs_mut()
+                .write_storage::<Position>()
+                .insert(avatar_id, Position { grid_id, point })
+                .unwrap();
+        };
+
+        // enter the dungeon from the building
+        let entrance = find_stairs(&sim, zone_id, true);
+        teleport(&mut sim, zone_id, entrance);
+        sim.set_action(Action::Descend);
+        sim.tick();
+        let pos = get_avatar_position(&sim);
+        assert_eq!(levels[0], pos.grid_id);
+        assert_eq!(find_stairs(&sim, levels[0], false), pos.point);
+        assert!(sim.events().contains(&Event::ClimbedStairs {
+            actor_id: avatar_id,
+            down: true
+        }));
+
+        // go deeper and back
+        let down = find_stairs(&sim, levels[0], true);
+        teleport(&mut sim, levels[0], down);
+        sim.set_action(Action::Descend);
+        sim.tick();
+        let pos = get_avatar_position(&sim);
+        assert_eq!(levels[1], pos.grid_id);
+        assert_eq!(find_stairs(&sim, levels[1], false), pos.point);
+
+        sim.set_action(Action::Ascend);
+        sim.tick();
+        assert_eq!(
+            Position {
+                grid_id: levels[0],
+                point: down
+            },
+            get_avatar_position(&sim)
+        );
+
+        // and leave to the surface
+        let up = find_stairs(&sim, levels[0], false);
+        teleport(&mut sim, levels[0], up);
+        sim.set_action(Action::Ascend);
+        sim.tick();
+        assert_eq!(
+            Position {
+                grid_id: zone_id,
+                point: entrance
+            },
+            get_avatar_position(&sim)
+        );
+        assert!(sim.events().contains(&Event::ClimbedStairs {
+            actor_id: avatar_id,
+            down: false
+        }));
+    }
+}
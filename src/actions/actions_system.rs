@@ -1,9 +1,22 @@
-use crate::actions::{get_available_actions, Action, EntityActions};
+use crate::actions::{
+    get_available_actions, is_walkable_step, set_door_state, step_position, Action, EntityActions,
+};
 
-use crate::models::{ObjectsType, Position};
-use crate::utils::find_objects_at;
+use crate::area::Tile;
+use crate::atmosphere;
+use crate::combat::{self, Combat, Faction};
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::{self, Energy};
+use crate::gridref::GridRef;
+use crate::inventory::{self, Inventory, Item};
+use crate::models::{Door, DoorState, ObjectsType, Position, Stairs, Stats, Surface};
+use crate::ship::damage::{self, REPAIR_AMOUNT};
+use crate::ship::{self, ModuleHealth, Ship};
+use crate::unwrap_or_continue;
+use crate::utils::{find_objects_around, find_objects_at};
 
 use crate::view::window::Window;
+use crate::view::Viewshed;
 use specs::prelude::*;
 
 pub struct ActionsSystem {}
@@ -13,26 +26,492 @@ impl<'a> System<'a> for ActionsSystem {
         Entities<'a>,
         WriteStorage<'a, EntityActions>,
         ReadStorage<'a, ObjectsType>,
-        ReadStorage<'a, Position>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Door>,
+        WriteStorage<'a, GridRef>,
+        WriteStorage<'a, Energy>,
+        WriteStorage<'a, Stats>,
+        ReadStorage<'a, Combat>,
+        ReadStorage<'a, Faction>,
+        WriteStorage<'a, Inventory>,
+        ReadStorage<'a, Item>,
+        ReadStorage<'a, Stairs>,
+        ReadStorage<'a, Surface>,
+        WriteStorage<'a, Viewshed>,
+        WriteStorage<'a, ModuleHealth>,
+        WriteStorage<'a, Ship>,
         WriteExpect<'a, Window>,
+        Write<'a, EntitiesEvents>,
     );
 
-    fn run(&mut self, (entities, mut actions, objects, positions, mut window): Self::SystemData) {
-        for (_e, actions, pos) in (&entities, &mut actions, &positions).join() {
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut actions,
+            objects,
+            mut positions,
+            mut doors,
+            mut grids,
+            mut energies,
+            mut stats,
+            combats,
+            factions,
+            mut inventories,
+            items,
+            stairs,
+            surfaces,
+            mut viewsheds,
+            mut healths,
+            mut ships,
+            mut window,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        // cells occupied by any actor, doors on it can not be closed
+        let occupied = (&actions, &positions)
+            .join()
+            .map(|(_, pos)| pos.clone())
+            .collect::<Vec<_>>();
+
+        let mut moves = vec![];
+        // items leaving or arriving on the ground
+        let mut picked = vec![];
+        let mut dropped = vec![];
+        // entities moving into other grid
+        let mut transfers = vec![];
+
+        for (e, actions, pos) in (&entities, &mut actions, &positions).join() {
+            // keep the action pending until the entity has energy to execute it
+            if !game_time::is_ready(&energies, e) {
+                continue;
+            }
+
             // take current action and check if can be executed
             match actions.current.take() {
+                Some(Action::Move(dir)) => {
+                    let (dx, dy) = dir.as_vec();
+                    let next = pos.point.translate(dx, dy);
+
+                    let area = unwrap_or_continue!(GridRef::find_gmap_mut(&mut grids, pos.grid_id));
+                    let next_pos = step_position(&surfaces, area, pos, next);
+
+                    // bump into a hostile is a melee attack
+                    let hostile = combat::find_living_at(&entities, &stats, &positions, &next_pos)
+                        .filter(
+                            |target_id| match (factions.get(e), factions.get(*target_id)) {
+                                (Some(a), Some(b)) => a.is_hostile(b),
+                                _ => false,
+                            },
+                        );
+                    if let Some(target_id) = hostile.filter(|_| next_pos.grid_id == pos.grid_id) {
+                        let damage = combats.get(e).map(|c| c.melee_damage).unwrap_or(1);
+                        events.push(combat::hit(&mut stats, e, target_id, damage, false));
+                        if let Some(energy) = energies.get_mut(e) {
+                            energy.spend();
+                        }
+                        continue;
+                    }
+
+                    let next_area =
+                        GridRef::find_gmap_mut(&mut grids, next_pos.grid_id).map(|area| &*area);
+                    if !is_walkable_step(next_area, &entities, &stats, &positions, &next_pos) {
+                        events.push(Event::ActionFailed {
+                            actor_id: e,
+                            reason: "path is blocked".to_string(),
+                        });
+                        continue;
+                    }
+                    if let Some(energy) = energies.get_mut(e) {
+                        energy.spend();
+                    }
+                    if next_pos.grid_id == pos.grid_id {
+                        moves.push((e, next));
+                    } else {
+                        transfers.push((e, next_pos));
+                    }
+                }
+                Some(Action::Attack(point)) => {
+                    let target_pos = Position {
+                        grid_id: pos.grid_id,
+                        point,
+                    };
+                    let target = combat::find_living_at(&entities, &stats, &positions, &target_pos)
+                        .filter(|target_id| *target_id != e);
+                    let target_id = match target {
+                        Some(target_id) if combat::is_adjacent(&pos.point, &point) => target_id,
+                        _ => {
+                            events.push(Event::ActionFailed {
+                                actor_id: e,
+                                reason: "nothing to attack".to_string(),
+                            });
+                            continue;
+                        }
+                    };
+
+                    let damage = combats.get(e).map(|c| c.melee_damage).unwrap_or(1);
+                    events.push(combat::hit(&mut stats, e, target_id, damage, false));
+                    if let Some(energy) = energies.get_mut(e) {
+                        energy.spend();
+                    }
+                }
+                Some(Action::Fire(point)) => {
+                    let fail = |events: &mut EntitiesEvents, reason: &str| {
+                        events.push(Event::ActionFailed {
+                            actor_id: e,
+                            reason: reason.to_string(),
+                        })
+                    };
+
+                    let combat = match combats.get(e) {
+                        Some(combat) if combat.has_ranged() => combat,
+                        _ => {
+                            fail(&mut events, "no ranged weapon");
+                            continue;
+                        }
+                    };
+                    if !combat::is_in_range(&pos.point, &point, combat.range) {
+                        fail(&mut events, "target is out of range");
+                        continue;
+                    }
+                    let area = unwrap_or_continue!(GridRef::find_gmap_mut(&mut grids, pos.grid_id));
+                    if !area.has_line_of_sight(&pos.point, &point) {
+                        fail(&mut events, "no line of sight");
+                        continue;
+                    }
+                    let target_pos = Position {
+                        grid_id: pos.grid_id,
+                        point,
+                    };
+                    let target = combat::find_living_at(&entities, &stats, &positions, &target_pos)
+                        .filter(|target_id| *target_id != e);
+                    let target_id = match target {
+                        Some(target_id) => target_id,
+                        None => {
+                            // ship modules can be shot too
+                            let module_id =
+                                find_objects_at(&entities, &objects, &positions, &target_pos)
+                                    .into_iter()
+                                    .map(|(id, _)| id)
+                                    .find(|id| {
+                                        healths.get(*id).map(|h| !h.is_broken()).unwrap_or(false)
+                                    });
+                            let module_id = match module_id {
+                                Some(module_id) => module_id,
+                                None => {
+                                    fail(&mut events, "nothing to shoot");
+                                    continue;
+                                }
+                            };
+
+                            let damage = combat.ranged_damage;
+                            let health = healths.get_mut(module_id).unwrap();
+                            health.health = (health.health - damage).max(0);
+                            events.push(Event::ModuleDamaged { module_id, damage });
+                            if let Some(energy) = energies.get_mut(e) {
+                                energy.spend();
+                            }
+                            continue;
+                        }
+                    };
+
+                    let damage = combat.ranged_damage;
+                    events.push(combat::hit(&mut stats, e, target_id, damage, true));
+                    if let Some(energy) = energies.get_mut(e) {
+                        energy.spend();
+                    }
+                }
+                Some(Action::Drop(index)) => {
+                    let item_id = inventories
+                        .get_mut(e)
+                        .filter(|inventory| index < inventory.items.len())
+                        .map(|inventory| inventory.items.remove(index));
+                    match item_id {
+                        Some(item_id) => {
+                            dropped.push((item_id, pos.clone()));
+                            events.push(Event::Dropped {
+                                actor_id: e,
+                                item_id,
+                            });
+                            if let Some(energy) = energies.get_mut(e) {
+                                energy.spend();
+                            }
+                        }
+                        None => events.push(Event::ActionFailed {
+                            actor_id: e,
+                            reason: "nothing to drop".to_string(),
+                        }),
+                    }
+                }
+                Some(Action::Use(index)) => {
+                    let item_id = unwrap_or_continue!(inventories
+                        .get(e)
+                        .and_then(|inventory| inventory.items.get(index))
+                        .cloned());
+                    let item = unwrap_or_continue!(items.get(item_id));
+                    let used = match stats.get_mut(e) {
+                        Some(stats) => inventory::use_item(item, stats),
+                        None => false,
+                    };
+                    if !used {
+                        events.push(Event::ActionFailed {
+                            actor_id: e,
+                            reason: format!("can not use {}", item.name()),
+                        });
+                        continue;
+                    }
+
+                    // consumed
+                    if let Some(inventory) = inventories.get_mut(e) {
+                        inventory.items.remove(index);
+                    }
+                    events.push(Event::ItemUsed {
+                        actor_id: e,
+                        kind: item.kind,
+                    });
+                    entities.delete(item_id).unwrap();
+                    if let Some(energy) = energies.get_mut(e) {
+                        energy.spend();
+                    }
+                }
                 Some(action) => {
                     let objects_at = find_objects_at(&entities, &objects, &positions, pos);
-                    let available_actions = get_available_actions(&objects_at);
-                    match available_actions.into_iter().find(|i| i == &action) {
-                        Some(Action::Interact) => {
+                    let objects_around = find_objects_around(&entities, &objects, &positions, pos);
+                    let damaged_hull_id =
+                        GridRef::find_gmap_mut(&mut grids, pos.grid_id).and_then(|area| {
+                            damage::find_damaged_hull_around(area, &ships, &pos.point)
+                        });
+                    let available_actions = get_available_actions(
+                        &objects_at,
+                        &objects_around,
+                        &positions,
+                        &doors,
+                        &healths,
+                        damaged_hull_id,
+                    );
+                    let action = match available_actions.into_iter().find(|i| i == &action) {
+                        Some(action) => action,
+                        None => {
+                            events.push(Event::ActionFailed {
+                                actor_id: e,
+                                reason: format!("can not {:?} here", action).to_lowercase(),
+                            });
+                            continue;
+                        }
+                    };
+                    if let Some(energy) = energies.get_mut(e) {
+                        energy.spend();
+                    }
+
+                    match action {
+                        Action::Interact => {
                             *window = Window::Cockpit;
                         }
+                        Action::Open(point) => {
+                            let door_pos = Position {
+                                grid_id: pos.grid_id,
+                                point,
+                            };
+                            let door_id = unwrap_or_continue!(find_door_at(
+                                &objects_around,
+                                &positions,
+                                &door_pos
+                            ));
+                            let door = unwrap_or_continue!(doors.get_mut(door_id));
+                            if door.state == DoorState::Locked {
+                                events.push(Event::ActionFailed {
+                                    actor_id: e,
+                                    reason: "door is locked".to_string(),
+                                });
+                                continue;
+                            }
+                            set_door_state(&mut grids, door, &door_pos, DoorState::Open);
+                            events.push(Event::DoorOpened {
+                                actor_id: e,
+                                door_id,
+                            });
+                        }
+                        Action::Close(point) => {
+                            let door_pos = Position {
+                                grid_id: pos.grid_id,
+                                point,
+                            };
+                            if occupied.contains(&door_pos) {
+                                events.push(Event::ActionFailed {
+                                    actor_id: e,
+                                    reason: "door is blocked".to_string(),
+                                });
+                                continue;
+                            }
+                            let door_id = unwrap_or_continue!(find_door_at(
+                                &objects_around,
+                                &positions,
+                                &door_pos
+                            ));
+                            let door = unwrap_or_continue!(doors.get_mut(door_id));
+                            set_door_state(&mut grids, door, &door_pos, DoorState::Closed);
+                            events.push(Event::DoorClosed {
+                                actor_id: e,
+                                door_id,
+                            });
+                        }
+                        Action::CycleAirlock => {
+                            let area = unwrap_or_continue!(GridRef::find_gmap_mut(
+                                &mut grids,
+                                pos.grid_id
+                            ));
+                            match atmosphere::cycle_airlock(area, &pos.point) {
+                                Some(air) => events.push(Event::AirlockCycled { actor_id: e, air }),
+                                None => events.push(Event::ActionFailed {
+                                    actor_id: e,
+                                    reason: "airlock is not sealed".to_string(),
+                                }),
+                            }
+                        }
+                        Action::Pickup => {
+                            let item_id = objects_at
+                                .iter()
+                                .filter(|(id, kind)| {
+                                    *kind == ObjectsType::Item && !picked.contains(id)
+                                })
+                                .map(|(id, _)| *id)
+                                .next();
+                            let item_id = unwrap_or_continue!(item_id);
+
+                            picked.push(item_id);
+                            match inventories.get_mut(e) {
+                                Some(inventory) => inventory.items.push(item_id),
+                                None => {
+                                    inventories
+                                        .insert(
+                                            e,
+                                            Inventory {
+                                                items: vec![item_id],
+                                            },
+                                        )
+                                        .unwrap();
+                                }
+                            }
+                            events.push(Event::PickedUp {
+                                actor_id: e,
+                                item_id,
+                            });
+                        }
+                        Action::Descend | Action::Ascend => {
+                            let down = action == Action::Descend;
+                            let target = unwrap_or_continue!(objects_at
+                                .iter()
+                                .find(|(_, kind)| *kind == ObjectsType::Stairs { down })
+                                .and_then(|(id, _)| stairs.get(*id)));
+
+                            transfers.push((
+                                e,
+                                Position {
+                                    grid_id: target.target_id,
+                                    point: target.target_pos,
+                                },
+                            ));
+                            events.push(Event::ClimbedStairs { actor_id: e, down });
+                        }
+                        Action::Repair => {
+                            // damaged modules first, then the hull breaches and last the hull walls
+                            let targets = objects_at.iter().chain(objects_around.iter());
+                            let module = targets.clone().find(|(id, _)| {
+                                healths.get(*id).map(|h| h.is_damaged()).unwrap_or(false)
+                            });
+                            if let Some((module_id, kind)) = module {
+                                let health = healths.get_mut(*module_id).unwrap();
+                                health.health =
+                                    (health.health + REPAIR_AMOUNT).min(health.max_health);
+                                events.push(Event::Repaired {
+                                    actor_id: e,
+                                    kind: *kind,
+                                });
+                                continue;
+                            }
+
+                            let breach_id = targets
+                                .filter(|(_, kind)| *kind == ObjectsType::Breach)
+                                .map(|(id, _)| *id)
+                                .next();
+                            let breach_id = match (breach_id, damaged_hull_id) {
+                                (Some(breach_id), _) => breach_id,
+                                (None, Some(ship_id)) => {
+                                    let ship = unwrap_or_continue!(ships.get_mut(ship_id));
+                                    ship.hull = (ship.hull + REPAIR_AMOUNT).min(ship::MAX_HULL);
+                                    events.push(Event::HullRepaired {
+                                        actor_id: e,
+                                        ship_id,
+                                    });
+                                    continue;
+                                }
+                                (None, None) => continue,
+                            };
+                            let breach_pos = unwrap_or_continue!(positions.get(breach_id)).clone();
+                            let area = unwrap_or_continue!(GridRef::find_gmap_mut(
+                                &mut grids,
+                                breach_pos.grid_id
+                            ));
+                            let mut cell = area
+                                .get_grid()
+                                .get_at(&breach_pos.point)
+                                .copied()
+                                .unwrap_or_default();
+                            cell.tile = Tile::Wall;
+                            area.set_cell_at(&breach_pos.point, cell);
+
+                            let ship_id = area.get_layer_entity_at(&breach_pos.point);
+                            if let Some(ship) = ship_id.and_then(|id| ships.get_mut(id)) {
+                                ship.hull = (ship.hull + REPAIR_AMOUNT).min(ship::MAX_HULL);
+                            }
+                            entities.delete(breach_id).unwrap();
+                            events.push(Event::Repaired {
+                                actor_id: e,
+                                kind: ObjectsType::Breach,
+                            });
+                        }
                         _ => {}
                     }
                 }
                 _ => {}
             }
         }
+
+        for (e, point) in moves {
+            if let Some(pos) = positions.get_mut(e) {
+                pos.point = point;
+            }
+        }
+
+        // what was known of the previous grid is not valid anymore
+        for (e, pos) in transfers {
+            positions.insert(e, pos).unwrap();
+            if let Some(viewshed) = viewsheds.get_mut(e) {
+                viewshed.visible_tiles.clear();
+                viewshed.know_tiles.clear();
+            }
+        }
+
+        // carried items have no position
+        for item_id in picked {
+            positions.remove(item_id);
+        }
+        for (item_id, pos) in dropped {
+            positions.insert(item_id, pos).unwrap();
+        }
     }
 }
+
+/// door between the objects around placed at the position
+fn find_door_at(
+    objects_around: &[(Entity, ObjectsType)],
+    positions: &WriteStorage<Position>,
+    door_pos: &Position,
+) -> Option<Entity> {
+    objects_around
+        .iter()
+        .find(|(id, kind)| {
+            matches!(kind, ObjectsType::Door { .. }) && positions.get(*id) == Some(door_pos)
+        })
+        .map(|(id, _)| *id)
+}
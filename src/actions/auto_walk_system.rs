@@ -0,0 +1,189 @@
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::Energy;
+use crate::gridref::GridRef;
+use crate::models::{Position, Stats, P2};
+use crate::unwrap_or_continue;
+use crate::view::Viewshed;
+use specs::prelude::*;
+use specs_derive::*;
+
+/// Walk one step each time the entity has energy until the end of the path. It is not persisted
+/// in the save.
+#[derive(Component, Debug, Clone)]
+pub struct AutoWalk {
+    /// next cells to walk, in order
+    pub path: Vec<P2>,
+    /// living entities in view when the walk started, any other coming into view stop the walk
+    pub seen: Option<Vec<Entity>>,
+}
+
+impl AutoWalk {
+    pub fn new(path: Vec<P2>) -> Self {
+        AutoWalk { path, seen: None }
+    }
+}
+
+pub struct AutoWalkSystem {}
+
+impl<'a> System<'a> for AutoWalkSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, AutoWalk>,
+        WriteStorage<'a, Position>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Stats>,
+        ReadStorage<'a, GridRef>,
+        WriteStorage<'a, Energy>,
+        Write<'a, EntitiesEvents>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut walks,
+            mut positions,
+            viewsheds,
+            stats,
+            grids,
+            mut energies,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        let living = (&entities, &stats, &positions)
+            .join()
+            .filter(|(_, stats, _)| !stats.is_dead())
+            .map(|(e, _, pos)| (e, pos.clone()))
+            .collect::<Vec<_>>();
+
+        let mut finished = vec![];
+
+        for (e, walk, pos, viewshed) in (&entities, &mut walks, &mut positions, &viewsheds).join() {
+            // stop when something new come into view
+            let visible = living
+                .iter()
+                .filter(|(id, other)| {
+                    *id != e
+                        && other.grid_id == pos.grid_id
+                        && viewshed
+                            .visible_tiles
+                            .iter()
+                            .any(|p| p.x == other.point.x && p.y == other.point.y)
+                })
+                .map(|(id, _)| *id)
+                .collect::<Vec<_>>();
+
+            match &walk.seen {
+                None => walk.seen = Some(visible),
+                Some(seen) => {
+                    if let Some(seen_id) = visible.iter().find(|id| !seen.contains(id)) {
+                        events.push(Event::WalkInterrupted {
+                            actor_id: e,
+                            seen_id: *seen_id,
+                        });
+                        finished.push(e);
+                        continue;
+                    }
+                }
+            }
+
+            if let Some(energy) = energies.get(e) {
+                if !energy.is_ready() {
+                    continue;
+                }
+            }
+
+            let next = match walk.path.first() {
+                Some(next) => *next,
+                None => {
+                    finished.push(e);
+                    continue;
+                }
+            };
+
+            // path can be blocked by doors closed after it was computed
+            let area = unwrap_or_continue!(GridRef::find_area(&grids, pos.grid_id));
+            let is_neighbour =
+                (next.x - pos.point.x).abs() <= 1 && (next.y - pos.point.y).abs() <= 1;
+            if !is_neighbour || area.move_cost_at(&next).is_none() {
+                events.push(Event::ActionFailed {
+                    actor_id: e,
+                    reason: "path is blocked".to_string(),
+                });
+                finished.push(e);
+                continue;
+            }
+
+            pos.point = next;
+            walk.path.remove(0);
+            if let Some(energy) = energies.get_mut(e) {
+                energy.spend();
+            }
+
+            if walk.path.is_empty() {
+                finished.push(e);
+            }
+        }
+
+        for e in finished {
+            walks.remove(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::Event;
+    use crate::models::{Player, Position, Stats, P2};
+    use crate::test_utils::{get_avatar_position, new_state_basic_scenery};
+
+    #[test]
+    fn test_walk_to_should_reach_target_over_many_ticks() {
+        let (mut sim, _) = new_state_basic_scenery();
+        let target = P2::new(11, 7);
+
+        assert!(sim.walk_to(target));
+        sim.tick();
+        assert_ne!(target, get_avatar_position(&sim).point);
+        let ticks = sim.run_until(100, |ecs| {
+            let avatar_id = ecs.fetch::<Player>().get_avatar_id();
+            ecs.read_storage::<Position>().get(avatar_id).unwrap().point == target
+        });
+        assert!(ticks.is_some(), "avatar never reach the target");
+
+        // closed doors block the path
+        assert!(!sim.walk_to(P2::new(14, 8)));
+        sim.tick();
+        assert!(matches!(
+            sim.events().last(),
+            Some(Event::ActionFailed { .. })
+        ));
+    }
+
+    #[test]
+    fn test_walk_to_should_stop_when_someone_come_into_view() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let target = P2::new(11, 7);
+
+        assert!(sim.walk_to(target));
+        sim.tick();
+
+        let stranger_id = sim
+            .ecs_mut()
+            .create_entity()
+            .with(Position {
+                grid_id: scenery.ships["ship"],
+                point: P2::new(7, 9),
+            })
+            .with(Stats::new(10, 10))
+            .build();
+
+        sim.run(100);
+        assert_ne!(target, get_avatar_position(&sim).point);
+        assert!(sim.events().contains(&Event::WalkInterrupted {
+            actor_id: sim.get_avatar_id(),
+            seen_id: stranger_id,
+        }));
+    }
+}
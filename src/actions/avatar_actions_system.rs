@@ -1,7 +1,9 @@
 use crate::actions::{get_available_actions, EntityActions};
 
-use crate::models::{ObjectsType, Player, Position};
-use crate::utils::find_objects_at;
+use crate::gridref::GridRef;
+use crate::models::{Door, ObjectsType, Player, Position};
+use crate::ship::{damage, ModuleHealth, Ship};
+use crate::utils::{find_objects_around, find_objects_at};
 
 use specs::prelude::*;
 
@@ -14,13 +16,31 @@ impl<'a> System<'a> for FindAvatarActionsSystem {
         WriteStorage<'a, EntityActions>,
         ReadStorage<'a, ObjectsType>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Door>,
+        ReadStorage<'a, ModuleHealth>,
+        ReadStorage<'a, GridRef>,
+        ReadStorage<'a, Ship>,
     );
 
-    fn run(&mut self, (entities, avatar, mut actions, objects, positions): Self::SystemData) {
+    fn run(
+        &mut self,
+        (entities, avatar, mut actions, objects, positions, doors, healths, grids, ships): Self::SystemData,
+    ) {
         for (_, actions, pos) in (avatar.get_avatarset(), &mut actions, &positions).join() {
             let objects_at = find_objects_at(&entities, &objects, &positions, pos);
+            let objects_around = find_objects_around(&entities, &objects, &positions, pos);
 
-            actions.actions = get_available_actions(&objects_at);
+            let damaged_hull_id = GridRef::find_area(&grids, pos.grid_id)
+                .and_then(|area| damage::find_damaged_hull_around(area, &ships, &pos.point));
+
+            actions.actions = get_available_actions(
+                &objects_at,
+                &objects_around,
+                &positions,
+                &doors,
+                &healths,
+                damaged_hull_id,
+            );
         }
     }
 }
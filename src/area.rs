@@ -1,17 +1,34 @@
 use super::models::*;
+use crate::atmosphere::Air;
 use crate::commons;
 use crate::commons::grid::{Coord, NGrid};
 use crate::commons::v2i::V2I;
+use crate::save::{entities_from, entities_into, EntityNotSaved};
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
 use specs_derive::*;
 
+/// path cost of a closed door, it takes a action to open it before walk in
+const DOOR_PATH_COST: f32 = 2.0;
+
 #[derive(PartialEq, Copy, Clone, Debug, Deserialize, Serialize)]
 pub enum Tile {
     Ground,
     Floor,
     Wall,
     Space,
+    /// mountain rock, blocks movement and sight
+    Rock,
+    Water,
+    /// trees and bushes, slow to walk through and block sight
+    Vegetation,
+    /// debris of destroyed walls
+    Rubble,
+    // cell with a door object, the tile is kept in sync with the Door component state
+    Door {
+        open: bool,
+    },
     // works like None
     OutOfMap,
 }
@@ -20,11 +37,31 @@ impl Tile {
     pub fn is_opaque(&self) -> bool {
         match self {
             Tile::Wall => true,
+            Tile::Rock => true,
+            Tile::Vegetation => true,
+            Tile::Door { open: false } => true,
             Tile::OutOfMap => true,
             _ => false,
         }
     }
 
+    /// cost to walk into a cell with this tile, None if it can not be walked
+    pub fn move_cost(&self) -> Option<f32> {
+        match self {
+            Tile::Floor => Some(1.0),
+            Tile::Door { open: true } => Some(1.0),
+            Tile::Ground => Some(1.2),
+            Tile::Rubble => Some(1.5),
+            Tile::Vegetation => Some(2.0),
+            _ => None,
+        }
+    }
+
+    /// true when an actor can step into it, space can be walked as there is nothing to block it
+    pub fn is_walkable(&self) -> bool {
+        self.move_cost().is_some() || *self == Tile::Space
+    }
+
     pub fn is_nothing(&self) -> bool {
         match self {
             Tile::Space => true,
@@ -49,11 +86,17 @@ pub struct Area {
     grid: NGrid<Cell>,
     /// entities that own on each grid in this map
     layers: Vec<Entity>,
+    /// the shape changed since the last time the air flowed through the compartments
+    air_dirty: bool,
 }
 
 impl Area {
     pub fn new(grid: NGrid<Cell>, layers: Vec<Entity>) -> Self {
-        Self { grid, layers }
+        Self {
+            grid,
+            layers,
+            air_dirty: true,
+        }
     }
     pub fn get_layer_entity_at(&self, coord: &Coord) -> Option<Entity> {
         self.grid
@@ -63,18 +106,120 @@ impl Area {
     pub fn get_grid(&self) -> &NGrid<Cell> {
         &self.grid
     }
+    /// changing a tile mark the area air as dirty, changing only the air does not
+    pub fn set_cell_at(&mut self, coord: &Coord, cell: Cell) -> Option<Cell> {
+        let previous = self.grid.set_at(coord, cell);
+        if previous.map(|c| c.tile) != Some(cell.tile) {
+            self.air_dirty = true;
+        }
+        previous
+    }
+
+    pub fn is_air_dirty(&self) -> bool {
+        self.air_dirty
+    }
+
+    /// return if the air was dirty, clearing it
+    pub fn take_air_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.air_dirty, false)
+    }
+    /// true when an actor can step into the cell at coord
+    pub fn is_walkable_at(&self, coord: &Coord) -> bool {
+        self.grid
+            .get_at(coord)
+            .map(|cell| cell.tile.is_walkable())
+            .unwrap_or(false)
+    }
+
+    /// move cost of the top layer cell at coord, None if it can not be walked
+    pub fn move_cost_at(&self, coord: &Coord) -> Option<f32> {
+        self.grid
+            .get_at(coord)
+            .and_then(|cell| cell.tile.move_cost())
+    }
+
+    /// walkable path from one cell to other, excluding the starting cell
+    pub fn find_path(&self, from: &Coord, to: &Coord) -> Option<Vec<Coord>> {
+        self.find_path_with(from, to, false)
+    }
+
+    /// same as find_path, but closed doors are considered walkable as they can be opened on the way
+    pub fn find_path_through_doors(&self, from: &Coord, to: &Coord) -> Option<Vec<Coord>> {
+        self.find_path_with(from, to, true)
+    }
+
+    fn path_cost_at(&self, coord: &Coord, through_doors: bool) -> Option<f32> {
+        match self.grid.get_at(coord).map(|cell| cell.tile) {
+            Some(Tile::Door { open: false }) if through_doors => Some(DOOR_PATH_COST),
+            Some(tile) => tile.move_cost(),
+            None => None,
+        }
+    }
+
+    fn find_path_with(&self, from: &Coord, to: &Coord, through_doors: bool) -> Option<Vec<Coord>> {
+        if !self.grid.is_valid(from) || self.path_cost_at(to, through_doors).is_none() {
+            return None;
+        }
+
+        let w = self.grid.get_width();
+        let start = commons::grid::coords_to_index(w, *from) as usize;
+        let end = commons::grid::coords_to_index(w, *to) as usize;
+        let map = PathMap {
+            area: self,
+            through_doors,
+        };
+        let path = rltk::a_star_search(start, end, &map);
+        if !path.success {
+            return None;
+        }
+
+        Some(
+            path.steps
+                .into_iter()
+                .skip(1)
+                .map(|index| commons::grid::index_to_coord(w, index as i32))
+                .collect(),
+        )
+    }
+
+    /// true when there is no opaque cell between both cells, the cells itself are not checked
+    pub fn has_line_of_sight(&self, from: &Coord, to: &Coord) -> bool {
+        rltk::line2d(
+            rltk::LineAlg::Bresenham,
+            rltk::Point::new(from.x, from.y),
+            rltk::Point::new(to.x, to.y),
+        )
+        .into_iter()
+        .map(|p| Coord::new(p.x, p.y))
+        .filter(|p| p != from && p != to)
+        .all(|p| {
+            self.grid
+                .get_at(&p)
+                .map(|cell| !cell.tile.is_opaque())
+                .unwrap_or(false)
+        })
+    }
+
     pub fn merge(&mut self, gmap: Area, pos: &P2) {
         self.grid.merge(gmap.grid, pos);
         self.layers.extend(gmap.layers.into_iter());
+        self.air_dirty = true;
     }
 
     pub fn get_layers(&self) -> &Vec<Entity> {
         &self.layers
     }
 
+    /// position of the grid owned by layer_id inside this area
+    pub fn get_layer_pos(&self, layer_id: Entity) -> Option<Coord> {
+        let index = self.layers.iter().position(|i| *i == layer_id)?;
+        self.grid.get_pos(index)
+    }
+
     pub fn remove_layer(&mut self, entity: Entity) -> Option<(Area, Coord)> {
         let index = self.layers.iter().position(|i| *i == entity)?;
         self.layers.remove(index);
+        self.air_dirty = true;
 
         let pgrid = self.grid.remove(index);
         let gmap = Area::new(NGrid::from_grid(pgrid.grid), vec![entity]);
@@ -82,6 +227,37 @@ impl Area {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct AreaData<M> {
+    grid: NGrid<Cell>,
+    layers: Vec<M>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Area
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = AreaData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(AreaData {
+            grid: self.grid.clone(),
+            layers: entities_into(&self.layers, ids)?,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Area::new(data.grid, entities_from(data.layers, ids)?))
+    }
+}
+
 impl rltk::Algorithm2D for Area {
     fn dimensions(&self) -> rltk::Point {
         let size = self.grid.get_size();
@@ -102,6 +278,93 @@ impl rltk::BaseMap for Area {
             .map(|i| i.tile.is_opaque())
             .unwrap_or(true)
     }
+
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.available_exits(idx, false)
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.pathing_distance(idx1, idx2)
+    }
+}
+
+impl Area {
+    /// walkable neighbours, diagonals are only available when both sides are walkable too
+    fn available_exits(
+        &self,
+        idx: usize,
+        through_doors: bool,
+    ) -> rltk::SmallVec<[(usize, f32); 10]> {
+        let w = self.grid.get_width();
+        let c = commons::grid::index_to_coord(w, idx as i32);
+        let mut exits = rltk::SmallVec::new();
+
+        let walkable = |dx: i32, dy: i32| {
+            let next = c.translate(dx, dy);
+            if self.grid.is_valid(&next) {
+                self.path_cost_at(&next, through_doors)
+            } else {
+                None
+            }
+        };
+
+        for (dx, dy) in &[(0, -1), (1, 0), (0, 1), (-1, 0)] {
+            if let Some(cost) = walkable(*dx, *dy) {
+                let index = commons::grid::coords_to_index(w, c.translate(*dx, *dy));
+                exits.push((index as usize, cost));
+            }
+        }
+
+        for (dx, dy) in &[(-1, -1), (1, -1), (1, 1), (-1, 1)] {
+            if walkable(*dx, 0).is_none() || walkable(0, *dy).is_none() {
+                continue;
+            }
+            if let Some(cost) = walkable(*dx, *dy) {
+                let index = commons::grid::coords_to_index(w, c.translate(*dx, *dy));
+                exits.push((index as usize, cost * 1.45));
+            }
+        }
+
+        exits
+    }
+
+    fn pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        let w = self.grid.get_width();
+        let p1 = commons::grid::index_to_coord(w, idx1 as i32);
+        let p2 = commons::grid::index_to_coord(w, idx2 as i32);
+        rltk::DistanceAlg::Pythagoras
+            .distance2d(rltk::Point::new(p1.x, p1.y), rltk::Point::new(p2.x, p2.y))
+    }
+}
+
+/// Area view used by the path finding to decide if closed doors can be walked
+struct PathMap<'a> {
+    area: &'a Area,
+    through_doors: bool,
+}
+
+impl<'a> rltk::Algorithm2D for PathMap<'a> {
+    fn dimensions(&self) -> rltk::Point {
+        self.area.dimensions()
+    }
+
+    fn in_bounds(&self, pos: rltk::Point) -> bool {
+        self.area.in_bounds(pos)
+    }
+}
+
+impl<'a> rltk::BaseMap for PathMap<'a> {
+    fn is_opaque(&self, idx: usize) -> bool {
+        rltk::BaseMap::is_opaque(self.area, idx)
+    }
+
+    fn get_available_exits(&self, idx: usize) -> rltk::SmallVec<[(usize, f32); 10]> {
+        self.area.available_exits(idx, self.through_doors)
+    }
+
+    fn get_pathing_distance(&self, idx1: usize, idx2: usize) -> f32 {
+        self.area.pathing_distance(idx1, idx2)
+    }
 }
 
 struct ViewGrid<'a> {
@@ -118,17 +381,31 @@ impl<'a> ViewGrid<'a> {
     }
 }
 
-pub const EMPTY_CELL: Cell = Cell { tile: Tile::Space };
+pub const EMPTY_CELL: Cell = Cell {
+    tile: Tile::Space,
+    air: Air::VACUUM,
+};
 
-#[derive(Component, Debug, Clone, Default, Copy)]
+#[derive(Component, Debug, Clone, Default, Copy, Serialize, Deserialize)]
 pub struct Cell {
     pub tile: Tile,
+    #[serde(default)]
+    pub air: Air,
     // pub objects? // how will return ref?
 }
 
 impl Cell {
+    /// interior tiles start pressurized, any other start with vacuum
     pub fn new(tile: Tile) -> Self {
-        Cell { tile }
+        let air = match tile {
+            Tile::Floor | Tile::Door { .. } => Air::STANDARD,
+            _ => Air::VACUUM,
+        };
+        Cell { tile, air }
+    }
+
+    pub fn with_air(tile: Tile, air: Air) -> Self {
+        Cell { tile, air }
     }
 }
 
@@ -0,0 +1,436 @@
+use crate::area::{Area, Cell, Tile};
+use crate::commons::grid::{get_4_neighbours, Coord, NGrid};
+use crate::game_time::GameTime;
+use crate::gridref::GridRef;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// minimum pressure where a breathable gas can be used
+pub const MIN_BREATHABLE_PRESSURE: f32 = 0.5;
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Gas {
+    Breathable,
+    Toxic,
+}
+
+/// Air of a cell, pressure is 0.0 for vacuum and 1.0 for standard pressure
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Air {
+    pub pressure: f32,
+    pub gas: Gas,
+}
+
+impl Air {
+    pub const VACUUM: Air = Air {
+        pressure: 0.0,
+        gas: Gas::Breathable,
+    };
+
+    pub const STANDARD: Air = Air {
+        pressure: 1.0,
+        gas: Gas::Breathable,
+    };
+
+    pub fn is_vacuum(&self) -> bool {
+        self.pressure <= 0.0
+    }
+
+    pub fn is_breathable(&self) -> bool {
+        self.gas == Gas::Breathable && self.pressure >= MIN_BREATHABLE_PRESSURE
+    }
+}
+
+impl Default for Air {
+    fn default() -> Self {
+        Air::VACUUM
+    }
+}
+
+/// How a tile interact with air flow
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum Flow {
+    /// walls and closed doors
+    Blocked,
+    /// cells that share the air of its compartment
+    Inside,
+    /// cells that never change its air, like planet ground or space
+    Outside,
+}
+
+fn flow_of(tile: Tile) -> Flow {
+    match tile {
+        Tile::Wall | Tile::Rock | Tile::Door { open: false } => Flow::Blocked,
+        Tile::Floor | Tile::Door { open: true } => Flow::Inside,
+        Tile::Ground
+        | Tile::Water
+        | Tile::Vegetation
+        | Tile::Rubble
+        | Tile::Space
+        | Tile::OutOfMap => Flow::Outside,
+    }
+}
+
+/// Group of connected inside cells, bounded by walls and closed doors
+#[derive(Debug, Clone)]
+pub struct Compartment {
+    pub cells: Vec<Coord>,
+    /// when the compartment is connected to outside, the air it is exposed, the lowest pressure
+    /// one when many
+    pub exposed_to: Option<Air>,
+}
+
+impl Compartment {
+    pub fn is_sealed(&self) -> bool {
+        self.exposed_to.is_none()
+    }
+
+    /// air all cells should have after it flow through the compartment
+    pub fn resulting_air(&self, grid: &NGrid<Cell>) -> Air {
+        if let Some(air) = self.exposed_to {
+            return air;
+        }
+
+        let mut total = 0.0;
+        let mut gas = Gas::Breathable;
+        for coord in &self.cells {
+            let air = grid.get_at(coord).map(|cell| cell.air).unwrap_or_default();
+            total += air.pressure;
+            if air.gas == Gas::Toxic && !air.is_vacuum() {
+                gas = Gas::Toxic;
+            }
+        }
+
+        Air {
+            pressure: total / self.cells.len() as f32,
+            gas,
+        }
+    }
+}
+
+/// flood fill the compartment that contains the coord, return None if coord is not a inside cell
+pub fn find_compartment_at(grid: &NGrid<Cell>, coord: &Coord) -> Option<Compartment> {
+    flood_fill(grid, coord, &mut HashSet::new())
+}
+
+pub fn find_compartments(grid: &NGrid<Cell>) -> Vec<Compartment> {
+    let mut visited = HashSet::new();
+    let mut list = vec![];
+
+    for y in 0..grid.get_height() {
+        for x in 0..grid.get_width() {
+            let coord = Coord::new(x, y);
+            if visited.contains(&coord) {
+                continue;
+            }
+            if let Some(compartment) = flood_fill(grid, &coord, &mut visited) {
+                list.push(compartment);
+            }
+        }
+    }
+
+    list
+}
+
+fn flood_fill(
+    grid: &NGrid<Cell>,
+    start: &Coord,
+    visited: &mut HashSet<Coord>,
+) -> Option<Compartment> {
+    match grid.get_at(start) {
+        Some(cell) if flow_of(cell.tile) == Flow::Inside => {}
+        _ => return None,
+    }
+
+    let mut cells = vec![];
+    let mut exposed_to: Option<Air> = None;
+    let mut queue = vec![*start];
+    visited.insert(*start);
+
+    while let Some(coord) = queue.pop() {
+        cells.push(coord);
+
+        for (_, next) in get_4_neighbours(&coord) {
+            // anything out of the grid is considered space
+            let cell = grid.get_at(&next).copied().unwrap_or_default();
+            match flow_of(cell.tile) {
+                Flow::Blocked => {}
+                Flow::Outside => match exposed_to {
+                    Some(air) if air.pressure <= cell.air.pressure => {}
+                    _ => exposed_to = Some(cell.air),
+                },
+                Flow::Inside => {
+                    if visited.insert(next) {
+                        queue.push(next);
+                    }
+                }
+            }
+        }
+    }
+
+    Some(Compartment { cells, exposed_to })
+}
+
+fn set_air(area: &mut Area, cells: &[Coord], air: Air) {
+    for coord in cells {
+        let mut cell = match area.get_grid().get_at(coord) {
+            Some(cell) if cell.air != air => *cell,
+            _ => continue,
+        };
+        cell.air = air;
+        area.set_cell_at(coord, cell);
+    }
+}
+
+/// flow the air through all compartments of the area. Exposed compartments get the outside air
+/// while sealed ones get its pressure equalized
+pub fn update_area(area: &mut Area) {
+    for compartment in find_compartments(area.get_grid()) {
+        let air = compartment.resulting_air(area.get_grid());
+        set_air(area, &compartment.cells, air);
+    }
+}
+
+/// pump air in or out of the sealed compartment at the coord, return the new air or None if the
+/// compartment is not sealed
+pub fn cycle_airlock(area: &mut Area, coord: &Coord) -> Option<Air> {
+    let compartment = find_compartment_at(area.get_grid(), coord)?;
+    if !compartment.is_sealed() {
+        return None;
+    }
+
+    let air = if compartment.resulting_air(area.get_grid()).is_breathable() {
+        Air::VACUUM
+    } else {
+        Air::STANDARD
+    };
+    set_air(area, &compartment.cells, air);
+
+    Some(air)
+}
+
+/// Flow the air of the areas which shape changed since the last update, like a door opened or a
+/// ship landed
+pub struct AtmosphereSystem {}
+
+impl<'a> System<'a> for AtmosphereSystem {
+    type SystemData = (WriteStorage<'a, GridRef>, ReadExpect<'a, GameTime>);
+
+    fn run(&mut self, (mut grids, time): Self::SystemData) {
+        if time.elapsed == 0 {
+            return;
+        }
+
+        for grid in (&mut grids).join() {
+            if let GridRef::GMap(area) = grid {
+                if area.take_air_dirty() {
+                    update_area(area);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Action;
+    use crate::commons::grid::Grid;
+    use crate::commons::grid_string;
+    use crate::models::{Location, P2};
+    use crate::ship::Command;
+    use crate::test_utils::{
+        get_air_at, get_avatar_position, new_state_default_scenery, walk, walk_into_airlock,
+    };
+
+    /// '#' wall, '.' floor, '+' closed door, '\'' open door, '_' space, ',' ground
+    fn new_area(map: &str) -> Area {
+        let grid: Grid<Cell> = grid_string::parse_map(
+            |ch| {
+                let tile = match ch {
+                    '#' => Tile::Wall,
+                    '.' => Tile::Floor,
+                    '+' => Tile::Door { open: false },
+                    '\'' => Tile::Door { open: true },
+                    '_' => Tile::Space,
+                    ',' => Tile::Ground,
+                    _ => return None,
+                };
+                let mut cell = Cell::new(tile);
+                if tile == Tile::Ground {
+                    cell.air = Air::STANDARD;
+                }
+                Some(cell)
+            },
+            map,
+        )
+        .unwrap();
+
+        let entity = World::new().entities().create();
+        Area::new(NGrid::from_grid(grid), vec![entity])
+    }
+
+    fn air_at(area: &Area, x: i32, y: i32) -> Air {
+        area.get_grid().get_at(&Coord::new(x, y)).unwrap().air
+    }
+
+    fn set_tile(area: &mut Area, x: i32, y: i32, tile: Tile) {
+        let coord = Coord::new(x, y);
+        let mut cell = *area.get_grid().get_at(&coord).unwrap();
+        cell.tile = tile;
+        area.set_cell_at(&coord, cell);
+    }
+
+    #[test]
+    fn test_atmosphere_sealed_compartments_should_keep_pressure_until_vented() {
+        let mut area = new_area(
+            r"_______
+_#####_
+_#.+.+_
+_#####_
+_______",
+        );
+
+        update_area(&mut area);
+        assert_eq!(Air::STANDARD, air_at(&area, 2, 2));
+        assert_eq!(Air::STANDARD, air_at(&area, 4, 2));
+
+        // opening the outer door only vent the outer room
+        set_tile(&mut area, 5, 2, Tile::Door { open: true });
+        update_area(&mut area);
+        assert_eq!(Air::STANDARD, air_at(&area, 2, 2));
+        assert!(air_at(&area, 4, 2).is_vacuum());
+
+        // opening the inner door vent everything
+        set_tile(&mut area, 3, 2, Tile::Door { open: true });
+        update_area(&mut area);
+        assert!(air_at(&area, 2, 2).is_vacuum());
+    }
+
+    #[test]
+    fn test_atmosphere_area_should_be_dirty_only_when_tiles_change() {
+        let mut area = new_area(
+            r"#####
+#.+.#
+#####",
+        );
+        assert!(area.take_air_dirty());
+        assert!(!area.take_air_dirty());
+
+        // pumping air does not change the compartments
+        let mut cell = *area.get_grid().get_at(&Coord::new(1, 1)).unwrap();
+        cell.air = Air::VACUUM;
+        area.set_cell_at(&Coord::new(1, 1), cell);
+        assert!(!area.is_air_dirty());
+
+        set_tile(&mut area, 2, 1, Tile::Door { open: true });
+        assert!(area.take_air_dirty());
+    }
+
+    #[test]
+    fn test_atmosphere_should_equalize_sealed_compartments() {
+        let mut area = new_area(
+            r"#######
+#...+.#
+#######",
+        );
+        let mut cell = *area.get_grid().get_at(&Coord::new(5, 1)).unwrap();
+        cell.air = Air::VACUUM;
+        area.set_cell_at(&Coord::new(5, 1), cell);
+
+        set_tile(&mut area, 4, 1, Tile::Door { open: true });
+        update_area(&mut area);
+
+        let air = air_at(&area, 1, 1);
+        assert_eq!(air, air_at(&area, 5, 1));
+        assert!((air.pressure - 0.8).abs() < 0.001);
+    }
+
+    #[test]
+    fn test_atmosphere_cycle_airlock() {
+        let mut area = new_area(
+            r"#######
+#.+.+__
+#######",
+        );
+
+        // vent the airlock
+        assert_eq!(
+            Some(Air::VACUUM),
+            cycle_airlock(&mut area, &Coord::new(3, 1))
+        );
+        set_tile(&mut area, 4, 1, Tile::Door { open: true });
+        update_area(&mut area);
+        assert!(air_at(&area, 3, 1).is_vacuum());
+        assert_eq!(Air::STANDARD, air_at(&area, 1, 1));
+
+        // can not cycle while outer door is open
+        assert_eq!(None, cycle_airlock(&mut area, &Coord::new(3, 1)));
+
+        set_tile(&mut area, 4, 1, Tile::Door { open: false });
+        assert_eq!(
+            Some(Air::STANDARD),
+            cycle_airlock(&mut area, &Coord::new(3, 1))
+        );
+    }
+
+    #[test]
+    fn test_atmosphere_compartment_open_to_ground_should_get_ground_air() {
+        let mut area = new_area(
+            r",,,,,
+,###,
+,#.',
+,###,",
+        );
+        let mut cell = *area.get_grid().get_at(&Coord::new(2, 2)).unwrap();
+        cell.air = Air::VACUUM;
+        area.set_cell_at(&Coord::new(2, 2), cell);
+
+        update_area(&mut area);
+        assert_eq!(Air::STANDARD, air_at(&area, 2, 2));
+    }
+
+    #[test]
+    fn test_airlock_should_vent_only_its_compartment() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        walk_into_airlock(&mut sim);
+
+        // depressurize and open the outer door
+        sim.set_action(Action::CycleAirlock);
+        sim.tick();
+        assert!(get_air_at(&sim, P2::new(19, 8)).is_vacuum());
+        walk(&mut sim, 1, 0, 1);
+        sim.set_action(Action::Open(P2::new(21, 8)));
+        sim.tick();
+        assert!(get_air_at(&sim, P2::new(20, 8)).is_vacuum());
+        assert_eq!(Air::STANDARD, get_air_at(&sim, P2::new(13, 8)));
+
+        // close it again and land, the airlock is still in vacuum
+        sim.set_action(Action::Close(P2::new(21, 8)));
+        sim.tick();
+        sim.set_ship_command(
+            ship_id,
+            Command::Land {
+                target_id: scenery.zones["Planet X"][0],
+                place_coords: P2::new(0, 0),
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::BodySurfacePlace { .. })
+            )
+        });
+        assert!(ticks.is_some(), "ship never land");
+        let pos = get_avatar_position(&sim).point;
+        assert!(get_air_at(&sim, pos).is_vacuum());
+
+        // opening the outer door fill the airlock with the planet air
+        sim.set_action(Action::Open(pos.translate(1, 0)));
+        sim.tick();
+        assert_eq!(Air::STANDARD, get_air_at(&sim, pos));
+    }
+}
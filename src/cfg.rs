@@ -3,81 +3,95 @@ use crate::models::ObjectsType;
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs_derive::*;
-
-pub const SHIP_MAP: &str = r"
-___________________________
-___________________________
-_______####________________
-_______EEE#________________
-_______##.#________________
-________#.#________________
-______###-####-#######_____
-______#.....#...#....!_____
-______#.@...|...#....!_____
-______#.....#...|....!_____
-______###-############_____
-________#.#________________
-_______##.#________________
-_______EEE#________________
-_______####________________
-___________________________
-___________________________
-";
-
-pub const HOUSE_MAP: &str = r"
-#########
-#.......#
-|.......|
-#...#####
-#...|...#
-###-#####
-#.......#
-|.......|
-#.......#
-###-#####
-";
+use std::fmt;
 
 pub const SCREEN_W: i32 = 80;
 pub const SCREEN_H: i32 = 50;
 pub const SECTOR_SIZE: i32 = 11;
+/// galaxy map is a square of sectors
+pub const GALAXY_SIZE: i32 = 5;
+
+pub const AVATAR_MAX_HEALTH: i32 = 100;
+/// how many ticks the avatar can hold its breath
+pub const AVATAR_MAX_OXYGEN: i32 = 600;
+/// energy recovered per tick, with ACTION_COST of 100 the avatar act every 10 ticks
+pub const AVATAR_SPEED: i32 = 10;
+pub const AVATAR_MELEE_DAMAGE: i32 = 5;
+pub const AVATAR_RANGED_DAMAGE: i32 = 4;
+pub const AVATAR_RANGE: i32 = 8;
+
+pub const CREW_MAX_HEALTH: i32 = 100;
+pub const CREW_MAX_OXYGEN: i32 = 600;
+pub const CREW_SPEED: i32 = 10;
+pub const CREW_MELEE_DAMAGE: i32 = 3;
+
+pub const CREATURE_MAX_HEALTH: i32 = 20;
+pub const CREATURE_MAX_OXYGEN: i32 = 600;
+pub const CREATURE_SPEED: i32 = 8;
+pub const CREATURE_MELEE_DAMAGE: i32 = 4;
+pub const CREATURE_VIEW_RANGE: i32 = 8;
 
+pub const LEGEND_FILE: &str = "data/legend.json";
+/// default legend, embedded so it is available without a file system like on wasm
+pub const DEFAULT_LEGEND: &str = include_str!("../data/legend.json");
+
+#[derive(Debug)]
+pub enum CfgError {
+    Io(String, std::io::Error),
+    Json(String, serde_json::Error),
+}
+
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CfgError::Io(path, e) => write!(f, "fail to read {}: {}", path, e),
+            CfgError::Json(path, e) => write!(f, "fail to parse {}: {}", path, e),
+        }
+    }
+}
+
+/// Legend used to convert ascii maps chars into tiles and objects
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct MapParserCfg {
     pub raw_map_tiles: Vec<(char, Tile)>,
     pub raw_map_objects: Vec<(char, ObjectsType)>,
+    /// doors of these chars are spawned locked
+    #[serde(default)]
+    pub raw_map_locked_doors: Vec<char>,
+}
+
+impl MapParserCfg {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_file(path: &str) -> Result<Self, CfgError> {
+        let json = std::fs::read_to_string(path).map_err(|e| CfgError::Io(path.to_string(), e))?;
+        Self::parse(path, &json)
+    }
+
+    pub fn parse(name: &str, json: &str) -> Result<Self, CfgError> {
+        serde_json::from_str(json).map_err(|e| CfgError::Json(name.to_string(), e))
+    }
 }
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Cfg {
+    /// default legend for maps that don't define its own
     pub map_parser: MapParserCfg,
 }
 
 impl Cfg {
-    pub fn new() -> Self {
-        let raw_map_tiles: Vec<(char, Tile)> = vec![
-            ('_', Tile::Space),
-            ('.', Tile::Floor),
-            ('#', Tile::Wall),
-            ('E', Tile::Wall),
-            ('-', Tile::Floor),
-            ('|', Tile::Floor),
-            ('@', Tile::Floor),
-            ('!', Tile::Floor),
-        ];
-
-        let raw_map_objects: Vec<(char, ObjectsType)> = vec![
-            ('E', ObjectsType::Engine),
-            ('-', ObjectsType::Door { vertical: false }),
-            ('|', ObjectsType::Door { vertical: true }),
-            ('@', ObjectsType::Cockpit),
-            ('!', ObjectsType::Door { vertical: true }),
-        ];
+    pub fn new(map_parser: MapParserCfg) -> Self {
+        Cfg { map_parser }
+    }
 
-        Cfg {
-            map_parser: MapParserCfg {
-                raw_map_tiles,
-                raw_map_objects,
-            },
+    /// load the legend from LEGEND_FILE when it exists so it can be changed without a rebuild,
+    /// otherwise the default copy embedded in the binary
+    pub fn load() -> Result<Self, CfgError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if std::path::Path::new(LEGEND_FILE).exists() {
+                return Ok(Cfg::new(MapParserCfg::load_file(LEGEND_FILE)?));
+            }
         }
+        Ok(Cfg::new(MapParserCfg::parse(LEGEND_FILE, DEFAULT_LEGEND)?))
     }
 }
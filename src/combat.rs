@@ -0,0 +1,238 @@
+pub mod creature_ai_system;
+
+use crate::events::Event;
+use crate::models::{Position, Stats, P2};
+use crate::view::Viewshed;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use specs_derive::*;
+use std::ops::Deref;
+
+/// Entities from different factions are hostile to each other
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Faction {
+    Human,
+    Creature,
+}
+
+impl Faction {
+    pub fn is_hostile(&self, other: &Faction) -> bool {
+        self != other
+    }
+}
+
+/// Damage done by the entity attacks, a range of 0 means it has no ranged attack
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Combat {
+    pub melee_damage: i32,
+    pub ranged_damage: i32,
+    pub range: i32,
+}
+
+impl Combat {
+    pub fn melee(damage: i32) -> Self {
+        Combat {
+            melee_damage: damage,
+            ranged_damage: 0,
+            range: 0,
+        }
+    }
+
+    pub fn has_ranged(&self) -> bool {
+        self.range > 0
+    }
+}
+
+/// Wild creature controlled by the CreatureAiSystem
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Creature {}
+
+pub fn is_adjacent(a: &P2, b: &P2) -> bool {
+    (a.x - b.x).abs() <= 1 && (a.y - b.y).abs() <= 1
+}
+
+pub fn is_in_range(from: &P2, to: &P2, range: i32) -> bool {
+    let distance = rltk::DistanceAlg::Pythagoras.distance2d(
+        rltk::Point::new(from.x, from.y),
+        rltk::Point::new(to.x, to.y),
+    );
+    distance <= range as f32
+}
+
+/// first living entity at the position
+pub fn find_living_at<'a, DS, DP>(
+    entities: &Entities<'a>,
+    stats: &Storage<'a, Stats, DS>,
+    positions: &Storage<'a, Position, DP>,
+    pos: &Position,
+) -> Option<Entity>
+where
+    DS: Deref<Target = MaskedStorage<Stats>>,
+    DP: Deref<Target = MaskedStorage<Position>>,
+{
+    (entities, stats, positions)
+        .join()
+        .find(|(_, stats, p)| !stats.is_dead() && *p == pos)
+        .map(|(e, _, _)| e)
+}
+
+/// position of the closest living hostile in the entity view
+pub fn find_nearest_hostile(
+    entities: &Entities,
+    positions: &ReadStorage<Position>,
+    stats: &ReadStorage<Stats>,
+    factions: &ReadStorage<Faction>,
+    viewsheds: &ReadStorage<Viewshed>,
+    entity: Entity,
+) -> Option<P2> {
+    let pos = positions.get(entity)?;
+    let faction = factions.get(entity)?;
+    let viewshed = viewsheds.get(entity)?;
+
+    let distance = |p: &Position| {
+        let (dx, dy) = (p.point.x - pos.point.x, p.point.y - pos.point.y);
+        dx * dx + dy * dy
+    };
+
+    (entities, stats, positions, factions)
+        .join()
+        .filter(|(other, stats, p, other_faction)| {
+            *other != entity
+                && !stats.is_dead()
+                && faction.is_hostile(other_faction)
+                && p.grid_id == pos.grid_id
+                && viewshed
+                    .visible_tiles
+                    .iter()
+                    .any(|v| v.x == p.point.x && v.y == p.point.y)
+        })
+        .min_by_key(|(_, _, p, _)| distance(p))
+        .map(|(_, _, p, _)| p.point)
+}
+
+/// apply the damage into the target and return the event describing the attack
+pub fn hit(
+    stats: &mut WriteStorage<Stats>,
+    attacker_id: Entity,
+    target_id: Entity,
+    damage: i32,
+    ranged: bool,
+) -> Event {
+    if let Some(stats) = stats.get_mut(target_id) {
+        stats.health -= damage;
+    }
+    Event::Attacked {
+        attacker_id,
+        target_id,
+        damage,
+        ranged,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Action;
+    use crate::events::Event;
+    use crate::models::{ObjectsType, Position, Stats, P2};
+    use crate::simulation::Simulation;
+    use crate::test_utils::{get_avatar_position, get_position, new_state_basic_scenery};
+    use crate::{cfg, loader};
+
+    #[test]
+    fn test_creature_should_hunt_and_attack_the_avatar() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let ship_id = scenery.ships["ship"];
+        let avatar_id = scenery.avatar_id;
+        let bug_id = loader::create_creature(
+            sim.ecs_mut(),
+            "bug",
+            Position {
+                grid_id: ship_id,
+                point: P2::new(11, 9),
+            },
+        );
+
+        let ticks = sim.run_until(200, |ecs| {
+            let stats = ecs.read_storage::<Stats>();
+            let stats = stats.get(avatar_id).unwrap();
+            stats.health < stats.max_health
+        });
+        assert!(ticks.is_some(), "bug never attacked");
+        assert!(sim.events().iter().any(|e| matches!(
+            e,
+            Event::Attacked { attacker_id, target_id, ranged: false, .. }
+                if *attacker_id == bug_id && *target_id == avatar_id
+        )));
+    }
+
+    #[test]
+    fn test_avatar_should_attack_and_kill_creatures() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let ship_id = scenery.ships["ship"];
+        let avatar_id = scenery.avatar_id;
+
+        // creatures without AI to be used as targets
+        let create_target = |sim: &mut Simulation, point: P2| {
+            let id = loader::create_creature(
+                sim.ecs_mut(),
+                "bug",
+                Position {
+                    grid_id: ship_id,
+                    point,
+                },
+            );
+            sim.ecs_mut().write_storage::<Creature>().remove(id);
+            id
+        };
+
+        // bump to attack
+        let bug_id = create_target(&mut sim, P2::new(9, 8));
+        sim.move_avatar(1, 0);
+        sim.tick();
+        assert_eq!(P2::new(8, 8), get_avatar_position(&sim).point);
+        assert!(sim.events().contains(&Event::Attacked {
+            attacker_id: avatar_id,
+            target_id: bug_id,
+            damage: cfg::AVATAR_MELEE_DAMAGE,
+            ranged: false,
+        }));
+
+        // can not shoot behind a closed door
+        let hidden_id = create_target(&mut sim, P2::new(14, 8));
+        sim.set_action(Action::Fire(P2::new(14, 8)));
+        sim.tick();
+        assert_eq!(
+            Some(&Event::ActionFailed {
+                actor_id: avatar_id,
+                reason: "no line of sight".to_string()
+            }),
+            sim.events().last()
+        );
+
+        // shoot until it die
+        while sim.ecs().read_storage::<Stats>().get(bug_id).is_some() {
+            assert!(sim.fire_at_nearest());
+            sim.tick();
+        }
+        assert!(sim.events().contains(&Event::Attacked {
+            attacker_id: avatar_id,
+            target_id: bug_id,
+            damage: cfg::AVATAR_RANGED_DAMAGE,
+            ranged: true,
+        }));
+        assert!(sim.events().contains(&Event::Died { entity_id: bug_id }));
+
+        // leaving a corpse behind
+        assert_eq!(
+            Some(&ObjectsType::Corpse),
+            sim.ecs().read_storage::<ObjectsType>().get(bug_id)
+        );
+        assert_eq!(P2::new(9, 8), get_position(&sim, bug_id).point);
+
+        // the other one is still alive and out of view
+        assert!(sim.ecs().read_storage::<Stats>().get(hidden_id).is_some());
+        assert!(!sim.fire_at_nearest());
+    }
+}
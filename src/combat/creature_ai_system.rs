@@ -0,0 +1,83 @@
+use crate::actions::{self, Action, EntityActions};
+use crate::combat::{self, Combat, Creature, Faction};
+use crate::game_time::{self, Energy, GameTime};
+use crate::gridref::GridRef;
+use crate::models::{Position, Stats};
+use crate::unwrap_or_continue;
+use crate::view::Viewshed;
+use specs::prelude::*;
+
+/// Creatures hunt the closest hostile in view, attacking it when close enough
+pub struct CreatureAiSystem {}
+
+impl<'a> System<'a> for CreatureAiSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Creature>,
+        WriteStorage<'a, EntityActions>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Viewshed>,
+        ReadStorage<'a, Faction>,
+        ReadStorage<'a, Combat>,
+        ReadStorage<'a, Stats>,
+        ReadStorage<'a, Energy>,
+        ReadStorage<'a, GridRef>,
+        ReadExpect<'a, GameTime>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            creatures,
+            mut actions,
+            positions,
+            viewsheds,
+            factions,
+            combats,
+            stats,
+            energies,
+            grids,
+            time,
+        ): Self::SystemData,
+    ) {
+        if time.actors_elapsed == 0 {
+            return;
+        }
+
+        for (e, _, actions, pos) in (&entities, &creatures, &mut actions, &positions).join() {
+            if actions.current.is_some()
+                || !game_time::is_ready(&energies, e)
+                || stats.get(e).map(|s| s.is_dead()).unwrap_or(false)
+            {
+                continue;
+            }
+
+            let target = unwrap_or_continue!(combat::find_nearest_hostile(
+                &entities, &positions, &stats, &factions, &viewsheds, e,
+            ));
+
+            let area = unwrap_or_continue!(GridRef::find_area(&grids, pos.grid_id));
+            let action = if combat::is_adjacent(&pos.point, &target) {
+                Action::Attack(target)
+            } else {
+                match combats.get(e) {
+                    Some(c)
+                        if c.has_ranged()
+                            && combat::is_in_range(&pos.point, &target, c.range)
+                            && area.has_line_of_sight(&pos.point, &target) =>
+                    {
+                        Action::Fire(target)
+                    }
+                    _ => {
+                        let path = unwrap_or_continue!(area.find_path(&pos.point, &target));
+                        let next = unwrap_or_continue!(path.first());
+                        actions::step_towards(area, &pos.point, next)
+                    }
+                }
+            };
+
+            actions.current = Some(action);
+        }
+    }
+}
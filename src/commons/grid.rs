@@ -1,5 +1,6 @@
 use super::recti;
 use super::v2i::V2I;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[derive(Clone, Copy, PartialEq, Debug, Hash, Eq)]
@@ -19,7 +20,7 @@ pub type Index = i32;
     3 4 5
     6 7 8
 */
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Grid<T> {
     width: i32,
     height: i32,
@@ -261,7 +262,7 @@ impl<T> FlexGrid<T> {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct PGrid<T> {
     pub pos: V2I,
     pub grid: Grid<T>,
@@ -377,7 +378,7 @@ pub trait GridCell {
     fn is_empty(&self) -> bool;
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct NGrid<T: GridCell> {
     grids: Vec<PGrid<T>>,
 }
@@ -436,6 +437,12 @@ impl<T: GridCell> NGrid<T> {
         })
     }
 
+    /// replace the value on the same layer returned by get_at, return the previous value
+    pub fn set_at(&mut self, coord: &Coord, value: T) -> Option<T> {
+        let index = self.get_layer(coord)?;
+        Some(self.grids[index].set_at(coord, value))
+    }
+
     // pub fn push(&mut self, coord: &V2I, mut grid: PGrid<T>) {
     //     // translate new surface into local position
     //     let pos = grid.get_pos().translate(coord.x, coord.y);
@@ -448,6 +455,11 @@ impl<T: GridCell> NGrid<T> {
         self.grids.len()
     }
 
+    /// position of the layer grid
+    pub fn get_pos(&self, index: usize) -> Option<Coord> {
+        self.grids.get(index).map(|g| g.pos)
+    }
+
     pub fn remove(&mut self, index: usize) -> PGrid<T> {
         assert!(index <= self.grids.len());
         self.grids.remove(index)
@@ -1,13 +1,39 @@
 use crate::commons::grid::Grid;
+use std::fmt;
 
+/// Lines and columns are 1-based and relative to the first non empty line of the map
 #[derive(Debug)]
 pub enum ParseMapError {
-    UnknownChar(char),
+    UnknownChar {
+        ch: char,
+        line: usize,
+        column: usize,
+    },
     FewLines,
-    InvalidLineWidth(String),
+    InvalidLineWidth {
+        line: usize,
+        content: String,
+    },
     UnknownTileAt([i32; 2]),
 }
 
+impl fmt::Display for ParseMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseMapError::UnknownChar { ch, line, column } => write!(
+                f,
+                "unknown char '{}' at line {} column {}",
+                ch, line, column
+            ),
+            ParseMapError::FewLines => write!(f, "map has no lines"),
+            ParseMapError::InvalidLineWidth { line, content } => {
+                write!(f, "invalid width at line {}: '{}'", line, content)
+            }
+            ParseMapError::UnknownTileAt([x, y]) => write!(f, "unknown tile at {},{}", x, y),
+        }
+    }
+}
+
 pub fn parse_map<P, Tile>(parser: P, map_str: &str) -> Result<Grid<Tile>, ParseMapError>
 where
     P: Fn(char) -> Option<Tile>,
@@ -22,10 +48,17 @@ where
 {
     let mut cells = Vec::with_capacity(map.len());
 
-    for ch in map.iter() {
+    for (index, ch) in map.iter().enumerate() {
         let tile = match parser(*ch) {
             Some(tile) => tile,
-            None => return Err(ParseMapError::UnknownChar(*ch)),
+            None => {
+                let coords = map.index_to_coords(index as i32);
+                return Err(ParseMapError::UnknownChar {
+                    ch: *ch,
+                    line: coords.y as usize + 1,
+                    column: coords.x as usize + 1,
+                });
+            }
         };
 
         cells.push(tile);
@@ -61,9 +94,12 @@ fn parse_map_str(map: &str) -> Result<Grid<char>, ParseMapError> {
     let height = lines.len() as i32;
     let mut cells = Vec::with_capacity((width * height) as usize);
 
-    for (_y, line) in lines.iter().enumerate() {
+    for (y, line) in lines.iter().enumerate() {
         if line.len() != width as usize {
-            return Err(ParseMapError::InvalidLineWidth(line.clone()));
+            return Err(ParseMapError::InvalidLineWidth {
+                line: y + 1,
+                content: line.clone(),
+            });
         }
 
         for ch in line.chars() {
@@ -147,4 +183,27 @@ mod test {
         assert_eq!(lines[1], "ABBA");
         assert_eq!(lines[2], "AAAA");
     }
+
+    #[test]
+    fn test_parse_map_unknown_char_should_report_position() {
+        let map = r#"
+            XXXX
+            X.?X
+            XXXX"#;
+
+        let parser = |ch| match ch {
+            'X' => Some(Tile::Wall),
+            '.' => Some(Tile::Floor),
+            _ => None,
+        };
+
+        match parse_map(parser, map) {
+            Err(ParseMapError::UnknownChar { ch, line, column }) => {
+                assert_eq!('?', ch);
+                assert_eq!(2, line);
+                assert_eq!(3, column);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
 }
@@ -64,6 +64,39 @@ impl RDistrib {
     pub fn next_int(&self, rng: &mut StdRng) -> i32 {
         (self.next(rng).round() as i32).max(0)
     }
+
+    /// check the parameters, so next never panics
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            RDistrib::MinMax(min, max) if min >= max || min.is_nan() || max.is_nan() => {
+                Err(format!("empty range {}..{}", min, max))
+            }
+            RDistrib::Normal(_, std_dev) if *std_dev < 0.0 || std_dev.is_nan() => {
+                Err(format!("invalid standard deviation {}", std_dev))
+            }
+            RDistrib::ChiSquare { k, .. } if *k <= 0.0 || k.is_nan() => {
+                Err(format!("invalid degrees of freedom {}", k))
+            }
+            RDistrib::List { values } if values.is_empty() => Err("empty list".to_string()),
+            RDistrib::WeightedList { values } if !values.iter().any(|(_, w)| *w > 0.0) => {
+                Err("no value with weight".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// highest int that next_int can return, None for unbounded distributions
+    pub fn max_int(&self) -> Option<i32> {
+        let max = match self {
+            RDistrib::MinMax(_, max) => *max,
+            RDistrib::List { values } => values.iter().cloned().fold(f32::MIN, f32::max),
+            RDistrib::WeightedList { values } => {
+                values.iter().map(|(v, _)| *v).fold(f32::MIN, f32::max)
+            }
+            RDistrib::Normal(..) | RDistrib::ChiSquare { .. } => return None,
+        };
+        Some((max.round() as i32).max(0))
+    }
 }
 
 pub fn select_weighted<'a, R: rand::Rng, K: Clone>(
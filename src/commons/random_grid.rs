@@ -72,7 +72,7 @@ impl LevelGrid {
     }
 
     pub fn get_coords(&self, index: usize) -> (usize, usize) {
-        (index % self.width, index / self.height)
+        (index % self.width, index / self.width)
     }
 
     pub fn neighbors(&self, index: usize) -> Vec<usize> {
@@ -52,6 +52,14 @@ impl RectI {
         RectI::new(pos.x, pos.y, self.get_width(), self.get_height())
     }
 
+    /// true when both rects share at least one cell
+    pub fn intersects(&self, other: &RectI) -> bool {
+        self.topleft.x <= other.bottomright.x
+            && other.topleft.x <= self.bottomright.x
+            && self.topleft.y <= other.bottomright.y
+            && other.topleft.y <= self.bottomright.y
+    }
+
     pub fn center(&self) -> V2I {
         return V2I::new(
             self.topleft.x + self.get_width() / 2,
@@ -78,6 +86,16 @@ fn test_recti_is_inside() {
     assert!(!r.is_inside(&(-2, -2).into()));
 }
 
+#[test]
+fn test_recti_intersects() {
+    let r = RectI::new(0, 0, 4, 4);
+    assert!(r.intersects(&RectI::new(2, 2, 4, 4)));
+    assert!(r.intersects(&RectI::new(4, 4, 2, 2)));
+    assert!(r.intersects(&RectI::new(1, 1, 1, 1)));
+    assert!(!r.intersects(&RectI::new(5, 0, 2, 2)));
+    assert!(!r.intersects(&RectI::new(0, -3, 2, 2)));
+}
+
 #[test]
 fn test_to_local() {
     let r = RectI::new(0, 0, 10, 10);
@@ -1,6 +1,8 @@
+use serde::{Deserialize, Serialize};
+
 pub const ZERO: V2I = V2I { x: 0, y: 0 };
 
-#[derive(Clone, Copy, PartialEq, Debug, Hash, Eq, PartialOrd, Default)]
+#[derive(Clone, Copy, PartialEq, Debug, Hash, Eq, PartialOrd, Default, Serialize, Deserialize)]
 pub struct V2I {
     pub x: i32,
     pub y: i32,
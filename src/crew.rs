@@ -0,0 +1,190 @@
+pub mod ai_system;
+
+use crate::save::EntityNotSaved;
+use crate::P2;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
+use specs_derive::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Job {
+    /// walk between the points in order, points are relative to the crew ship grid
+    Patrol { points: Vec<P2>, next: usize },
+    /// stay at the ship cockpit
+    ManCockpit,
+    /// stay near the ship engines
+    RepairEngines,
+    /// keep close to the target
+    Follow { target_id: Entity },
+}
+
+/// NPC member of a ship crew, it is controlled by the CrewAiSystem
+#[derive(Component, Debug, Clone)]
+pub struct Crew {
+    /// ship the crew belongs to
+    pub ship_id: Entity,
+    pub job: Job,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum JobData<M> {
+    Patrol { points: Vec<P2>, next: usize },
+    ManCockpit,
+    RepairEngines,
+    Follow { target_id: M },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CrewData<M> {
+    ship_id: M,
+    job: JobData<M>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Crew
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = CrewData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        let job = match &self.job {
+            Job::Patrol { points, next } => JobData::Patrol {
+                points: points.clone(),
+                next: *next,
+            },
+            Job::ManCockpit => JobData::ManCockpit,
+            Job::RepairEngines => JobData::RepairEngines,
+            Job::Follow { target_id } => JobData::Follow {
+                target_id: ids(*target_id).ok_or(EntityNotSaved)?,
+            },
+        };
+        Ok(CrewData {
+            ship_id: ids(self.ship_id).ok_or(EntityNotSaved)?,
+            job,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        let job = match data.job {
+            JobData::Patrol { points, next } => Job::Patrol { points, next },
+            JobData::ManCockpit => Job::ManCockpit,
+            JobData::RepairEngines => Job::RepairEngines,
+            JobData::Follow { target_id } => Job::Follow {
+                target_id: ids(target_id).ok_or(EntityNotSaved)?,
+            },
+        };
+        Ok(Crew {
+            ship_id: ids(data.ship_id).ok_or(EntityNotSaved)?,
+            job,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::combat;
+    use crate::events::Event;
+    use crate::gridref::GridRef;
+    use crate::models::{Location, Position, P2};
+    use crate::ship::Command;
+    use crate::test_utils::{
+        get_avatar_position, get_position, new_state_basic_scenery, new_state_default_scenery,
+    };
+
+    #[test]
+    fn test_crew_should_walk_to_their_jobs() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let pilot_id = scenery.crew["pilot"];
+        let engineer_id = scenery.crew["engineer"];
+
+        // the avatar is in the cockpit, the pilot wait next to it
+        sim.run(100);
+        let pilot_pos = get_position(&sim, pilot_id).point;
+        assert_ne!(P2::new(8, 8), pilot_pos);
+        assert!(combat::is_adjacent(&P2::new(8, 8), &pilot_pos));
+
+        // engineer need to open the door to reach the engines
+        sim.move_avatar(0, 1);
+        let ticks = sim.run_until(500, |ecs| {
+            let positions = ecs.read_storage::<Position>();
+            positions.get(pilot_id).unwrap().point == P2::new(8, 8)
+                && positions.get(engineer_id).unwrap().point == P2::new(9, 12)
+        });
+        assert!(ticks.is_some(), "crew never reach their jobs");
+        assert!(sim.events().iter().any(|e| matches!(
+            e,
+            Event::DoorOpened { actor_id, .. } if *actor_id == engineer_id
+        )));
+
+        // and stay there
+        sim.run(100);
+        assert_eq!(P2::new(8, 8), get_position(&sim, pilot_id).point);
+        assert_eq!(P2::new(9, 12), get_position(&sim, engineer_id).point);
+    }
+
+    #[test]
+    fn test_crew_should_travel_with_ship_and_keep_their_jobs() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let zone_id = scenery.zones["Planet X"][0];
+        let pilot_id = scenery.crew["pilot"];
+        let engineer_id = scenery.crew["engineer"];
+
+        sim.set_ship_command(
+            ship_id,
+            Command::Land {
+                target_id: zone_id,
+                place_coords: P2::new(10, 10),
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::BodySurfacePlace { .. })
+            )
+        });
+        assert!(ticks.is_some(), "ship never land");
+        assert_eq!(zone_id, get_position(&sim, pilot_id).grid_id);
+        assert_eq!(zone_id, get_position(&sim, engineer_id).grid_id);
+
+        // patrol points are relative to the ship, even when landed
+        let ship_pos = {
+            let grids = sim.ecs().read_storage::<GridRef>();
+            let area = GridRef::find_area(&grids, zone_id).unwrap();
+            area.get_layer_pos(ship_id).unwrap()
+        };
+        {
+            let mut crews = sim.ecs_mut().write_storage::<Crew>();
+            crews.get_mut(pilot_id).unwrap().job = Job::Patrol {
+                points: vec![P2::new(7, 7), P2::new(14, 9)],
+                next: 0,
+            };
+            crews.get_mut(engineer_id).unwrap().job = Job::Follow {
+                target_id: scenery.avatar_id,
+            };
+        }
+        let ticks = sim.run_until(500, |ecs| {
+            ecs.read_storage::<Position>().get(pilot_id).unwrap().point == ship_pos.translate(7, 7)
+        });
+        assert!(ticks.is_some(), "pilot never reach first patrol point");
+        let ticks = sim.run_until(500, |ecs| {
+            ecs.read_storage::<Position>().get(pilot_id).unwrap().point == ship_pos.translate(14, 9)
+        });
+        assert!(ticks.is_some(), "pilot never reach second patrol point");
+
+        let avatar_pos = get_avatar_position(&sim).point;
+        let engineer_pos = get_position(&sim, engineer_id).point;
+        assert!((avatar_pos.x - engineer_pos.x).abs() <= 1);
+        assert!((avatar_pos.y - engineer_pos.y).abs() <= 1);
+    }
+}
@@ -0,0 +1,158 @@
+use crate::actions::{self, Action, EntityActions};
+use crate::area::Area;
+use crate::crew::{Crew, Job};
+use crate::game_time::{self, Energy, GameTime};
+use crate::gridref::GridRef;
+use crate::models::{ObjectsType, Player, Position, Stats, P2};
+use crate::ship::ModuleHealth;
+use crate::{commons, unwrap_or_continue};
+use specs::prelude::*;
+
+/// Decide the next action of each crew member from its job, the actions are executed by the
+/// ActionsSystem
+pub struct CrewAiSystem {}
+
+impl<'a> System<'a> for CrewAiSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Crew>,
+        WriteStorage<'a, EntityActions>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, ObjectsType>,
+        ReadStorage<'a, GridRef>,
+        ReadStorage<'a, Energy>,
+        ReadStorage<'a, Stats>,
+        ReadStorage<'a, ModuleHealth>,
+        ReadExpect<'a, Player>,
+        ReadExpect<'a, GameTime>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut crews,
+            mut actions,
+            positions,
+            objects,
+            grids,
+            energies,
+            stats,
+            healths,
+            player,
+            time,
+        ): Self::SystemData,
+    ) {
+        if time.actors_elapsed == 0 {
+            return;
+        }
+
+        for (e, crew, actions, pos) in (&entities, &mut crews, &mut actions, &positions).join() {
+            // crew in the player squad are controlled by the player
+            if player.is_avatar(e)
+                || actions.current.is_some()
+                || !game_time::is_ready(&energies, e)
+                || stats.get(e).map(|s| s.is_dead()).unwrap_or(false)
+            {
+                continue;
+            }
+
+            let area = unwrap_or_continue!(GridRef::find_area(&grids, pos.grid_id));
+
+            let targets = match &mut crew.job {
+                Job::Patrol { points, next } => {
+                    if points.is_empty() {
+                        continue;
+                    }
+                    let ship_pos = unwrap_or_continue!(area.get_layer_pos(crew.ship_id));
+                    *next %= points.len();
+                    let mut target = ship_pos.translate(points[*next].x, points[*next].y);
+                    if target == pos.point {
+                        *next = (*next + 1) % points.len();
+                        target = ship_pos.translate(points[*next].x, points[*next].y);
+                    }
+                    vec![target]
+                }
+                Job::ManCockpit => (&objects, &positions)
+                    .join()
+                    .filter(|(kind, p)| **kind == ObjectsType::Cockpit && p.grid_id == pos.grid_id)
+                    .map(|(_, p)| p.point)
+                    .collect(),
+                Job::RepairEngines => {
+                    let engines = (&objects, &positions, healths.maybe())
+                        .join()
+                        .filter(|(kind, p, _)| {
+                            **kind == ObjectsType::Engine && p.grid_id == pos.grid_id
+                        })
+                        .map(|(_, p, health)| {
+                            (p.point, health.map(|h| h.is_damaged()).unwrap_or(false))
+                        })
+                        .collect::<Vec<_>>();
+
+                    let next_to = |point: &P2| {
+                        commons::grid::get_4_neighbours(point)
+                            .iter()
+                            .any(|(_, neighbour)| *neighbour == pos.point)
+                    };
+                    if engines
+                        .iter()
+                        .any(|(point, damaged)| *damaged && next_to(point))
+                    {
+                        actions.current = Some(Action::Repair);
+                        continue;
+                    }
+
+                    // walk to the damaged engines first
+                    let any_damaged = engines.iter().any(|(_, damaged)| *damaged);
+                    engines
+                        .into_iter()
+                        .filter(|(_, damaged)| *damaged || !any_damaged)
+                        .flat_map(|(point, _)| commons::grid::get_4_neighbours(&point))
+                        .map(|(_, point)| point)
+                        .filter(|point| area.move_cost_at(point).is_some())
+                        .collect()
+                }
+                Job::Follow { target_id } => match positions.get(*target_id) {
+                    Some(target) if target.grid_id == pos.grid_id => {
+                        let (dx, dy) = (target.point.x - pos.point.x, target.point.y - pos.point.y);
+                        if dx.abs() <= 1 && dy.abs() <= 1 {
+                            continue;
+                        }
+                        vec![target.point]
+                    }
+                    _ => continue,
+                },
+            };
+
+            if targets.contains(&pos.point) {
+                continue;
+            }
+
+            if let Some(action) = next_action(area, &pos.point, &targets) {
+                // wait for the way to be free instead of walking over other actors
+                if let Action::Move(dir) = &action {
+                    let (dx, dy) = dir.as_vec();
+                    let next = Position {
+                        grid_id: pos.grid_id,
+                        point: pos.point.translate(dx, dy),
+                    };
+                    if !actions::is_walkable_step(Some(area), &entities, &stats, &positions, &next)
+                    {
+                        continue;
+                    }
+                }
+                actions.current = Some(action);
+            }
+        }
+    }
+}
+
+/// first action to walk into the closest reachable target
+fn next_action(area: &Area, from: &P2, targets: &[P2]) -> Option<Action> {
+    let path = targets
+        .iter()
+        .filter_map(|target| area.find_path_through_doors(from, target))
+        .min_by_key(|path| path.len())?;
+    let next = path.first()?;
+    Some(actions::step_towards(area, from, next))
+}
@@ -0,0 +1,248 @@
+use crate::area::{Cell, Tile};
+use crate::commons::grid::{Coord, Grid};
+use crate::commons::random_grid::{LevelGrid, RandomGrid, RandomGridCfg};
+use crate::loader;
+use crate::models::{DoorState, Position, P2};
+use crate::scenery::SceneryError;
+use rand::rngs::StdRng;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+
+/// smallest room that fits both stairs side by side
+const MIN_ROOM_SIZE: i32 = 4;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DungeonCfg {
+    /// rooms per level
+    pub width: usize,
+    pub height: usize,
+    /// cells of each room including one of its walls
+    pub room_size: i32,
+    pub levels: u32,
+    /// probability of a door between two adjacent rooms, all rooms are always reachable
+    pub portal_prob: f32,
+    /// hostile creatures spawned on each level
+    #[serde(default)]
+    pub creatures: u32,
+}
+
+impl DungeonCfg {
+    /// fail on parameters the levels can not be generated from
+    pub fn validate(&self) -> Result<(), SceneryError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SceneryError::InvalidDungeon(format!(
+                "levels of {}x{} rooms",
+                self.width, self.height
+            )));
+        }
+        if self.room_size < MIN_ROOM_SIZE {
+            return Err(SceneryError::InvalidDungeon(format!(
+                "room_size {} is smaller than {}",
+                self.room_size, MIN_ROOM_SIZE
+            )));
+        }
+        if self.levels == 0 {
+            return Err(SceneryError::InvalidDungeon("no levels".to_string()));
+        }
+        // the random grid only connects all rooms within these bounds
+        if self.portal_prob.is_nan() || self.portal_prob <= 0.1 || self.portal_prob >= 1.0 {
+            return Err(SceneryError::InvalidDungeon(format!(
+                "portal_prob {} is not between 0.1 and 1.0",
+                self.portal_prob
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Create all dungeon levels connected by stairs, the first level stairs up leads to the entrance
+/// where a stairs down is placed. Return the levels from top to bottom.
+pub fn create_dungeon(
+    world: &mut World,
+    rng: &mut StdRng,
+    cfg: &DungeonCfg,
+    entrance: Position,
+) -> Result<Vec<Entity>, SceneryError> {
+    cfg.validate()?;
+
+    let random_grid = RandomGrid::new(
+        &RandomGridCfg {
+            width: cfg.width,
+            height: cfg.height,
+            portal_prob: cfg.portal_prob,
+            deep_levels: cfg.levels,
+        },
+        rng,
+    );
+
+    let mut level_ids = vec![];
+    for (deep, level) in random_grid.levels.iter().enumerate() {
+        let (grid, doors) = new_level_grid(level, cfg.room_size);
+        let level_id =
+            loader::create_dungeon_level(world, &format!("dungeon level {}", deep + 1), grid);
+        for (point, vertical) in doors {
+            let pos = Position {
+                grid_id: level_id,
+                point,
+            };
+            loader::create_door(world, pos, vertical, DoorState::Closed);
+        }
+        level_ids.push(level_id);
+    }
+
+    // first level has no up portal, its stairs up are placed at any room
+    let mut ups = vec![];
+    let mut downs = vec![];
+    for level in &random_grid.levels {
+        let up_room = level
+            .get_up_portal()
+            .unwrap_or_else(|| rng.gen_range(0..level.len()));
+        let up = room_center(level, up_room, cfg.room_size);
+        ups.push(up);
+        downs.push(level.get_down_portal().map(|down_room| {
+            let down = room_center(level, down_room, cfg.room_size);
+            if down == up {
+                down.translate(1, 0)
+            } else {
+                down
+            }
+        }));
+    }
+
+    loader::create_stairs(world, entrance.clone(), true, level_ids[0], ups[0]);
+    for (deep, level_id) in level_ids.iter().enumerate() {
+        let (up_target_id, up_target_pos) = if deep == 0 {
+            (entrance.grid_id, entrance.point)
+        } else {
+            (level_ids[deep - 1], downs[deep - 1].unwrap())
+        };
+        let pos = Position {
+            grid_id: *level_id,
+            point: ups[deep],
+        };
+        loader::create_stairs(world, pos, false, up_target_id, up_target_pos);
+
+        if let Some(down) = downs[deep] {
+            let pos = Position {
+                grid_id: *level_id,
+                point: down,
+            };
+            loader::create_stairs(world, pos, true, level_ids[deep + 1], ups[deep + 1]);
+        }
+
+        loader::create_creatures(world, *level_id, cfg.creatures, rng);
+    }
+
+    Ok(level_ids)
+}
+
+fn room_center(level: &LevelGrid, room: usize, room_size: i32) -> P2 {
+    let (x, y) = level.get_coords(room);
+    P2::new(
+        x as i32 * room_size + room_size / 2,
+        y as i32 * room_size + room_size / 2,
+    )
+}
+
+/// rooms carved into solid walls, with doors in the walls between connected rooms. Return the
+/// grid and the doors position and if they are vertical.
+fn new_level_grid(level: &LevelGrid, room_size: i32) -> (Grid<Cell>, Vec<(P2, bool)>) {
+    let width = level.get_width() as i32 * room_size + 1;
+    let height = level.get_height() as i32 * room_size + 1;
+    let mut grid = Grid::new(width, height, || Cell::new(Tile::Wall));
+    let mut doors = vec![];
+
+    for ry in 0..level.get_height() {
+        for rx in 0..level.get_width() {
+            let (x0, y0) = (rx as i32 * room_size, ry as i32 * room_size);
+            for y in y0 + 1..y0 + room_size {
+                for x in x0 + 1..x0 + room_size {
+                    grid.set_at(Coord::new(x, y), Cell::new(Tile::Floor));
+                }
+            }
+
+            let index = level.get_index(rx, ry);
+            if rx + 1 < level.get_width() && level.is_portal(index, level.get_index(rx + 1, ry)) {
+                doors.push((P2::new(x0 + room_size, y0 + room_size / 2), true));
+            }
+            if ry + 1 < level.get_height() && level.is_portal(index, level.get_index(rx, ry + 1)) {
+                doors.push((P2::new(x0 + room_size / 2, y0 + room_size), false));
+            }
+        }
+    }
+
+    for (point, _) in &doors {
+        grid.set_at(*point, Cell::new(Tile::Door { open: false }));
+    }
+
+    (grid, doors)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::scenery;
+    use crate::simulation::Simulation;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_level_grid_should_have_door_for_each_portal() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let random_grid = RandomGrid::new(
+            &RandomGridCfg {
+                width: 3,
+                height: 2,
+                portal_prob: 0.5,
+                deep_levels: 1,
+            },
+            &mut rng,
+        );
+        let level = &random_grid.levels[0];
+
+        let (grid, doors) = new_level_grid(level, 5);
+        assert_eq!(16, grid.get_width());
+        assert_eq!(11, grid.get_height());
+        assert_eq!(level.get_portals().len(), doors.len());
+        assert_eq!(Tile::Floor, grid.get_at(room_center(level, 0, 5)).tile);
+        for (point, _) in doors {
+            assert_eq!(Tile::Door { open: false }, grid.get_at(point).tile);
+        }
+    }
+
+    #[test]
+    fn test_invalid_dungeon_should_fail_to_create_scenery() {
+        let invalid = |change: &dyn Fn(&mut DungeonCfg)| {
+            let mut scenery_cfg = scenery::load_default_scenery().unwrap();
+            let dungeon = scenery_cfg
+                .planets
+                .iter_mut()
+                .flat_map(|planet| planet.zones.iter_mut())
+                .find_map(|zone| zone.dungeon.as_mut())
+                .expect("default scenery has no dungeon");
+            change(dungeon);
+            Simulation::from_scenery(&scenery_cfg).err()
+        };
+
+        assert!(matches!(
+            invalid(&|cfg| cfg.width = 0),
+            Some(SceneryError::InvalidDungeon(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.height = 0),
+            Some(SceneryError::InvalidDungeon(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.room_size = 2),
+            Some(SceneryError::InvalidDungeon(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.levels = 0),
+            Some(SceneryError::InvalidDungeon(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.portal_prob = 1.0),
+            Some(SceneryError::InvalidDungeon(_))
+        ));
+    }
+}
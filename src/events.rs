@@ -1,9 +1,259 @@
+use crate::atmosphere::Air;
+use crate::game_time::GameTime;
+use crate::inventory::ItemKind;
+use crate::models::ObjectsType;
+use crate::trade::Commodity;
 use specs::prelude::*;
-use specs_derive::*;
 
-#[derive(Component)]
+/// max number of entries kept in the MessageLog
+pub const MAX_LOG_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    ArrivedOrbit {
+        ship_id: Entity,
+        body_id: Entity,
+    },
+    Landed {
+        ship_id: Entity,
+        zone_id: Entity,
+    },
+    Launched {
+        ship_id: Entity,
+        body_id: Entity,
+    },
+    Docked {
+        ship_id: Entity,
+        station_id: Entity,
+    },
+    Jumped {
+        ship_id: Entity,
+        sector_id: Entity,
+    },
+    /// amount is positive when bought and negative when sold, credits is the ship credits change
+    Traded {
+        ship_id: Entity,
+        station_id: Entity,
+        commodity: Commodity,
+        amount: i32,
+        credits: i32,
+    },
+    /// credits is the ship credits change, always negative
+    Refueled {
+        ship_id: Entity,
+        station_id: Entity,
+        amount: u32,
+        credits: i32,
+    },
+    FuelHarvested {
+        ship_id: Entity,
+        amount: u32,
+    },
+    OutOfFuel {
+        ship_id: Entity,
+    },
+    ShipDamaged {
+        ship_id: Entity,
+        damage: i32,
+    },
+    ModuleDamaged {
+        module_id: Entity,
+        damage: i32,
+    },
+    HullBreached {
+        ship_id: Entity,
+    },
+    /// repaired breaches are removed, so only its kind is kept
+    Repaired {
+        actor_id: Entity,
+        kind: ObjectsType,
+    },
+    /// hull walls patched, restoring the hull of the ship
+    HullRepaired {
+        actor_id: Entity,
+        ship_id: Entity,
+    },
+    EncounterStarted {
+        ship_id: Entity,
+        other_id: Entity,
+    },
+    Hailed {
+        ship_id: Entity,
+        other_id: Entity,
+    },
+    ShipAttacked {
+        ship_id: Entity,
+        other_id: Entity,
+    },
+    Fled {
+        ship_id: Entity,
+        other_id: Entity,
+    },
+    /// credits taken from the boarded ship
+    Boarded {
+        ship_id: Entity,
+        other_id: Entity,
+        credits: i32,
+    },
+    DoorOpened {
+        actor_id: Entity,
+        door_id: Entity,
+    },
+    DoorClosed {
+        actor_id: Entity,
+        door_id: Entity,
+    },
+    AirlockCycled {
+        actor_id: Entity,
+        air: Air,
+    },
+    ActionFailed {
+        actor_id: Entity,
+        reason: String,
+    },
+    WalkInterrupted {
+        actor_id: Entity,
+        seen_id: Entity,
+    },
+    Died {
+        entity_id: Entity,
+    },
+    Attacked {
+        attacker_id: Entity,
+        target_id: Entity,
+        damage: i32,
+        ranged: bool,
+    },
+    PickedUp {
+        actor_id: Entity,
+        item_id: Entity,
+    },
+    Dropped {
+        actor_id: Entity,
+        item_id: Entity,
+    },
+    /// used items are consumed, so only its kind is kept
+    ItemUsed {
+        actor_id: Entity,
+        kind: ItemKind,
+    },
+    ClimbedStairs {
+        actor_id: Entity,
+        down: bool,
+    },
+    JoinedSquad {
+        entity_id: Entity,
+    },
+    LeftSquad {
+        entity_id: Entity,
+    },
+}
+
+/// Event bus, systems push events during the tick and the MessageLogSystem move them into the
+/// MessageLog at the end of it
+#[derive(Debug, Default)]
 pub struct EntitiesEvents {
     pub events: Vec<Event>,
 }
 
-pub enum Event {}
+impl EntitiesEvents {
+    pub fn push(&mut self, event: Event) {
+        log::debug!("event {:?}", event);
+        self.events.push(event);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub tick: u64,
+    pub event: Event,
+}
+
+/// History of all events, used by the message log panel
+#[derive(Debug, Default)]
+pub struct MessageLog {
+    entries: Vec<LogEntry>,
+    /// how many entries the panel is scrolled back from the last one
+    pub scroll: usize,
+}
+
+impl MessageLog {
+    pub fn push(&mut self, tick: u64, event: Event) {
+        self.entries.push(LogEntry { tick, event });
+        if self.entries.len() > MAX_LOG_ENTRIES {
+            self.entries.remove(0);
+        }
+    }
+
+    pub fn get_entries(&self) -> &Vec<LogEntry> {
+        &self.entries
+    }
+
+    pub fn scroll_back(&mut self) {
+        if self.scroll + 1 < self.entries.len() {
+            self.scroll += 1;
+        }
+    }
+
+    pub fn scroll_forward(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    /// the last `count` entries considering the scroll, from older to newer
+    pub fn visible_entries(&self, count: usize) -> &[LogEntry] {
+        let end = self.entries.len().saturating_sub(self.scroll);
+        let start = end.saturating_sub(count);
+        &self.entries[start..end]
+    }
+}
+
+/// Every event since the start, unlike the MessageLog nothing is dropped. Only kept when the
+/// resource is inserted, like the headless Simulation does
+#[derive(Debug, Default)]
+pub struct EventsHistory {
+    pub events: Vec<Event>,
+}
+
+pub struct MessageLogSystem {}
+
+impl<'a> System<'a> for MessageLogSystem {
+    type SystemData = (
+        Write<'a, EntitiesEvents>,
+        Write<'a, MessageLog>,
+        Option<Write<'a, EventsHistory>>,
+        ReadExpect<'a, GameTime>,
+    );
+
+    fn run(&mut self, (mut events, mut log, mut history, time): Self::SystemData) {
+        for event in events.events.drain(..) {
+            if let Some(history) = history.as_mut() {
+                history.events.push(event.clone());
+            }
+            log.push(time.tick, event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_message_log_visible_entries_with_scroll() {
+        let entity_id = World::new().entities().create();
+        let mut log = MessageLog::default();
+        for tick in 0..5 {
+            log.push(tick, Event::Died { entity_id });
+        }
+
+        let ticks = |entries: &[LogEntry]| entries.iter().map(|e| e.tick).collect::<Vec<_>>();
+        assert_eq!(vec![2, 3, 4], ticks(log.visible_entries(3)));
+
+        log.scroll_back();
+        log.scroll_back();
+        assert_eq!(vec![0, 1, 2], ticks(log.visible_entries(3)));
+
+        log.scroll_forward();
+        assert_eq!(vec![1, 2, 3], ticks(log.visible_entries(3)));
+    }
+}
@@ -0,0 +1,445 @@
+use crate::area::Tile;
+use crate::atmosphere::Air;
+use crate::cfg;
+use crate::commons::prob::{self, RDistrib, Weighted};
+use crate::dungeon_gen::DungeonCfg;
+use crate::models::{SurfaceTileKind, P2};
+use crate::scenery::{
+    JumpCfg, LocationCfg, PlanetCfg, SceneryCfg, SceneryError, SectorCfg, ShipCfg, StationCfg,
+    ZoneCfg,
+};
+use crate::ship::ShipAiKind;
+use crate::trade::Cargo;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// default generator parameters, embedded so it is available without a file system like on wasm
+pub const DEFAULT_GALAXY_GEN: &str = include_str!("../data/galaxy.json");
+
+/// Parameters of the galaxy generator, the same seed and parameters always generate the same
+/// galaxy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GalaxyGenCfg {
+    pub seed: u64,
+    /// limited by the galaxy map size
+    pub sectors: RDistrib,
+    pub planets_per_sector: RDistrib,
+    pub stations_per_sector: RDistrib,
+    /// jump links added to the ones required to connect all sectors
+    pub extra_jumps: RDistrib,
+    /// planet surface is a square of surface_size x surface_size zones
+    pub surface_size: RDistrib,
+    pub zone_size: RDistrib,
+    pub zone_kinds: Vec<Weighted<SurfaceTileKind>>,
+    pub creatures_per_zone: RDistrib,
+    /// probability of a structure zone to have a dungeon
+    pub dungeon_prob: f32,
+    pub dungeon: DungeonCfg,
+    /// other ships flying in the galaxy, the player ships are kept from the template
+    pub ships: RDistrib,
+    pub ship_map: String,
+    pub station_map: String,
+}
+
+impl GalaxyGenCfg {
+    /// check the parameters before generating, so a bad cfg is reported instead of failing in
+    /// the middle of the generation
+    pub fn validate(&self, template: &SceneryCfg) -> Result<(), SceneryError> {
+        let distributions = [
+            ("sectors", &self.sectors),
+            ("planets_per_sector", &self.planets_per_sector),
+            ("stations_per_sector", &self.stations_per_sector),
+            ("extra_jumps", &self.extra_jumps),
+            ("surface_size", &self.surface_size),
+            ("zone_size", &self.zone_size),
+            ("creatures_per_zone", &self.creatures_per_zone),
+            ("ships", &self.ships),
+        ];
+        for (name, distrib) in &distributions {
+            distrib
+                .validate()
+                .map_err(|e| SceneryError::InvalidGalaxyGen(format!("{}: {}", name, e)))?;
+        }
+
+        if !(0.0..=1.0).contains(&self.dungeon_prob) {
+            return Err(SceneryError::InvalidGalaxyGen(format!(
+                "dungeon_prob {} is not a probability",
+                self.dungeon_prob
+            )));
+        }
+
+        self.dungeon.validate()?;
+
+        for map in &[&self.ship_map, &self.station_map] {
+            if !template.maps.contains_key(*map) {
+                return Err(SceneryError::UnknownMap(map.to_string()));
+            }
+        }
+
+        // worst case, every ship in the same sector with all jumps, planets and stations
+        let max_sectors = self
+            .sectors
+            .max_int()
+            .map(|sectors| sectors.min(cfg::GALAXY_SIZE * cfg::GALAXY_SIZE));
+        let max_bodies = [
+            max_sectors.map(|sectors| sectors - 1),
+            self.planets_per_sector.max_int(),
+            self.stations_per_sector.max_int(),
+            self.ships.max_int().map(|ships| ships + 1),
+        ]
+        .iter()
+        .copied()
+        .sum::<Option<i32>>();
+        if let Some(max_bodies) = max_bodies {
+            if max_bodies > cfg::SECTOR_SIZE * cfg::SECTOR_SIZE {
+                return Err(SceneryError::InvalidGalaxyGen(format!(
+                    "up to {} bodies could be placed in a sector of {} places",
+                    max_bodies,
+                    cfg::SECTOR_SIZE * cfg::SECTOR_SIZE
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn load_default() -> Result<GalaxyGenCfg, SceneryError> {
+    Ok(serde_json::from_str(DEFAULT_GALAXY_GEN)?)
+}
+
+/// Free positions inside a sector, so bodies never share the same place
+struct SectorPlaces {
+    free: Vec<P2>,
+}
+
+impl SectorPlaces {
+    fn new() -> Self {
+        let half = cfg::SECTOR_SIZE / 2;
+        let mut free = vec![];
+        for y in -half..=half {
+            for x in -half..=half {
+                free.push(P2::new(x, y));
+            }
+        }
+        SectorPlaces { free }
+    }
+
+    fn take(&mut self, rng: &mut StdRng) -> Result<P2, SceneryError> {
+        if self.free.is_empty() {
+            return Err(SceneryError::InvalidGalaxyGen(
+                "no free place in sector".to_string(),
+            ));
+        }
+        let index = rng.gen_range(0..self.free.len());
+        Ok(self.free.swap_remove(index))
+    }
+}
+
+/// Generate a new scenery using maps, prefabs, avatar, crew and items from the template. The
+/// template ships are kept and start orbiting the first planet.
+pub fn generate(cfg: &GalaxyGenCfg, template: &SceneryCfg) -> Result<SceneryCfg, SceneryError> {
+    cfg.validate(template)?;
+    let mut rng = StdRng::seed_from_u64(cfg.seed);
+
+    // sectors at unique galaxy map positions
+    let mut galaxy_places = vec![];
+    for y in 0..cfg::GALAXY_SIZE {
+        for x in 0..cfg::GALAXY_SIZE {
+            galaxy_places.push(P2::new(x, y));
+        }
+    }
+    let sectors_count = (cfg.sectors.next_int(&mut rng).max(1) as usize).min(galaxy_places.len());
+    let mut sectors = vec![];
+    let mut places = vec![];
+    for i in 0..sectors_count {
+        let index = rng.gen_range(0..galaxy_places.len());
+        sectors.push(SectorCfg {
+            name: format!("sector {}", i),
+            pos: galaxy_places.swap_remove(index),
+        });
+        places.push(SectorPlaces::new());
+    }
+
+    // jumps, each sector is connected to a previous one and some random extra links
+    let mut links = vec![];
+    for i in 1..sectors_count {
+        links.push((rng.gen_range(0..i), i));
+    }
+    if sectors_count > 1 {
+        for _ in 0..cfg.extra_jumps.next_int(&mut rng) {
+            let a = rng.gen_range(0..sectors_count);
+            let b = rng.gen_range(0..sectors_count);
+            if a != b && !links.contains(&(a, b)) && !links.contains(&(b, a)) {
+                links.push((a, b));
+            }
+        }
+    }
+
+    let mut jumps = vec![];
+    for (a, b) in links {
+        let pos_a = places[a].take(&mut rng)?;
+        let pos_b = places[b].take(&mut rng)?;
+        jumps.push(JumpCfg {
+            name: format!("gate to {}", sectors[b].name),
+            sector: sectors[a].name.clone(),
+            pos: pos_a,
+            target_sector: sectors[b].name.clone(),
+            target_pos: pos_b,
+        });
+        jumps.push(JumpCfg {
+            name: format!("gate to {}", sectors[a].name),
+            sector: sectors[b].name.clone(),
+            pos: pos_b,
+            target_sector: sectors[a].name.clone(),
+            target_pos: pos_a,
+        });
+    }
+
+    // planets and stations
+    let mut planets = vec![];
+    let mut stations = vec![];
+    for (sector_index, sector) in sectors.iter().enumerate() {
+        for i in 0..cfg.planets_per_sector.next_int(&mut rng) {
+            let pos = places[sector_index].take(&mut rng)?;
+            planets.push(generate_planet(
+                cfg,
+                &mut rng,
+                format!("planet {}-{}", sector_index, i),
+                sector.name.clone(),
+                pos,
+            ));
+        }
+
+        for i in 0..cfg.stations_per_sector.next_int(&mut rng) {
+            stations.push(StationCfg {
+                name: format!("station {}-{}", sector_index, i),
+                sector: sector.name.clone(),
+                pos: places[sector_index].take(&mut rng)?,
+                map: cfg.station_map.clone(),
+            });
+        }
+    }
+
+    // template ships start at the first sector, orbiting the first planet when there is one
+    let start_location = match planets.first() {
+        Some(planet) => LocationCfg::Orbit {
+            body: planet.name.clone(),
+        },
+        None => LocationCfg::Sector {
+            sector: sectors[0].name.clone(),
+            pos: places[0].take(&mut rng)?,
+        },
+    };
+    let mut ships = template
+        .ships
+        .iter()
+        .map(|ship| ShipCfg {
+            location: start_location.clone(),
+            ..ship.clone()
+        })
+        .collect::<Vec<_>>();
+
+    for i in 0..cfg.ships.next_int(&mut rng) {
+        let sector_index = rng.gen_range(0..sectors_count);
+        ships.push(ShipCfg {
+            name: format!("ship {}", i),
+            map: cfg.ship_map.clone(),
+            location: LocationCfg::Sector {
+                sector: sectors[sector_index].name.clone(),
+                pos: places[sector_index].take(&mut rng)?,
+            },
+            cargo: Cargo::default(),
+            fuel: None,
+            ai: Some(ShipAiKind::ALL[rng.gen_range(0..ShipAiKind::ALL.len())]),
+        });
+    }
+
+    Ok(SceneryCfg {
+        maps: template.maps.clone(),
+        prefabs: template.prefabs.clone(),
+        sectors,
+        planets,
+        stations,
+        jumps,
+        ships,
+        avatar: template.avatar.clone(),
+        crew: template.crew.clone(),
+        items: template.items.clone(),
+    })
+}
+
+fn generate_planet(
+    cfg: &GalaxyGenCfg,
+    rng: &mut StdRng,
+    name: String,
+    sector: String,
+    pos: P2,
+) -> PlanetCfg {
+    let surface_size = cfg.surface_size.next_int(rng).max(1);
+    let zone_size = cfg.zone_size.next_int(rng).max(1);
+
+    let mut zones = vec![];
+    for _ in 0..surface_size * surface_size {
+        let kind = *prob::select_weighted(rng, &cfg.zone_kinds).unwrap_or(&SurfaceTileKind::Plain);
+        let creatures = match kind {
+            SurfaceTileKind::Structure => 0,
+            _ => cfg.creatures_per_zone.next_int(rng) as u32,
+        };
+        let dungeon = match kind {
+            SurfaceTileKind::Structure if rng.gen::<f32>() < cfg.dungeon_prob => {
+                Some(cfg.dungeon.clone())
+            }
+            _ => None,
+        };
+
+        zones.push(ZoneCfg {
+            kind,
+            tile: Tile::Ground,
+            buildings: vec![],
+            seed: rng.gen(),
+            creatures,
+            dungeon,
+        });
+    }
+
+    PlanetCfg {
+        name,
+        sector,
+        pos,
+        surface_size,
+        zone_size,
+        air: Air::STANDARD,
+        zones,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::Galaxy;
+    use crate::scenery;
+    use crate::simulation::Simulation;
+    use specs::prelude::*;
+    use std::collections::HashSet;
+
+    fn load() -> (GalaxyGenCfg, SceneryCfg) {
+        (
+            load_default().unwrap(),
+            scenery::load_default_scenery().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_same_seed_should_generate_same_galaxy() {
+        let (mut cfg, template) = load();
+
+        let json =
+            |cfg: &GalaxyGenCfg| serde_json::to_string(&generate(cfg, &template).unwrap()).unwrap();
+        let first = json(&cfg);
+        assert_eq!(first, json(&cfg));
+
+        cfg.seed += 1;
+        assert_ne!(first, json(&cfg));
+    }
+
+    #[test]
+    fn test_all_sectors_should_be_connected_by_jumps() {
+        let (cfg, template) = load();
+
+        for seed in 0..10 {
+            let galaxy = generate(
+                &GalaxyGenCfg {
+                    seed,
+                    ..cfg.clone()
+                },
+                &template,
+            )
+            .unwrap();
+
+            let mut reached = HashSet::new();
+            let mut queue = vec![galaxy.sectors[0].name.clone()];
+            while let Some(sector) = queue.pop() {
+                if !reached.insert(sector.clone()) {
+                    continue;
+                }
+                for jump in galaxy.jumps.iter().filter(|j| j.sector == sector) {
+                    queue.push(jump.target_sector.clone());
+                }
+            }
+            assert_eq!(galaxy.sectors.len(), reached.len(), "seed {}", seed);
+
+            for planet in &galaxy.planets {
+                assert_eq!(
+                    planet.surface_size * planet.surface_size,
+                    planet.zones.len() as i32
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_invalid_cfg_should_fail_to_generate() {
+        let (cfg, template) = load();
+        let invalid = |change: &dyn Fn(&mut GalaxyGenCfg)| {
+            let mut cfg = cfg.clone();
+            change(&mut cfg);
+            generate(&cfg, &template).err()
+        };
+
+        assert!(matches!(
+            invalid(&|cfg| cfg.ship_map = "unknown".to_string()),
+            Some(SceneryError::UnknownMap(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.sectors = RDistrib::MinMax(3.0, 3.0)),
+            Some(SceneryError::InvalidGalaxyGen(_))
+        ));
+        // more bodies than places in a sector, either checked up front or while placing them
+        assert!(matches!(
+            invalid(&|cfg| cfg.ships = RDistrib::MinMax(200.0, 300.0)),
+            Some(SceneryError::InvalidGalaxyGen(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.ships = RDistrib::Normal(2000.0, 1.0)),
+            Some(SceneryError::InvalidGalaxyGen(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.dungeon.levels = 0),
+            Some(SceneryError::InvalidDungeon(_))
+        ));
+        assert!(matches!(
+            invalid(&|cfg| cfg.dungeon.room_size = 1),
+            Some(SceneryError::InvalidDungeon(_))
+        ));
+    }
+
+    #[test]
+    fn test_generated_galaxy_should_be_playable() {
+        let template = scenery::load_default_scenery().unwrap();
+        let mut gen_cfg = load_default().unwrap();
+        gen_cfg.seed = 42;
+        let scenery_cfg = generate(&gen_cfg, &template).unwrap();
+
+        let (mut sim, scenery) = Simulation::from_scenery(&scenery_cfg).unwrap();
+        let galaxy = sim
+            .ecs()
+            .read_storage::<Galaxy>()
+            .get(scenery.galaxy_id)
+            .cloned()
+            .unwrap();
+        assert_eq!(scenery_cfg.sectors.len(), galaxy.sectors.len());
+        assert_eq!(scenery_cfg.ships.len(), scenery.ships.len());
+        for planet in &scenery_cfg.planets {
+            assert_eq!(
+                planet.zones.len(),
+                scenery.zones[&planet.name].len(),
+                "planet {}",
+                planet.name
+            );
+        }
+
+        sim.run(10);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::models::Player;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::*;
+
+/// energy spent by any action, an actor can only act when it has at least this energy
+pub const ACTION_COST: i32 = 100;
+/// real time milliseconds of a tick
+pub const TICK_MS: f32 = 16.0;
+/// ticks executed at most in a frame, slow frames don't make the game jump ahead
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum TimeMode {
+    /// one tick each TICK_MS of real time
+    RealTime,
+    /// real time, but the clock is stopped
+    Paused,
+    /// the world runs in real time, but actors only recover energy while the player avatar is
+    /// waiting for it
+    TurnBased,
+}
+
+/// Game clock, systems must use elapsed ticks instead of counting frames
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GameTime {
+    /// total of ticks since the game start
+    pub tick: u64,
+    /// ticks elapsed on the current frame
+    pub elapsed: u32,
+    /// ticks elapsed on the current frame for the actors with Energy
+    #[serde(default)]
+    pub actors_elapsed: u32,
+    pub mode: TimeMode,
+    /// real time not converted into ticks yet
+    #[serde(skip)]
+    pub real_time_ms: f32,
+}
+
+impl GameTime {
+    /// accumulate the real time of a frame, it is converted into ticks by GameTimeSystem
+    pub fn add_real_time(&mut self, ms: f32) {
+        self.real_time_ms += ms;
+    }
+
+    /// consume the accumulated real time as whole ticks
+    fn take_real_time_ticks(&mut self) -> u32 {
+        let ticks = (self.real_time_ms / TICK_MS) as u32;
+        self.real_time_ms -= ticks as f32 * TICK_MS;
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.real_time_ms = 0.0;
+        }
+        ticks.min(MAX_TICKS_PER_FRAME)
+    }
+
+    /// switch between turn-based and real time
+    pub fn toggle_turn_based(&mut self) {
+        self.mode = match self.mode {
+            TimeMode::TurnBased => TimeMode::RealTime,
+            _ => TimeMode::TurnBased,
+        };
+    }
+
+    /// pause or resume real time, do nothing in turn-based
+    pub fn toggle_pause(&mut self) {
+        self.mode = match self.mode {
+            TimeMode::RealTime => TimeMode::Paused,
+            TimeMode::Paused => TimeMode::RealTime,
+            TimeMode::TurnBased => TimeMode::TurnBased,
+        };
+    }
+}
+
+impl Default for GameTime {
+    fn default() -> Self {
+        GameTime {
+            tick: 0,
+            elapsed: 0,
+            actors_elapsed: 0,
+            mode: TimeMode::RealTime,
+            real_time_ms: 0.0,
+        }
+    }
+}
+
+/// Initiative of actors, it is recovered by speed every tick
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Energy {
+    pub value: i32,
+    pub speed: i32,
+}
+
+impl Energy {
+    pub fn new(speed: i32) -> Self {
+        Energy {
+            value: ACTION_COST,
+            speed,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.value >= ACTION_COST
+    }
+
+    pub fn ticks_to_ready(&self) -> u32 {
+        let missing = ACTION_COST - self.value;
+        if missing <= 0 {
+            return 0;
+        }
+        let speed = self.speed.max(1);
+        ((missing + speed - 1) / speed) as u32
+    }
+
+    pub fn spend(&mut self) {
+        self.value -= ACTION_COST;
+    }
+
+    pub fn regen(&mut self, ticks: u32) {
+        self.value = (self.value + self.speed * ticks as i32).min(ACTION_COST);
+    }
+}
+
+/// return true if the entity has energy to act, entities without Energy are always ready
+pub fn is_ready<D>(energies: &D, entity: Entity) -> bool
+where
+    D: specs::storage::GenericReadStorage<Component = Energy>,
+{
+    energies.get(entity).map(|e| e.is_ready()).unwrap_or(true)
+}
+
+/// Advance the clock, must be the first system executed on each frame
+pub struct GameTimeSystem {}
+
+impl<'a> System<'a> for GameTimeSystem {
+    type SystemData = (
+        WriteExpect<'a, GameTime>,
+        ReadExpect<'a, Player>,
+        WriteStorage<'a, Energy>,
+    );
+
+    fn run(&mut self, (mut time, player, mut energies): Self::SystemData) {
+        let real_time = time.take_real_time_ticks();
+        let (elapsed, actors_elapsed) = match time.mode {
+            TimeMode::RealTime => (real_time, real_time),
+            TimeMode::Paused => (0, 0),
+            // actors wait until all controlled avatars can act
+            TimeMode::TurnBased => {
+                let actors_elapsed = (&player.get_controlled(), &energies)
+                    .join()
+                    .map(|(_, e)| e.ticks_to_ready())
+                    .max()
+                    .unwrap_or(real_time);
+                (real_time, actors_elapsed)
+            }
+        };
+
+        time.elapsed = elapsed;
+        time.actors_elapsed = actors_elapsed;
+        time.tick += elapsed as u64;
+
+        if actors_elapsed > 0 {
+            for energy in (&mut energies).join() {
+                energy.regen(actors_elapsed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cfg;
+    use crate::models::Location;
+    use crate::ship::Command;
+    use crate::simulation::Simulation;
+    use crate::test_utils::new_state_basic_scenery;
+
+    #[test]
+    fn test_energy_ticks_to_ready() {
+        let mut energy = Energy::new(30);
+        assert!(energy.is_ready());
+        assert_eq!(0, energy.ticks_to_ready());
+
+        energy.spend();
+        assert!(!energy.is_ready());
+        assert_eq!(4, energy.ticks_to_ready());
+
+        energy.regen(3);
+        assert_eq!(90, energy.value);
+        energy.regen(3);
+        assert_eq!(ACTION_COST, energy.value);
+    }
+
+    #[test]
+    fn test_real_time_should_advance_a_tick_per_tick_duration() {
+        let mut time = GameTime::default();
+        time.add_real_time(TICK_MS * 2.5);
+        assert_eq!(2, time.take_real_time_ticks());
+        time.add_real_time(TICK_MS * 0.5);
+        assert_eq!(1, time.take_real_time_ticks());
+        assert_eq!(0, time.take_real_time_ticks());
+
+        // long frames are not caught up
+        time.add_real_time(TICK_MS * 100.0);
+        assert_eq!(MAX_TICKS_PER_FRAME, time.take_real_time_ticks());
+        assert_eq!(0, time.take_real_time_ticks());
+    }
+
+    #[test]
+    fn test_turn_based_time_should_only_hold_actors_until_avatar_act() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let ship_id = scenery.ships["ship"];
+        sim.set_time_mode(TimeMode::TurnBased);
+
+        // actors wait for the player, the world keeps running
+        let run_actors = |sim: &mut Simulation, ticks: u32| {
+            let mut actors_ticks = 0;
+            for _ in 0..ticks {
+                sim.tick();
+                actors_ticks += sim.get_time().actors_elapsed;
+            }
+            actors_ticks
+        };
+        assert_eq!(0, run_actors(&mut sim, 10));
+        assert_eq!(10, sim.get_time().tick);
+
+        // any action advance the actors until the avatar is ready again
+        sim.move_avatar(1, 0);
+        let turn_ticks = (ACTION_COST / cfg::AVATAR_SPEED) as u32;
+        assert_eq!(turn_ticks, run_actors(&mut sim, 10));
+
+        // ships fly while the player thinks
+        sim.set_ship_command(
+            ship_id,
+            Command::FlyTo {
+                target_id: scenery.bodies["Planet X"],
+            },
+        )
+        .unwrap();
+        sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { .. })
+            )
+        })
+        .expect("ship never arrive");
+
+        // paused real time don't advance
+        sim.set_time_mode(TimeMode::Paused);
+        let tick = sim.get_time().tick;
+        sim.run(10);
+        assert_eq!(tick, sim.get_time().tick);
+
+        sim.set_time_mode(TimeMode::RealTime);
+        sim.run(10);
+        assert_eq!(tick + 10, sim.get_time().tick);
+    }
+}
@@ -1,11 +1,14 @@
 use crate::commons::grid::Coord;
+use crate::save::EntityNotSaved as NoError;
 use crate::Area;
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
 use specs_derive::*;
 
 /// Entity that hold the gmap of the following object. To find the real grid the references must be
 /// followed until a GMap is found and them search on what layer index belong to this object
-#[derive(Component, Debug, Clone)]
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub enum GridRef {
     Ref(Entity),
     GMap(Area),
@@ -0,0 +1,211 @@
+use crate::models::Stats;
+use crate::save::{entities_from, entities_into, EntityNotSaved};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
+use specs_derive::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ItemKind {
+    Medkit {
+        heal: i32,
+    },
+    OxygenTank {
+        oxygen: i32,
+    },
+    /// parts that are only useful for trading or repairs
+    Scrap,
+}
+
+/// Something that can be carried. Items on the ground have a Position, carried items don't and
+/// are referenced by the owner Inventory
+#[derive(Component, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub kind: ItemKind,
+}
+
+impl Item {
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            ItemKind::Medkit { .. } => "medkit",
+            ItemKind::OxygenTank { .. } => "oxygen tank",
+            ItemKind::Scrap => "scrap",
+        }
+    }
+}
+
+#[derive(Component, Clone, Debug, Default, PartialEq)]
+pub struct Inventory {
+    /// carried items in the order they were picked
+    pub items: Vec<Entity>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InventoryData<M> {
+    items: Vec<M>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Inventory
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = InventoryData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(InventoryData {
+            items: entities_into(&self.items, ids)?,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Inventory {
+            items: entities_from(data.items, ids)?,
+        })
+    }
+}
+
+/// apply the item effect, return false if the item can not be used
+pub fn use_item(item: &Item, stats: &mut Stats) -> bool {
+    match item.kind {
+        ItemKind::Medkit { heal } => {
+            stats.health = (stats.health + heal).min(stats.max_health);
+            true
+        }
+        ItemKind::OxygenTank { oxygen } => {
+            stats.oxygen = (stats.oxygen + oxygen).min(stats.max_oxygen);
+            true
+        }
+        ItemKind::Scrap => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Action;
+    use crate::events::Event;
+    use crate::loader;
+    use crate::models::{Location, Position, Stats, P2};
+    use crate::ship::Command;
+    use crate::simulation::Simulation;
+    use crate::test_utils::{get_avatar_position, get_position, new_state_default_scenery};
+
+    #[test]
+    fn test_use_item_should_not_exceed_max() {
+        let mut stats = Stats::new(100, 100);
+        stats.health = 90;
+        stats.oxygen = 10;
+
+        let medkit = Item {
+            kind: ItemKind::Medkit { heal: 30 },
+        };
+        assert!(use_item(&medkit, &mut stats));
+        assert_eq!(100, stats.health);
+
+        let tank = Item {
+            kind: ItemKind::OxygenTank { oxygen: 50 },
+        };
+        assert!(use_item(&tank, &mut stats));
+        assert_eq!(60, stats.oxygen);
+
+        let scrap = Item {
+            kind: ItemKind::Scrap,
+        };
+        assert!(!use_item(&scrap, &mut stats));
+    }
+
+    #[test]
+    fn test_items_should_be_picked_used_and_dropped() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let zone_id = scenery.zones["Planet X"][0];
+        let avatar_id = scenery.avatar_id;
+        let carried = |sim: &Simulation| {
+            sim.ecs()
+                .read_storage::<Inventory>()
+                .get(avatar_id)
+                .unwrap()
+                .items
+                .clone()
+        };
+
+        // pick up the medkit
+        sim.move_avatar(-1, -1);
+        sim.set_action(Action::Pickup);
+        sim.tick();
+        let medkit_id = carried(&sim)[0];
+        assert!(sim.events().contains(&Event::PickedUp {
+            actor_id: avatar_id,
+            item_id: medkit_id
+        }));
+        assert!(sim
+            .ecs()
+            .read_storage::<Position>()
+            .get(medkit_id)
+            .is_none());
+
+        // and use it to heal
+        sim.ecs_mut()
+            .write_storage::<Stats>()
+            .get_mut(avatar_id)
+            .unwrap()
+            .health = 50;
+        sim.set_action(Action::Use(0));
+        sim.tick();
+        assert_eq!(
+            80,
+            sim.ecs()
+                .read_storage::<Stats>()
+                .get(avatar_id)
+                .unwrap()
+                .health
+        );
+        assert!(carried(&sim).is_empty());
+        assert!(sim.events().contains(&Event::ItemUsed {
+            actor_id: avatar_id,
+            kind: ItemKind::Medkit { heal: 30 },
+        }));
+
+        // drop some scrap inside the ship
+        let avatar_pos = get_avatar_position(&sim);
+        let scrap_id = loader::create_item(
+            sim.ecs_mut(),
+            Item {
+                kind: ItemKind::Scrap,
+            },
+            avatar_pos,
+        );
+        sim.set_action(Action::Pickup);
+        sim.tick();
+        assert_eq!(vec![scrap_id], carried(&sim));
+        sim.set_action(Action::Drop(0));
+        sim.tick();
+        assert!(carried(&sim).is_empty());
+        assert_eq!(P2::new(7, 7), get_position(&sim, scrap_id).point);
+
+        // that travel with the ship
+        sim.set_ship_command(
+            ship_id,
+            Command::Land {
+                target_id: zone_id,
+                place_coords: P2::new(10, 10),
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::BodySurfacePlace { .. })
+            )
+        });
+        assert!(ticks.is_some(), "ship never land");
+        assert_eq!(get_avatar_position(&sim), get_position(&sim, scrap_id));
+    }
+}
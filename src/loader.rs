@@ -1,41 +1,74 @@
 use std::collections::HashSet;
 
-use crate::actions::EntityActions;
+use crate::actions::{self, EntityActions};
 use crate::area::{Area, Cell, Tile};
+use crate::cfg;
 use crate::cfg::MapParserCfg;
+use crate::combat::{Combat, Creature, Faction};
 use crate::commons::grid::{Grid, NGrid};
+use crate::commons::grid_string;
 use crate::commons::grid_string::ParseMapError;
+use crate::commons::prob;
 use crate::commons::v2i::V2I;
+use crate::crew::{Crew, Job};
+use crate::game_time::Energy;
 use crate::gridref::GridRef;
+use crate::inventory::{Inventory, Item, ItemKind};
 use crate::models::{
-    Avatar, Label, Location, ObjectsType, Position, Sector, SectorBody, Surface, SurfaceTileKind,
+    Avatar, Door, DoorState, Galaxy, Label, Location, ObjectsType, Position, Sector, SectorBody,
+    SpawnPoint, Stairs, Stats, Surface, SurfaceTileKind, P2,
 };
-use crate::ship::Ship;
+use crate::ship::{self, ModuleHealth, Ship};
+use crate::trade::{Cargo, Market};
 use crate::view::{Renderable, Viewshed};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use rltk::RGB;
 use specs::prelude::*;
 
-pub fn create_sector(world: &mut World) -> Entity {
-    world.create_entity().with(Sector::default()).build()
+pub fn create_sector(world: &mut World, label: &str, pos: P2) -> Entity {
+    world
+        .create_entity()
+        .with(Sector {
+            pos,
+            bodies: vec![],
+        })
+        .with(Label {
+            name: label.to_string(),
+        })
+        .build()
 }
 
-pub fn create_planet_zone(world: &mut World, index: usize, size: i32, tile: Tile) -> Entity {
-    create_planet_zone_from(world, index, size, tile, vec![])
+pub fn create_galaxy(world: &mut World, sectors: Vec<Entity>) -> Entity {
+    world.create_entity().with(Galaxy { sectors }).build()
 }
 
-pub fn create_planet_zone_from(
+/// jump point that move ships into the target sector at target_pos
+pub fn create_jump(
     world: &mut World,
-    index: usize,
-    size: i32,
-    tile: Tile,
-    buildings: Vec<(V2I, &Grid<Cell>)>,
+    label: &str,
+    location: Location,
+    target: Entity,
+    target_pos: P2,
 ) -> Entity {
-    let mut grid = Grid::new_square(size, || Cell { tile });
-
-    for (pos, other) in buildings {
-        grid.merge(pos, other);
-    }
+    world
+        .create_entity()
+        .with(SectorBody::Jump { target_pos, target })
+        .with(Label {
+            name: label.to_string(),
+        })
+        .with(location)
+        .build()
+}
 
+/// planet zone with its creatures, the same seed always spawn them at the same places
+pub fn create_planet_zone(
+    world: &mut World,
+    index: usize,
+    grid: Grid<Cell>,
+    creatures: u32,
+    seed: u64,
+) -> Entity {
     let builder = world.create_entity();
     let gmap = Area::new(NGrid::from_grid(grid), vec![builder.entity]);
     let zone_id = builder
@@ -45,9 +78,95 @@ pub fn create_planet_zone_from(
         .with(GridRef::GMap(gmap))
         .build();
 
+    let mut rng = StdRng::seed_from_u64(seed);
+    create_creatures(world, zone_id, creatures, &mut rng);
+
     zone_id
 }
 
+/// a level of a dungeon, the grid is its own area
+pub fn create_dungeon_level(world: &mut World, label: &str, grid: Grid<Cell>) -> Entity {
+    let builder = world.create_entity();
+    let gmap = Area::new(NGrid::from_grid(grid), vec![builder.entity]);
+    builder
+        .with(Label {
+            name: label.to_string(),
+        })
+        .with(GridRef::GMap(gmap))
+        .build()
+}
+
+/// the cell tile is set from the door state
+pub fn create_door(world: &mut World, pos: Position, vertical: bool, state: DoorState) -> Entity {
+    let door = Door { state };
+    actions::update_door_tile(&mut world.write_storage::<GridRef>(), &door, &pos);
+
+    let icon = if vertical { '|' } else { '-' };
+    world
+        .create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437(icon),
+            fg: RGB::named(rltk::CYAN),
+            bg: RGB::named(rltk::BLACK),
+            priority: 0,
+        })
+        .with(ObjectsType::Door { vertical })
+        .with(door)
+        .build()
+}
+
+/// ship hardware like engines or reactors
+pub fn create_ship_module(world: &mut World, pos: Position, kind: ObjectsType) -> Entity {
+    let (glyph, color) = match kind {
+        ObjectsType::Cockpit => ('C', rltk::BLUE),
+        ObjectsType::Engine => ('E', rltk::RED),
+        ObjectsType::Airlock => ('A', rltk::YELLOW),
+        ObjectsType::FuelTank => ('F', rltk::ORANGE),
+        ObjectsType::Reactor => ('R', rltk::MAGENTA),
+        ObjectsType::Sensors => ('S', rltk::CYAN),
+        ObjectsType::LifeSupport => ('L', rltk::LIGHT_BLUE),
+        _ => ('?', rltk::WHITE),
+    };
+    world
+        .create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437(glyph),
+            fg: RGB::named(color),
+            bg: RGB::named(rltk::BLACK),
+            priority: 0,
+        })
+        .with(kind)
+        .with(ModuleHealth::new(ship::MODULE_HEALTH))
+        .build()
+}
+
+pub fn create_stairs(
+    world: &mut World,
+    pos: Position,
+    down: bool,
+    target_id: Entity,
+    target_pos: P2,
+) -> Entity {
+    let icon = if down { '>' } else { '<' };
+    world
+        .create_entity()
+        .with(pos)
+        .with(Renderable {
+            glyph: rltk::to_cp437(icon),
+            fg: RGB::named(rltk::WHITE),
+            bg: RGB::named(rltk::BLACK),
+            priority: 0,
+        })
+        .with(ObjectsType::Stairs { down })
+        .with(Stairs {
+            target_id,
+            target_pos,
+        })
+        .build()
+}
+
 pub fn create_planet(
     world: &mut World,
     label: &str,
@@ -77,6 +196,7 @@ pub fn create_ship(
     world: &mut World,
     label: &str,
     ship: Ship,
+    cargo: Cargo,
     location: Location,
     ship_grid: NGrid<Cell>,
 ) -> Entity {
@@ -86,10 +206,12 @@ pub fn create_ship(
     let ship_gmap = Area::new(ship_grid, vec![ship_id]);
 
     builder
+        .with(SectorBody::Ship)
         .with(Label {
             name: label.to_string(),
         })
         .with(ship)
+        .with(cargo)
         .with(location)
         .with(GridRef::GMap(ship_gmap))
         .build();
@@ -97,10 +219,46 @@ pub fn create_ship(
     ship_id
 }
 
+/// stations market prices are seeded by its entity id
+pub fn create_station(
+    world: &mut World,
+    label: &str,
+    location: Location,
+    station_grid: NGrid<Cell>,
+) -> Entity {
+    let builder = world.create_entity();
+
+    let station_id = builder.entity;
+    let station_gmap = Area::new(station_grid, vec![station_id]);
+
+    builder
+        .with(SectorBody::Station)
+        .with(Label {
+            name: label.to_string(),
+        })
+        .with(Market {
+            seed: station_id.id() as u64,
+        })
+        .with(location)
+        .with(GridRef::GMap(station_gmap))
+        .build();
+
+    station_id
+}
+
 pub fn create_avatar(world: &mut World, position: Position) -> Entity {
     world
         .create_entity()
         .with(Avatar {})
+        .with(Stats::new(cfg::AVATAR_MAX_HEALTH, cfg::AVATAR_MAX_OXYGEN))
+        .with(Energy::new(cfg::AVATAR_SPEED))
+        .with(Inventory::default())
+        .with(Faction::Human)
+        .with(Combat {
+            melee_damage: cfg::AVATAR_MELEE_DAMAGE,
+            ranged_damage: cfg::AVATAR_RANGED_DAMAGE,
+            range: cfg::AVATAR_RANGE,
+        })
         .with(Label {
             name: "player".to_string(),
         })
@@ -123,23 +281,183 @@ pub fn create_avatar(world: &mut World, position: Position) -> Entity {
         .build()
 }
 
+pub fn create_crew(
+    world: &mut World,
+    name: &str,
+    ship_id: Entity,
+    job: Job,
+    position: Position,
+) -> Entity {
+    world
+        .create_entity()
+        .with(Crew { ship_id, job })
+        .with(Stats::new(cfg::CREW_MAX_HEALTH, cfg::CREW_MAX_OXYGEN))
+        .with(Energy::new(cfg::CREW_SPEED))
+        .with(Faction::Human)
+        .with(Combat::melee(cfg::CREW_MELEE_DAMAGE))
+        .with(Label {
+            name: name.to_string(),
+        })
+        .with(position)
+        .with(Renderable {
+            glyph: rltk::to_cp437('@'),
+            fg: RGB::named(rltk::GREEN),
+            bg: RGB::named(rltk::BLACK),
+            priority: 1,
+        })
+        .with(Viewshed {
+            visible_tiles: vec![],
+            know_tiles: HashSet::new(),
+            range: 16,
+        })
+        .with(EntityActions::new())
+        .build()
+}
+
+pub fn create_creature(world: &mut World, name: &str, position: Position) -> Entity {
+    world
+        .create_entity()
+        .with(Creature {})
+        .with(Stats::new(
+            cfg::CREATURE_MAX_HEALTH,
+            cfg::CREATURE_MAX_OXYGEN,
+        ))
+        .with(Energy::new(cfg::CREATURE_SPEED))
+        .with(Faction::Creature)
+        .with(Combat::melee(cfg::CREATURE_MELEE_DAMAGE))
+        .with(Label {
+            name: name.to_string(),
+        })
+        .with(position)
+        .with(Renderable {
+            glyph: rltk::to_cp437('b'),
+            fg: RGB::named(rltk::ORANGE),
+            bg: RGB::named(rltk::BLACK),
+            priority: 1,
+        })
+        .with(Viewshed {
+            visible_tiles: vec![],
+            know_tiles: HashSet::new(),
+            range: cfg::CREATURE_VIEW_RANGE,
+        })
+        .with(EntityActions::new())
+        .build()
+}
+
+/// spawn creatures at random walkable cells of the zone
+pub fn create_creatures<R: rand::Rng>(
+    world: &mut World,
+    zone_id: Entity,
+    count: u32,
+    rng: &mut R,
+) -> Vec<Entity> {
+    let cells = {
+        let grids = world.read_storage::<GridRef>();
+        let area = match GridRef::find_area(&grids, zone_id) {
+            Some(area) => area,
+            None => return vec![],
+        };
+        let size = area.get_grid().get_size();
+        let mut cells = vec![];
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let point = V2I::new(x, y);
+                if area.move_cost_at(&point).is_some() {
+                    cells.push(point);
+                }
+            }
+        }
+        cells
+    };
+
+    let mut result = vec![];
+    for _ in 0..count {
+        let point = match prob::select(rng, &cells) {
+            Some(point) => *point,
+            None => break,
+        };
+        let position = Position {
+            grid_id: zone_id,
+            point,
+        };
+        result.push(create_creature(world, "bug", position));
+    }
+    result
+}
+
+pub fn create_item(world: &mut World, item: Item, position: Position) -> Entity {
+    let (glyph, color) = match item.kind {
+        ItemKind::Medkit { .. } => ('!', rltk::RED),
+        ItemKind::OxygenTank { .. } => ('o', rltk::CYAN),
+        ItemKind::Scrap => ('*', rltk::GRAY),
+    };
+
+    world
+        .create_entity()
+        .with(ObjectsType::Item)
+        .with(Label {
+            name: item.name().to_string(),
+        })
+        .with(position)
+        .with(Renderable {
+            glyph: rltk::to_cp437(glyph),
+            fg: RGB::named(color),
+            bg: RGB::named(rltk::BLACK),
+            priority: 0,
+        })
+        .with(item)
+        .build()
+}
+
+pub fn create_spawn_point(world: &mut World, position: Position) -> Entity {
+    world
+        .create_entity()
+        .with(SpawnPoint {})
+        .with(position)
+        .build()
+}
+
 pub fn new_grid_from_ast(map_ast: &MapAst) -> Grid<Cell> {
-    let cells = map_ast.iter().map(|e| Cell { tile: e.tile }).collect();
+    let cells = map_ast.iter().map(|e| Cell::new(e.tile)).collect();
     Grid::new_from(map_ast.get_width(), map_ast.get_height(), cells)
 }
 
 pub struct MapAstCell {
     pub tile: Tile,
     pub obj: Option<ObjectsType>,
+    /// a door spawned locked
+    pub locked: bool,
 }
 
 pub type MapAst = Grid<MapAstCell>;
 
+pub fn parse_map(cfg: &MapParserCfg, map: &str) -> Result<MapAst, ParseMapError> {
+    let parser = |ch| {
+        let tile = cfg
+            .raw_map_tiles
+            .iter()
+            .find(|(c, _)| *c == ch)
+            .map(|(_, tile)| *tile)?;
+
+        let obj = cfg
+            .raw_map_objects
+            .iter()
+            .find(|(c, _)| *c == ch)
+            .map(|(_, obj)| *obj);
+
+        let locked = cfg.raw_map_locked_doors.contains(&ch);
+
+        Some(MapAstCell { tile, obj, locked })
+    };
+
+    grid_string::parse_map(parser, map)
+}
+
 pub fn parse_map_objects(
     ecs: &mut World,
     source_pos: V2I,
     grid_id: Entity,
-    map_ast: MapAst,
+    map_ast: &MapAst,
 ) -> Result<(), ParseMapError> {
     map_ast.iter().enumerate().for_each(|(index, c)| {
         let mut local_pos = map_ast.index_to_coords(index as i32);
@@ -153,45 +471,74 @@ pub fn parse_map_objects(
 
         match c.obj {
             Some(ObjectsType::Door { vertical }) => {
-                let icon = if vertical { '|' } else { '-' };
-                ecs.create_entity()
-                    .with(pos)
-                    .with(Renderable {
-                        glyph: rltk::to_cp437(icon),
-                        fg: RGB::named(rltk::CYAN),
-                        bg: RGB::named(rltk::BLACK),
-                        priority: 0,
-                    })
-                    .with(ObjectsType::Door { vertical })
-                    .build();
+                let state = match c.tile {
+                    Tile::Door { open: true } => DoorState::Open,
+                    _ if c.locked => DoorState::Locked,
+                    _ => DoorState::Closed,
+                };
+                create_door(ecs, pos, vertical, state);
             }
-            Some(ObjectsType::Cockpit) => {
-                ecs.create_entity()
-                    .with(pos)
-                    .with(Renderable {
-                        glyph: rltk::to_cp437('C'),
-                        fg: RGB::named(rltk::BLUE),
-                        bg: RGB::named(rltk::BLACK),
-                        priority: 0,
-                    })
-                    .with(ObjectsType::Cockpit)
-                    .build();
+            Some(kind) if kind.is_ship_module() => {
+                create_ship_module(ecs, pos, kind);
             }
-            Some(ObjectsType::Engine) => {
-                ecs.create_entity()
-                    .with(pos)
-                    .with(Renderable {
-                        glyph: rltk::to_cp437('E'),
-                        fg: RGB::named(rltk::RED),
-                        bg: RGB::named(rltk::BLACK),
-                        priority: 0,
-                    })
-                    .with(ObjectsType::Engine)
-                    .build();
-            }
-            None => {}
+            // corpses, items and stairs are not part of the maps
+            Some(_) | None => {}
         }
     });
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::atmosphere::Air;
+    use crate::state::State;
+
+    #[test]
+    fn test_planet_zone_creatures_should_be_spawned_from_the_zone_seed() {
+        let mut state = State::new(cfg::Cfg::load().unwrap());
+        let mut spawn = |seed: u64| {
+            let grid = Grid::new_square(16, || Cell::with_air(Tile::Ground, Air::STANDARD));
+            let zone_id = create_planet_zone(&mut state.ecs, 0, grid, 3, seed);
+            let positions = state.ecs.read_storage::<Position>();
+            let creatures = state.ecs.read_storage::<Creature>();
+            let mut points = (&positions, &creatures)
+                .join()
+                .filter(|(pos, _)| pos.grid_id == zone_id)
+                .map(|(pos, _)| pos.point)
+                .collect::<Vec<_>>();
+            points.sort_by_key(|p| (p.y, p.x));
+            points
+        };
+
+        let first = spawn(7);
+        assert_eq!(3, first.len());
+        assert_eq!(first, spawn(7));
+        assert_ne!(first, spawn(8));
+    }
+
+    #[test]
+    fn test_legend_should_spawn_locked_doors() {
+        let mut state = State::new(cfg::Cfg::load().unwrap());
+        let legend = state.ecs.fetch::<cfg::Cfg>().map_parser.clone();
+        let ast = parse_map(&legend, "#=#\n#-#").unwrap();
+        let grid_id = state.ecs.create_entity().build();
+        parse_map_objects(&mut state.ecs, V2I::new(0, 0), grid_id, &ast).unwrap();
+
+        let positions = state.ecs.read_storage::<Position>();
+        let doors = state.ecs.read_storage::<Door>();
+        let mut states = (&positions, &doors)
+            .join()
+            .map(|(pos, door)| (pos.point, door.state))
+            .collect::<Vec<_>>();
+        states.sort_by_key(|(point, _)| point.y);
+        assert_eq!(
+            vec![
+                (V2I::new(1, 0), DoorState::Locked),
+                (V2I::new(1, 1), DoorState::Closed)
+            ],
+            states
+        );
+    }
+}
@@ -1,10 +1,15 @@
 use crate::{Location, P2};
-use specs::{Entity, ReadStorage};
+use specs::storage::MaskedStorage;
+use specs::{Entity, Storage};
+use std::ops::Deref;
 
-pub fn resolve_sector_pos(
-    locations: &ReadStorage<Location>,
+pub fn resolve_sector_pos<D>(
+    locations: &Storage<Location, D>,
     entity: Entity,
-) -> Option<(P2, Entity)> {
+) -> Option<(P2, Entity)>
+where
+    D: Deref<Target = MaskedStorage<Location>>,
+{
     match locations.get(entity) {
         Some(Location::Sector { pos, sector_id }) => Some((pos.clone(), *sector_id)),
         Some(Location::Orbit { target_id }) => resolve_sector_pos(locations, *target_id),
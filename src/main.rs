@@ -1,16 +1,12 @@
 extern crate core;
 
-use rltk::Rltk;
 use specs::prelude::*;
 use state::State;
 
 use crate::actions::actions_system::ActionsSystem;
+use crate::actions::auto_walk_system::AutoWalkSystem;
 use crate::actions::avatar_actions_system::FindAvatarActionsSystem;
 use crate::area::Area;
-use crate::commons::grid::NGrid;
-use crate::commons::v2i::V2I;
-use crate::commons::{grid_string, v2i};
-use crate::loader::MapAstCell;
 use crate::models::*;
 use crate::ship::Ship;
 use crate::view::cockpit_window::CockpitWindowState;
@@ -19,34 +15,81 @@ use crate::visibility_system::VisibilitySystem;
 
 pub mod actions;
 pub mod area;
+pub mod atmosphere;
 pub mod cfg;
+pub mod combat;
 pub mod commons;
+pub mod crew;
+pub mod dungeon_gen;
 pub mod events;
+pub mod galaxy_gen;
+pub mod game_time;
 pub mod gridref;
+pub mod inventory;
 pub mod loader;
 pub mod locations;
 pub mod models;
+pub mod save;
+pub mod scenery;
 pub mod sectors;
 pub mod ship;
+pub mod simulation;
+pub mod squad;
 pub mod state;
+pub mod survival_system;
+#[cfg(test)]
+pub mod test_utils;
+pub mod trade;
 pub mod utils;
 pub mod view;
 pub mod visibility_system;
+pub mod zone_gen;
+
+/// Advance the simulation by one tick, it has no dependency on rltk so can run headless
+pub fn run_systems(ecs: &mut World) {
+    let mut s = game_time::GameTimeSystem {};
+    s.run_now(ecs);
 
-pub fn run_systems(st: &mut State, _ctx: &mut Rltk) {
     let mut s = VisibilitySystem {};
-    s.run_now(&st.ecs);
+    s.run_now(ecs);
 
     let mut s = FindAvatarActionsSystem {};
-    s.run_now(&st.ecs);
+    s.run_now(ecs);
+
+    let mut s = crew::ai_system::CrewAiSystem {};
+    s.run_now(ecs);
+
+    let mut s = combat::creature_ai_system::CreatureAiSystem {};
+    s.run_now(ecs);
 
     let mut s = ActionsSystem {};
-    s.run_now(&st.ecs);
+    s.run_now(ecs);
+
+    let mut s = AutoWalkSystem {};
+    s.run_now(ecs);
+
+    let mut s = ship::ai_system::ShipAiSystem {};
+    s.run_now(ecs);
 
     let mut s = ship::systems::FlyToSystem {};
-    s.run_now(&st.ecs);
+    s.run_now(ecs);
+
+    let mut s = ship::encounter::EncounterSystem {};
+    s.run_now(ecs);
+
+    let mut s = ship::damage::ShipDamageSystem {};
+    s.run_now(ecs);
+
+    let mut s = atmosphere::AtmosphereSystem {};
+    s.run_now(ecs);
+
+    let mut s = survival_system::SurvivalSystem {};
+    s.run_now(ecs);
+
+    let mut s = events::MessageLogSystem {};
+    s.run_now(ecs);
 
-    st.ecs.maintain();
+    ecs.maintain();
 }
 
 fn main() -> rltk::BError {
@@ -60,131 +103,126 @@ fn main() -> rltk::BError {
     let context = RltkBuilder::simple80x50().with_title("Alien").build()?;
 
     // initialize
-    let cfg = cfg::Cfg::new();
-
-    let parser = |ch| {
-        let tile = cfg
-            .map_parser
-            .raw_map_tiles
-            .iter()
-            .find(|(c, tile)| *c == ch)
-            .map(|(_, tile)| *tile)?;
-
-        let obj = cfg
-            .map_parser
-            .raw_map_objects
-            .iter()
-            .find(|(c, tile)| *c == ch)
-            .map(|(_, obj)| *obj);
-
-        Some(MapAstCell {
-            tile: tile,
-            obj: obj,
-        })
-    };
-
-    let ship_map_ast = grid_string::parse_map(parser, cfg::SHIP_MAP).expect("fail to load map");
-    let ship_grid = loader::new_grid_from_ast(&ship_map_ast);
-
-    let house_ast = grid_string::parse_map(parser, cfg::HOUSE_MAP).expect("fail to load house map");
-    let house_grid = loader::new_grid_from_ast(&house_ast);
-
-    let spawn_x = ship_grid.get_width() / 2 - 5;
-    let spawn_y = ship_grid.get_height() / 2;
+    let cfg = cfg::Cfg::load().unwrap_or_else(|e| panic!("fail to load cfg: {}", e));
 
     let mut gs = State::new(cfg);
     gs.ecs.insert(Window::World);
     gs.ecs.insert(CockpitWindowState::default());
 
-    // load scenery
-    let sector_id = loader::create_sector(&mut gs.ecs);
-    log::debug!("sector id {:?}", sector_id);
-
-    let mut planets_zones: Vec<(Entity, SurfaceTileKind)> = (0..3)
-        .map(|i| loader::create_planet_zone(&mut gs.ecs, i, 100, area::Tile::Ground))
-        .map(|e| (e, SurfaceTileKind::Plain))
-        .collect();
-
-    let house_pos = V2I::new(15, 15);
-    let house_grid_id = loader::create_planet_zone_from(
-        &mut gs.ecs,
-        3,
-        100,
-        area::Tile::Ground,
-        vec![(house_pos, &house_grid)],
-    );
-    planets_zones.push((house_grid_id, SurfaceTileKind::Structure));
-
-    log::debug!("planet zones id {:?}", planets_zones);
-
-    let planet_id = loader::create_planet(
-        &mut gs.ecs,
-        "Planet X",
-        Location::Sector {
-            sector_id,
-            pos: P2::new(5, 0),
-        },
-        planets_zones,
-        2,
-    );
-    log::debug!("planet id {:?}", planet_id);
-
-    let ship_location = Location::Orbit {
-        target_id: planet_id,
+    // load scenery, with "--seed <n>" the galaxy is generated using the scenery as template
+    let args: Vec<String> = std::env::args().collect();
+    let (scenery_path, seed) = match args.get(1).map(|s| s.as_str()) {
+        Some("--seed") => {
+            let seed = args
+                .get(2)
+                .and_then(|s| s.parse::<u64>().ok())
+                .unwrap_or_else(|| panic!("invalid seed, usage: --seed <n> [scenery]"));
+            (args.get(3).cloned(), Some(seed))
+        }
+        _ => (args.get(1).cloned(), None),
     };
-    // let ship_location = Location::Sector {
-    //     sector_id: sector_id,
-    //     pos: P2::new(0, 0),
-    // }
-    let ship_id = loader::create_ship(
-        &mut gs.ecs,
-        "ship",
-        Ship {
-            current_command: ship::Command::Idle,
-            move_calm_down: 0,
-        },
-        ship_location,
-        NGrid::from_grid(ship_grid),
-    );
-    log::debug!("ship id {:?}", ship_id);
-
-    let avatar_entity_id = loader::create_avatar(
-        &mut gs.ecs,
-        Position {
-            grid_id: ship_id,
-            point: (spawn_x, spawn_y).into(),
-        },
-    );
-    log::info!("avatar id: {:?}", avatar_entity_id);
-
-    gs.ecs.insert(Player::new(avatar_entity_id));
-
-    // load objects
-    loader::parse_map_objects(&mut gs.ecs, v2i::ZERO, ship_id, ship_map_ast)
-        .expect("fail to load map objects");
-    loader::parse_map_objects(&mut gs.ecs, house_pos, house_grid_id, house_ast)
-        .expect("fail to load map objects");
-
-    sectors::update_bodies_list(&mut gs.ecs);
+    let mut scenery_cfg = match &scenery_path {
+        #[cfg(not(target_arch = "wasm32"))]
+        Some(path) => scenery::load_scenery_file(path),
+        _ => scenery::load_default_scenery(),
+    }
+    .unwrap_or_else(|e| panic!("fail to load scenery {:?}: {}", scenery_path, e));
+    if let Some(seed) = seed {
+        let mut gen_cfg = galaxy_gen::load_default()
+            .unwrap_or_else(|e| panic!("fail to load galaxy generator cfg: {}", e));
+        gen_cfg.seed = seed;
+        scenery_cfg = galaxy_gen::generate(&gen_cfg, &scenery_cfg)
+            .unwrap_or_else(|e| panic!("fail to generate galaxy: {}", e));
+    }
+    scenery::create_scenery(&mut gs.ecs, &scenery_cfg)
+        .unwrap_or_else(|e| panic!("fail to create scenery {:?}: {}", scenery_path, e));
 
     rltk::main_loop(context, gs)
 }
 
 #[cfg(test)]
 mod test {
-    use crate::cfg::Cfg;
-    use crate::state::State;
+    use crate::events::Event;
+    use crate::gridref::GridRef;
+    use crate::models::{Location, P2};
+    use crate::ship::Command;
+    use crate::test_utils::{assert_orbiting, get_avatar_position, new_state_basic_scenery};
+    use crate::view::window::Window;
+    use specs::prelude::*;
 
     #[test]
     fn test_acceptance() {
-        let mut s = new_state_basic_scenery();
-    }
-
-    pub fn new_state_basic_scenery() {
-        let mut state = new_state();
-    }
-
-    pub fn new_state() -> State {
-        State::new(Cfg::new())
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let ship_id = scenery.ships["ship"];
+        let planet_id = scenery.bodies["Planet X"];
+        let zone_id = scenery.zones["Planet X"][0];
+
+        // avatar start at cockpit and can interact with it
+        sim.interact();
+        sim.tick();
+        assert!(matches!(*sim.ecs().fetch::<Window>(), Window::Cockpit));
+
+        // fly until orbit the planet
+        sim.set_ship_command(
+            ship_id,
+            Command::FlyTo {
+                target_id: planet_id,
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { .. })
+            )
+        });
+        assert!(ticks.is_some(), "ship never arrive to planet");
+        assert_orbiting(&sim, ship_id, planet_id);
+        assert!(sim.events().contains(&Event::ArrivedOrbit {
+            ship_id,
+            body_id: planet_id
+        }));
+
+        // land
+        sim.set_ship_command(
+            ship_id,
+            Command::Land {
+                target_id: zone_id,
+                place_coords: P2::new(0, 0),
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::BodySurfacePlace { .. })
+            )
+        });
+        assert!(ticks.is_some(), "ship never land");
+        assert!(sim.events().contains(&Event::Landed { ship_id, zone_id }));
+        assert_eq!(zone_id, get_avatar_position(&sim).grid_id);
+        match sim.ecs().read_storage::<GridRef>().get(ship_id) {
+            Some(GridRef::Ref(id)) => assert_eq!(zone_id, *id),
+            other => panic!("unexpected ship grid {:?}", other),
+        }
+
+        // walk in the landed ship
+        let before = get_avatar_position(&sim).point;
+        sim.move_avatar(1, 0);
+        assert_eq!(before.translate(1, 0), get_avatar_position(&sim).point);
+
+        // launch back to orbit
+        sim.set_ship_command(ship_id, Command::Launch).unwrap();
+        sim.tick();
+        assert_orbiting(&sim, ship_id, planet_id);
+        assert_eq!(
+            Some(&Event::Launched {
+                ship_id,
+                body_id: planet_id
+            }),
+            sim.events().last()
+        );
+        assert_eq!(ship_id, get_avatar_position(&sim).grid_id);
+        assert_eq!(P2::new(9, 8), get_avatar_position(&sim).point);
     }
 }
@@ -1,25 +1,66 @@
 use crate::commons;
 use crate::commons::grid::Coord;
 use crate::commons::v2i::V2I;
+use crate::save::{entities_from, entities_into, EntityNotSaved};
 use serde::{Deserialize, Serialize};
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
 use specs_derive::*;
 
 pub type Index = usize;
 pub type P2 = V2I;
 
-#[derive(Component, Debug)]
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Avatar {}
 
-#[derive(Component, Debug, Clone)]
+/// Survival stats of living entities, updated by the SurvivalSystem
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stats {
+    pub health: i32,
+    pub max_health: i32,
+    pub oxygen: i32,
+    pub max_oxygen: i32,
+}
+
+impl Stats {
+    pub fn new(max_health: i32, max_oxygen: i32) -> Self {
+        Stats {
+            health: max_health,
+            max_health,
+            oxygen: max_oxygen,
+            max_oxygen,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+
+    pub fn restore(&mut self) {
+        self.health = self.max_health;
+        self.oxygen = self.max_oxygen;
+    }
+}
+
+/// Where the player avatar respawn after dying, it is a object so it moves together with its grid
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct SpawnPoint {}
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct Label {
     pub name: String,
 }
 
+/// Avatars controlled by the player, actions are given to the focused avatar or to all of them
+/// when in group mode
 #[derive(Debug)]
 pub struct Player {
+    /// focused avatar, the camera follow it
     avatar_id: Entity,
+    /// all controlled avatars in the order they joined
+    avatars: Vec<Entity>,
     bscurrent: BitSet,
+    group: bool,
 }
 
 impl Player {
@@ -29,7 +70,9 @@ impl Player {
 
         Player {
             avatar_id: current,
+            avatars: vec![current],
             bscurrent: bsc,
+            group: false,
         }
     }
 
@@ -37,9 +80,78 @@ impl Player {
         return self.avatar_id;
     }
 
+    /// all controlled avatars
     pub fn get_avatarset(&self) -> &BitSet {
         &self.bscurrent
     }
+
+    pub fn get_avatars(&self) -> &Vec<Entity> {
+        &self.avatars
+    }
+
+    pub fn is_avatar(&self, entity: Entity) -> bool {
+        self.avatars.contains(&entity)
+    }
+
+    /// avatars that receive the player commands, the focused one or all when in group mode
+    pub fn get_controlled(&self) -> BitSet {
+        if self.group {
+            self.bscurrent.clone()
+        } else {
+            let mut bs = BitSet::new();
+            bs.add(self.avatar_id.id());
+            bs
+        }
+    }
+
+    pub fn add_avatar(&mut self, avatar_id: Entity) {
+        if !self.avatars.contains(&avatar_id) {
+            self.avatars.push(avatar_id);
+        }
+        self.bscurrent.add(avatar_id.id());
+    }
+
+    /// the last avatar can not be removed, when the focused avatar is removed the focus move to
+    /// the first one
+    pub fn remove_avatar(&mut self, avatar_id: Entity) -> bool {
+        if self.avatars.len() <= 1 || !self.avatars.contains(&avatar_id) {
+            return false;
+        }
+
+        self.avatars.retain(|e| *e != avatar_id);
+        self.bscurrent.remove(avatar_id.id());
+        if self.avatar_id == avatar_id {
+            self.avatar_id = self.avatars[0];
+        }
+        true
+    }
+
+    pub fn set_focus(&mut self, avatar_id: Entity) -> bool {
+        if !self.avatars.contains(&avatar_id) {
+            return false;
+        }
+        self.avatar_id = avatar_id;
+        true
+    }
+
+    /// move the focus to the next avatar, return the new focused avatar
+    pub fn focus_next(&mut self) -> Entity {
+        let index = self
+            .avatars
+            .iter()
+            .position(|e| *e == self.avatar_id)
+            .unwrap_or(0);
+        self.avatar_id = self.avatars[(index + 1) % self.avatars.len()];
+        self.avatar_id
+    }
+
+    pub fn is_group(&self) -> bool {
+        self.group
+    }
+
+    pub fn set_group(&mut self, group: bool) {
+        self.group = group;
+    }
 }
 
 #[derive(Component, Clone, Debug, PartialEq)]
@@ -48,6 +160,40 @@ pub struct Position {
     pub point: Coord,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PositionData<M> {
+    pub grid_id: M,
+    pub point: Coord,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Position
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = PositionData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(PositionData {
+            grid_id: ids(self.grid_id).ok_or(EntityNotSaved)?,
+            point: self.point,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Position {
+            grid_id: ids(data.grid_id).ok_or(EntityNotSaved)?,
+            point: data.point,
+        })
+    }
+}
+
 #[derive(Component, Clone, Debug, PartialEq)]
 pub struct GridPosition {
     pub grid_id: Option<Entity>,
@@ -78,7 +224,7 @@ impl Position {
     // }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug, Hash, Eq)]
+#[derive(Clone, Copy, PartialEq, Debug, Hash, Eq, Serialize, Deserialize)]
 pub enum Dir {
     N,
     E,
@@ -116,22 +262,208 @@ impl Dir {
 }
 #[derive(Component, PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum ObjectsType {
-    Door { vertical: bool },
+    Door {
+        vertical: bool,
+    },
     Engine,
     Cockpit,
+    /// pump air in and out of the compartment it is placed
+    Airlock,
+    FuelTank,
+    Reactor,
+    Sensors,
+    LifeSupport,
+    /// remains of a dead entity
+    Corpse,
+    /// item on the ground, see inventory::Item
+    Item,
+    /// leads to other level, see Stairs
+    Stairs {
+        down: bool,
+    },
+    /// hole in a ship hull, the cell is rubble until repaired back into a wall
+    Breach,
 }
 
+impl ObjectsType {
+    /// ship hardware, built with the ship and counted into its stats
+    pub fn is_ship_module(&self) -> bool {
+        matches!(
+            self,
+            ObjectsType::Engine
+                | ObjectsType::Cockpit
+                | ObjectsType::Airlock
+                | ObjectsType::FuelTank
+                | ObjectsType::Reactor
+                | ObjectsType::Sensors
+                | ObjectsType::LifeSupport
+        )
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ObjectsType::Door { .. } => "door",
+            ObjectsType::Engine => "engine",
+            ObjectsType::Cockpit => "cockpit",
+            ObjectsType::Airlock => "airlock",
+            ObjectsType::FuelTank => "fuel tank",
+            ObjectsType::Reactor => "reactor",
+            ObjectsType::Sensors => "sensors",
+            ObjectsType::LifeSupport => "life support",
+            ObjectsType::Corpse => "corpse",
+            ObjectsType::Item => "item",
+            ObjectsType::Stairs { .. } => "stairs",
+            ObjectsType::Breach => "hull breach",
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum DoorState {
+    Open,
+    Closed,
+    /// closed and it can not be opened
+    Locked,
+}
+
+/// State of a ObjectsType::Door, the cell Tile::Door is derived from it
+#[derive(Component, Clone, Debug, Serialize, Deserialize)]
+pub struct Door {
+    pub state: DoorState,
+}
+
+impl Door {
+    pub fn is_open(&self) -> bool {
+        self.state == DoorState::Open
+    }
+}
+
+/// Where a ObjectsType::Stairs leads to, who climb it arrives at target_pos on the target_id grid
+#[derive(Component, Clone, Debug)]
+pub struct Stairs {
+    pub target_id: Entity,
+    pub target_pos: P2,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct StairsData<M> {
+    pub target_id: M,
+    pub target_pos: P2,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Stairs
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = StairsData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(StairsData {
+            target_id: ids(self.target_id).ok_or(EntityNotSaved)?,
+            target_pos: self.target_pos,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Stairs {
+            target_id: ids(data.target_id).ok_or(EntityNotSaved)?,
+            target_pos: data.target_pos,
+        })
+    }
+}
+
+/// All sectors connected by jump points
 #[derive(Component, Debug, Clone)]
-pub struct Galaxy {}
+pub struct Galaxy {
+    pub sectors: Vec<Entity>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct GalaxyData<M> {
+    pub sectors: Vec<M>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Galaxy
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = GalaxyData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(GalaxyData {
+            sectors: entities_into(&self.sectors, ids)?,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Galaxy {
+            sectors: entities_from(data.sectors, ids)?,
+        })
+    }
+}
 
 #[derive(Component, Debug, Clone)]
 pub struct Sector {
+    /// position in the galaxy map
+    pub pos: P2,
     pub bodies: Vec<Entity>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SectorData<M> {
+    #[serde(default)]
+    pub pos: P2,
+    pub bodies: Vec<M>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Sector
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = SectorData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(SectorData {
+            pos: self.pos,
+            bodies: entities_into(&self.bodies, ids)?,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Sector {
+            pos: data.pos,
+            bodies: entities_from(data.bodies, ids)?,
+        })
+    }
+}
+
 impl Default for Sector {
     fn default() -> Self {
-        Sector { bodies: vec![] }
+        Sector {
+            pos: P2::new(0, 0),
+            bodies: vec![],
+        }
     }
 }
 
@@ -143,13 +475,65 @@ pub enum SectorBody {
     Ship,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub enum SectorBodyData<M> {
+    Planet,
+    Station,
+    Jump { target_pos: P2, target: M },
+    Ship,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for SectorBody
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = SectorBodyData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        let data = match self {
+            SectorBody::Planet => SectorBodyData::Planet,
+            SectorBody::Station => SectorBodyData::Station,
+            SectorBody::Jump { target_pos, target } => SectorBodyData::Jump {
+                target_pos: *target_pos,
+                target: ids(*target).ok_or(EntityNotSaved)?,
+            },
+            SectorBody::Ship => SectorBodyData::Ship,
+        };
+        Ok(data)
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        let body = match data {
+            SectorBodyData::Planet => SectorBody::Planet,
+            SectorBodyData::Station => SectorBody::Station,
+            SectorBodyData::Jump { target_pos, target } => SectorBody::Jump {
+                target_pos,
+                target: ids(target).ok_or(EntityNotSaved)?,
+            },
+            SectorBodyData::Ship => SectorBody::Ship,
+        };
+        Ok(body)
+    }
+}
+
 #[derive(Component, Debug, Clone)]
 pub struct SurfaceZone {}
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum SurfaceTileKind {
     Plain,
     Structure,
+    Mountains,
+    Water,
+    Forest,
+    Ruins,
 }
 
 #[derive(Component, Debug, Clone)]
@@ -160,6 +544,46 @@ pub struct Surface {
     pub zones: Vec<Entity>,
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SurfaceData<M> {
+    pub width: i32,
+    pub height: i32,
+    pub tiles: Vec<SurfaceTileKind>,
+    pub zones: Vec<M>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Surface
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = SurfaceData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(SurfaceData {
+            width: self.width,
+            height: self.height,
+            tiles: self.tiles.clone(),
+            zones: entities_into(&self.zones, ids)?,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Surface {
+            width: data.width,
+            height: data.height,
+            tiles: data.tiles,
+            zones: entities_from(data.zones, ids)?,
+        })
+    }
+}
+
 impl Surface {
     pub fn find_surface_body(
         entities: &Entities,
@@ -175,10 +599,62 @@ impl Surface {
         None
     }
 
+    /// terrain kind of the zone
+    pub fn find_zone_kind(
+        storage: &ReadStorage<Surface>,
+        zone_id: Entity,
+    ) -> Option<SurfaceTileKind> {
+        storage.join().find_map(|surface| {
+            let index = surface.zones.iter().position(|id| *id == zone_id)?;
+            surface.tiles.get(index).copied()
+        })
+    }
+
     pub fn get_tile(&self, x: i32, y: i32) -> Option<SurfaceTileKind> {
         let index = commons::grid::coords_to_index(self.width, Coord::new(x, y));
         self.tiles.get(index as usize).copied()
     }
+
+    pub fn get_zone(&self, x: i32, y: i32) -> Option<Entity> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = commons::grid::coords_to_index(self.width, Coord::new(x, y));
+        self.zones.get(index as usize).copied()
+    }
+
+    /// When the point is beyond the zone border, the same point mirrored into the neighbour zone.
+    /// None when it is inside the zone or there is no zone in that direction.
+    pub fn cross_border(
+        storage: &ReadStorage<Surface>,
+        zone_id: Entity,
+        zone_size: P2,
+        point: P2,
+    ) -> Option<Position> {
+        let border = |value: i32, size: i32| {
+            if value < 0 {
+                -1
+            } else if value >= size {
+                1
+            } else {
+                0
+            }
+        };
+        let (dx, dy) = (border(point.x, zone_size.x), border(point.y, zone_size.y));
+        if dx == 0 && dy == 0 {
+            return None;
+        }
+
+        let surface = storage.join().find(|s| s.zones.contains(&zone_id))?;
+        let index = surface.zones.iter().position(|id| *id == zone_id)? as i32;
+        let zone_coords = commons::grid::index_to_coord(surface.width, index);
+        let next_id = surface.get_zone(zone_coords.x + dx, zone_coords.y + dy)?;
+
+        Some(Position {
+            grid_id: next_id,
+            point: P2::new(point.x - dx * zone_size.x, point.y - dy * zone_size.y),
+        })
+    }
 }
 
 #[derive(Component, Debug, Clone)]
@@ -206,4 +682,119 @@ pub enum Location {
         // pos in surface
         grid_pos: P2,
     },
+    // docked, the ship grid is merged into the station grid
+    Docked {
+        station_id: Entity,
+        grid_pos: P2,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum LocationData<M> {
+    Sector {
+        sector_id: M,
+        pos: P2,
+    },
+    Orbit {
+        target_id: M,
+    },
+    BodySurface {
+        body_id: M,
+        place_coords: P2,
+    },
+    BodySurfacePlace {
+        body_id: M,
+        place_coords: P2,
+        grid_pos: P2,
+    },
+    Docked {
+        station_id: M,
+        grid_pos: P2,
+    },
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Location
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = LocationData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        let data = match self {
+            Location::Sector { sector_id, pos } => LocationData::Sector {
+                sector_id: ids(*sector_id).ok_or(EntityNotSaved)?,
+                pos: *pos,
+            },
+            Location::Orbit { target_id } => LocationData::Orbit {
+                target_id: ids(*target_id).ok_or(EntityNotSaved)?,
+            },
+            Location::BodySurface {
+                body_id,
+                place_coords,
+            } => LocationData::BodySurface {
+                body_id: ids(*body_id).ok_or(EntityNotSaved)?,
+                place_coords: *place_coords,
+            },
+            Location::BodySurfacePlace {
+                body_id,
+                place_coords,
+                grid_pos,
+            } => LocationData::BodySurfacePlace {
+                body_id: ids(*body_id).ok_or(EntityNotSaved)?,
+                place_coords: *place_coords,
+                grid_pos: *grid_pos,
+            },
+            Location::Docked {
+                station_id,
+                grid_pos,
+            } => LocationData::Docked {
+                station_id: ids(*station_id).ok_or(EntityNotSaved)?,
+                grid_pos: *grid_pos,
+            },
+        };
+        Ok(data)
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        let location = match data {
+            LocationData::Sector { sector_id, pos } => Location::Sector {
+                sector_id: ids(sector_id).ok_or(EntityNotSaved)?,
+                pos,
+            },
+            LocationData::Orbit { target_id } => Location::Orbit {
+                target_id: ids(target_id).ok_or(EntityNotSaved)?,
+            },
+            LocationData::BodySurface {
+                body_id,
+                place_coords,
+            } => Location::BodySurface {
+                body_id: ids(body_id).ok_or(EntityNotSaved)?,
+                place_coords,
+            },
+            LocationData::BodySurfacePlace {
+                body_id,
+                place_coords,
+                grid_pos,
+            } => Location::BodySurfacePlace {
+                body_id: ids(body_id).ok_or(EntityNotSaved)?,
+                place_coords,
+                grid_pos,
+            },
+            LocationData::Docked {
+                station_id,
+                grid_pos,
+            } => Location::Docked {
+                station_id: ids(station_id).ok_or(EntityNotSaved)?,
+                grid_pos,
+            },
+        };
+        Ok(location)
+    }
 }
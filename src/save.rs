@@ -0,0 +1,409 @@
+use crate::actions::EntityActions;
+use crate::cfg::Cfg;
+use crate::combat::{Combat, Creature, Faction};
+use crate::crew::Crew;
+use crate::game_time::{Energy, GameTime};
+use crate::gridref::GridRef;
+use crate::inventory::{Inventory, Item};
+use crate::models::{
+    Avatar, Door, Galaxy, Label, Location, ObjectsType, Player, Position, Sector, SectorBody,
+    SpawnPoint, Stairs, Stats, Surface,
+};
+use crate::ship::encounter::Encounter;
+use crate::ship::{ModuleHealth, Ship, ShipAi};
+use crate::state::State;
+use crate::trade::{Cargo, Market};
+use crate::view::cockpit_window::CockpitWindowState;
+use crate::view::window::Window;
+use crate::view::{Renderable, Viewshed};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{
+    DeserializeComponents, MarkerAllocator, SerializeComponents, SimpleMarker,
+    SimpleMarkerAllocator,
+};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt;
+
+/// Increase every time the save format change in a non compatible way
+/// - 2: ships fuel, hull and ai, ships encounters
+pub const SAVE_VERSION: u32 = 2;
+pub const SAVE_FILE: &str = "savegame.json";
+
+/// Marker type for all entities that are persisted in the save file
+pub struct SerializeMe;
+
+pub type SaveMarker = SimpleMarker<SerializeMe>;
+pub type SaveMarkerAllocator = SimpleMarkerAllocator<SerializeMe>;
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    UnsupportedVersion(u32),
+    NoPlayer,
+    /// the named component storage could not be converted
+    Component(&'static str, serde_json::Error),
+}
+
+impl From<std::io::Error> for SaveError {
+    fn from(e: std::io::Error) -> Self {
+        SaveError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(e: serde_json::Error) -> Self {
+        SaveError::Json(e)
+    }
+}
+
+/// Error of the components conversion, a reference to an entity that is not saved. The specs
+/// derive of ConvertSaveload names its error `NoError`, so modules deriving it for components
+/// that hold other converted components import this type with that name
+#[derive(Debug)]
+pub struct EntityNotSaved;
+
+impl fmt::Display for EntityNotSaved {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reference to an entity that is not saved")
+    }
+}
+
+impl From<Infallible> for EntityNotSaved {
+    fn from(e: Infallible) -> Self {
+        match e {}
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SaveFile {
+    version: u32,
+    /// all saved entities, in the same order they were created
+    entities: Vec<SaveMarker>,
+    avatar_id: SaveMarker,
+    avatars: Vec<SaveMarker>,
+    /// player squad is moving as a group
+    #[serde(default)]
+    group: bool,
+    #[serde(default)]
+    game_time: GameTime,
+    /// serialized storage for each component, indexed by the component name
+    components: BTreeMap<String, serde_json::Value>,
+}
+
+/// Call the given macro with the list of all components that are persisted. Any new component
+/// registered in the World must be added here to be saved.
+macro_rules! with_saved_components {
+    ($m:ident, $ecs:expr, $components:expr) => {
+        $m!(
+            $ecs,
+            $components,
+            Position,
+            Renderable,
+            Viewshed,
+            ObjectsType,
+            Door,
+            Stairs,
+            Stats,
+            SpawnPoint,
+            Energy,
+            EntityActions,
+            Crew,
+            Faction,
+            Combat,
+            Creature,
+            Item,
+            Inventory,
+            Cargo,
+            Market,
+            Ship,
+            ModuleHealth,
+            ShipAi,
+            Encounter,
+            Avatar,
+            Location,
+            Surface,
+            Sector,
+            Galaxy,
+            Label,
+            SectorBody,
+            GridRef
+        )
+    };
+}
+
+macro_rules! serialize_individually {
+    ($ecs:expr, $components:expr, $( $type:ty ),*) => {
+        $(
+            let value = SerializeComponents::<EntityNotSaved, SaveMarker>::serialize(
+                &($ecs.read_storage::<$type>(),),
+                &$ecs.entities(),
+                &$ecs.read_storage::<SaveMarker>(),
+                serde_json::value::Serializer,
+            )
+            .map_err(|e| SaveError::Component(stringify!($type), e))?;
+            $components.insert(stringify!($type).to_string(), value);
+        )*
+    };
+}
+
+macro_rules! deserialize_individually {
+    ($ecs:expr, $components:expr, $( $type:ty ),*) => {
+        $(
+            if let Some(value) = $components.remove(stringify!($type)) {
+                DeserializeComponents::<EntityNotSaved, _>::deserialize(
+                    &mut ($ecs.write_storage::<$type>(),),
+                    &$ecs.entities(),
+                    &mut $ecs.write_storage::<SaveMarker>(),
+                    &mut $ecs.write_resource::<SaveMarkerAllocator>(),
+                    value,
+                )
+                .map_err(|e| SaveError::Component(stringify!($type), e))?;
+            }
+        )*
+    };
+}
+
+/// Convert a list of entities into markers, used by components that hold entities collections
+pub fn entities_into<M, F>(list: &[Entity], mut ids: F) -> Result<Vec<M>, EntityNotSaved>
+where
+    F: FnMut(Entity) -> Option<M>,
+{
+    list.iter().map(|e| ids(*e).ok_or(EntityNotSaved)).collect()
+}
+
+/// Convert a list of markers back into entities, used by components that hold entities collections
+pub fn entities_from<M, F>(list: Vec<M>, mut ids: F) -> Result<Vec<Entity>, EntityNotSaved>
+where
+    F: FnMut(M) -> Option<Entity>,
+{
+    list.into_iter()
+        .map(|m| ids(m).ok_or(EntityNotSaved))
+        .collect()
+}
+
+/// Mark all alive entities, so any reference between entities can be resolved on load
+fn mark_all(ecs: &World) {
+    let entities = ecs.entities();
+    let mut markers = ecs.write_storage::<SaveMarker>();
+    let mut allocator = ecs.write_resource::<SaveMarkerAllocator>();
+    for e in (&entities).join() {
+        allocator.mark(e, &mut markers);
+    }
+}
+
+pub fn save_world(ecs: &World) -> Result<String, SaveError> {
+    mark_all(ecs);
+
+    let (entities, avatar_id, avatars, group) = {
+        let entities = ecs.entities();
+        let markers = ecs.read_storage::<SaveMarker>();
+        let player = ecs.fetch::<Player>();
+
+        let all = (&entities, &markers)
+            .join()
+            .map(|(_, m)| *m)
+            .collect::<Vec<_>>();
+        let avatar_id = *markers
+            .get(player.get_avatar_id())
+            .ok_or(SaveError::NoPlayer)?;
+        let avatars = player
+            .get_avatars()
+            .iter()
+            .filter_map(|e| markers.get(*e).cloned())
+            .collect::<Vec<_>>();
+
+        (all, avatar_id, avatars, player.is_group())
+    };
+
+    let mut components = BTreeMap::new();
+    with_saved_components!(serialize_individually, ecs, components);
+
+    let save = SaveFile {
+        version: SAVE_VERSION,
+        entities,
+        avatar_id,
+        avatars,
+        group,
+        game_time: (*ecs.fetch::<GameTime>()).clone(),
+        components,
+    };
+
+    Ok(serde_json::to_string(&save)?)
+}
+
+/// Load the save into the world, it expect a fresh World with all components registered
+pub fn load_world(ecs: &mut World, json: &str) -> Result<(), SaveError> {
+    let mut save: SaveFile = serde_json::from_str(json)?;
+    if save.version != SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(save.version));
+    }
+
+    // create all entities first to keep same creation order
+    let resolve = |ecs: &World, marker: SaveMarker| -> Entity {
+        ecs.write_resource::<SaveMarkerAllocator>().retrieve_entity(
+            marker,
+            &mut ecs.write_storage::<SaveMarker>(),
+            &ecs.entities(),
+        )
+    };
+
+    for marker in &save.entities {
+        resolve(ecs, *marker);
+    }
+
+    let components = &mut save.components;
+    with_saved_components!(deserialize_individually, ecs, components);
+
+    let mut player = Player::new(resolve(ecs, save.avatar_id));
+    for marker in save.avatars {
+        player.add_avatar(resolve(ecs, marker));
+    }
+    player.set_group(save.group);
+    ecs.insert(player);
+    ecs.insert(save.game_time);
+
+    ecs.maintain();
+
+    Ok(())
+}
+
+pub fn save_game(ecs: &World, path: &str) -> Result<(), SaveError> {
+    let json = save_world(ecs)?;
+    std::fs::write(path, json)?;
+    Ok(())
+}
+
+pub fn load_game(cfg: Cfg, path: &str) -> Result<State, SaveError> {
+    let json = std::fs::read_to_string(path)?;
+    let mut state = State::new(cfg);
+    load_world(&mut state.ecs, &json)?;
+    state.ecs.insert(Window::World);
+    state.ecs.insert(CockpitWindowState::default());
+    Ok(state)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::P2;
+    use crate::{scenery, ship};
+
+    fn new_landed_state() -> (State, Entity, Entity) {
+        let mut state = State::new(Cfg::load().unwrap());
+        let scenery_cfg = scenery::load_default_scenery().unwrap();
+        let scenery = scenery::create_scenery(&mut state.ecs, &scenery_cfg).unwrap();
+
+        let ship_id = scenery.ships["ship"];
+        let zone_id = scenery.zones["Planet X"][3];
+        state
+            .ecs
+            .write_storage::<Ship>()
+            .get_mut(ship_id)
+            .unwrap()
+            .current_command = ship::Command::Land {
+            target_id: zone_id,
+            place_coords: P2::new(1, 1),
+        };
+
+        state.ecs.write_resource::<GameTime>().elapsed = 1;
+        ship::systems::FlyToSystem {}.run_now(&state.ecs);
+        state.ecs.maintain();
+
+        (state, ship_id, zone_id)
+    }
+
+    #[test]
+    fn test_save_and_load_landed_ship() {
+        let (state, ship_id, zone_id) = new_landed_state();
+        let json = save_world(&state.ecs).unwrap();
+
+        let mut loaded = State::new(Cfg::load().unwrap());
+        load_world(&mut loaded.ecs, &json).unwrap();
+
+        // entities are recreated in the same order
+        let player = loaded.ecs.fetch::<Player>();
+        let avatar_id = player.get_avatar_id();
+        let positions = loaded.ecs.read_storage::<Position>();
+        assert_eq!(zone_id, positions.get(avatar_id).unwrap().grid_id);
+
+        let grids = loaded.ecs.read_storage::<GridRef>();
+        match grids.get(ship_id) {
+            Some(GridRef::Ref(id)) => assert_eq!(zone_id, *id),
+            other => panic!("unexpected ship grid {:?}", other),
+        }
+        let area = GridRef::find_area(&grids, zone_id).unwrap();
+        assert_eq!(&vec![zone_id, ship_id], area.get_layers());
+        assert_eq!(2, area.get_grid().len());
+
+        drop(player);
+        drop(positions);
+        drop(grids);
+
+        // saving again should give exactly the same content
+        let json_loaded = save_world(&loaded.ecs).unwrap();
+        assert_eq!(json, json_loaded);
+    }
+
+    #[test]
+    fn test_load_should_fail_on_unknown_version() {
+        let (state, _, _) = new_landed_state();
+        let json = save_world(&state.ecs).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value["version"] = serde_json::json!(SAVE_VERSION + 1);
+
+        let mut loaded = State::new(Cfg::load().unwrap());
+        match load_world(&mut loaded.ecs, &value.to_string()) {
+            Err(SaveError::UnsupportedVersion(_)) => {}
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_save_should_fail_on_reference_to_deleted_entity() {
+        let (mut state, _, _) = new_landed_state();
+        let deleted_id = state.ecs.create_entity().build();
+        state.ecs.delete_entity(deleted_id).unwrap();
+        state.ecs.maintain();
+
+        let avatar_id = state.ecs.fetch::<Player>().get_avatar_id();
+        state
+            .ecs
+            .write_storage::<Inventory>()
+            .insert(
+                avatar_id,
+                Inventory {
+                    items: vec![deleted_id],
+                },
+            )
+            .unwrap();
+
+        match save_world(&state.ecs) {
+            Err(SaveError::Component("Inventory", _)) => {}
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn test_save_should_fail_on_position_in_deleted_grid() {
+        let (mut state, _, _) = new_landed_state();
+        let deleted_id = state.ecs.create_entity().build();
+        state.ecs.delete_entity(deleted_id).unwrap();
+        state.ecs.maintain();
+
+        let avatar_id = state.ecs.fetch::<Player>().get_avatar_id();
+        state
+            .ecs
+            .write_storage::<Position>()
+            .get_mut(avatar_id)
+            .unwrap()
+            .grid_id = deleted_id;
+
+        match save_world(&state.ecs) {
+            Err(SaveError::Component("Position", _)) => {}
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+}
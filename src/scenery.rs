@@ -0,0 +1,566 @@
+use crate::area::Tile;
+use crate::atmosphere::Air;
+use crate::cfg::{Cfg, CfgError, MapParserCfg};
+use crate::commons::grid::NGrid;
+use crate::commons::grid_string::ParseMapError;
+use crate::commons::recti::RectI;
+use crate::crew::Job;
+use crate::dungeon_gen::{self, DungeonCfg};
+use crate::inventory::{Item, ItemKind};
+use crate::loader::MapAst;
+use crate::models::{Location, Player, Position, SurfaceTileKind, P2};
+use crate::ship::{self, Command, Ship, ShipAi, ShipAiKind};
+use crate::trade::Cargo;
+use crate::{loader, sectors, zone_gen};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// default scenery, embedded so it is available without a file system like on wasm
+pub const DEFAULT_SCENERY: &str = include_str!("../data/scenery.json");
+
+/// Describe a full starting situation, all references between objects are done by name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SceneryCfg {
+    /// ascii maps used by buildings and ships
+    pub maps: BTreeMap<String, MapCfg>,
+    /// maps placed at random by the zones generator
+    #[serde(default)]
+    pub prefabs: Vec<String>,
+    pub sectors: Vec<SectorCfg>,
+    pub planets: Vec<PlanetCfg>,
+    #[serde(default)]
+    pub stations: Vec<StationCfg>,
+    #[serde(default)]
+    pub jumps: Vec<JumpCfg>,
+    pub ships: Vec<ShipCfg>,
+    pub avatar: AvatarCfg,
+    #[serde(default)]
+    pub crew: Vec<CrewCfg>,
+    #[serde(default)]
+    pub items: Vec<ItemCfg>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MapCfg {
+    /// legend file used to parse this map, when none the default Cfg legend is used
+    #[serde(default)]
+    pub legend: Option<String>,
+    /// one string per line
+    pub lines: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SectorCfg {
+    pub name: String,
+    /// position in the galaxy map
+    #[serde(default)]
+    pub pos: P2,
+}
+
+/// jump point at sector that move ships into the target sector at target_pos
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JumpCfg {
+    pub name: String,
+    pub sector: String,
+    pub pos: P2,
+    pub target_sector: String,
+    pub target_pos: P2,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanetCfg {
+    pub name: String,
+    pub sector: String,
+    pub pos: P2,
+    /// surface is a square of surface_size x surface_size zones
+    pub surface_size: i32,
+    pub zone_size: i32,
+    /// air of the planet ground
+    #[serde(default = "standard_air")]
+    pub air: Air,
+    pub zones: Vec<ZoneCfg>,
+}
+
+fn standard_air() -> Air {
+    Air::STANDARD
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneCfg {
+    pub kind: SurfaceTileKind,
+    pub tile: Tile,
+    /// placed on top of the generated zone content
+    #[serde(default)]
+    pub buildings: Vec<BuildingCfg>,
+    /// seed used to generate the zone content from its kind
+    #[serde(default)]
+    pub seed: u64,
+    /// number of hostile creatures spawned at random places
+    #[serde(default)]
+    pub creatures: u32,
+    /// dungeon below the zone, its entrance is placed in the first building
+    #[serde(default)]
+    pub dungeon: Option<DungeonCfg>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildingCfg {
+    pub map: String,
+    pub pos: P2,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StationCfg {
+    pub name: String,
+    pub sector: String,
+    pub pos: P2,
+    pub map: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipCfg {
+    pub name: String,
+    pub map: String,
+    pub location: LocationCfg,
+    #[serde(default)]
+    pub cargo: Cargo,
+    /// fuel in the tanks, full when not defined
+    #[serde(default)]
+    pub fuel: Option<u32>,
+    /// ships with ai are controlled by the ShipAiSystem
+    #[serde(default)]
+    pub ai: Option<ShipAiKind>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum LocationCfg {
+    Sector { sector: String, pos: P2 },
+    Orbit { body: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvatarCfg {
+    pub ship: String,
+    pub pos: P2,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrewCfg {
+    pub name: String,
+    pub ship: String,
+    /// position inside the ship map
+    pub pos: P2,
+    pub job: JobCfg,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ItemCfg {
+    pub kind: ItemKind,
+    pub ship: String,
+    /// position inside the ship map
+    pub pos: P2,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum JobCfg {
+    /// points are relative to the ship map
+    Patrol {
+        points: Vec<P2>,
+    },
+    ManCockpit,
+    RepairEngines,
+    FollowAvatar,
+}
+
+#[derive(Debug)]
+pub enum SceneryError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Legend(CfgError),
+    UnknownMap(String),
+    UnknownSector(String),
+    UnknownBody(String),
+    UnknownShip(String),
+    InvalidSurface(String),
+    ParseMap(String, ParseMapError),
+    InvalidGalaxyGen(String),
+    InvalidDungeon(String),
+}
+
+impl fmt::Display for SceneryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneryError::Io(e) => write!(f, "fail to read scenery: {}", e),
+            SceneryError::Json(e) => write!(f, "fail to parse scenery: {}", e),
+            SceneryError::Legend(e) => write!(f, "fail to load legend: {}", e),
+            SceneryError::UnknownMap(name) => write!(f, "unknown map {}", name),
+            SceneryError::UnknownSector(name) => write!(f, "unknown sector {}", name),
+            SceneryError::UnknownBody(name) => write!(f, "unknown body {}", name),
+            SceneryError::UnknownShip(name) => write!(f, "unknown ship {}", name),
+            SceneryError::InvalidSurface(name) => {
+                write!(f, "planet {} zones don't match its surface size", name)
+            }
+            SceneryError::ParseMap(name, e) => write!(f, "fail to parse map {}: {}", name, e),
+            SceneryError::InvalidGalaxyGen(reason) => {
+                write!(f, "invalid galaxy generator cfg: {}", reason)
+            }
+            SceneryError::InvalidDungeon(reason) => write!(f, "invalid dungeon cfg: {}", reason),
+        }
+    }
+}
+
+impl From<std::io::Error> for SceneryError {
+    fn from(e: std::io::Error) -> Self {
+        SceneryError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SceneryError {
+    fn from(e: serde_json::Error) -> Self {
+        SceneryError::Json(e)
+    }
+}
+
+/// Entities created from a scenery, indexed by its names
+#[derive(Debug)]
+pub struct Scenery {
+    pub galaxy_id: Entity,
+    pub sectors: HashMap<String, Entity>,
+    pub bodies: HashMap<String, Entity>,
+    pub zones: HashMap<String, Vec<Entity>>,
+    /// dungeon levels by the zone of its entrance, from top to bottom
+    pub dungeons: HashMap<Entity, Vec<Entity>>,
+    pub ships: HashMap<String, Entity>,
+    pub crew: HashMap<String, Entity>,
+    pub avatar_id: Entity,
+}
+
+pub fn parse_scenery(json: &str) -> Result<SceneryCfg, SceneryError> {
+    Ok(serde_json::from_str(json)?)
+}
+
+pub fn load_default_scenery() -> Result<SceneryCfg, SceneryError> {
+    parse_scenery(DEFAULT_SCENERY)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_scenery_file(path: &str) -> Result<SceneryCfg, SceneryError> {
+    let json = std::fs::read_to_string(path)?;
+    parse_scenery(&json)
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_legend(path: &str, _default_legend: &MapParserCfg) -> Result<MapParserCfg, SceneryError> {
+    MapParserCfg::load_file(path).map_err(SceneryError::Legend)
+}
+
+/// without a file system only the default legend, embedded in the binary, can be used
+#[cfg(target_arch = "wasm32")]
+fn load_legend(path: &str, default_legend: &MapParserCfg) -> Result<MapParserCfg, SceneryError> {
+    if path == crate::cfg::LEGEND_FILE {
+        return Ok(default_legend.clone());
+    }
+    let e = std::io::Error::new(std::io::ErrorKind::Unsupported, "no file system on wasm");
+    Err(SceneryError::Legend(CfgError::Io(path.to_string(), e)))
+}
+
+/// Instantiate all scenery objects into the world and set the Player resource
+pub fn create_scenery(ecs: &mut World, cfg: &SceneryCfg) -> Result<Scenery, SceneryError> {
+    let default_legend = ecs.fetch::<Cfg>().map_parser.clone();
+
+    let mut maps = HashMap::new();
+    for (name, map) in &cfg.maps {
+        let legend = match &map.legend {
+            Some(path) => load_legend(path, &default_legend)?,
+            None => default_legend.clone(),
+        };
+        let ast = loader::parse_map(&legend, &map.lines.join("\n"))
+            .map_err(|e| SceneryError::ParseMap(name.clone(), e))?;
+        maps.insert(name.as_str(), ast);
+    }
+    let get_map = |name: &str| -> Result<&MapAst, SceneryError> {
+        maps.get(name)
+            .ok_or_else(|| SceneryError::UnknownMap(name.to_string()))
+    };
+
+    let prefabs = cfg
+        .prefabs
+        .iter()
+        .map(|name| Ok(loader::new_grid_from_ast(get_map(name)?)))
+        .collect::<Result<Vec<_>, SceneryError>>()?;
+
+    let mut sector_ids = HashMap::new();
+    let mut body_ids = HashMap::new();
+    let mut zone_ids = HashMap::new();
+    let mut ship_ids = HashMap::new();
+    let mut dungeon_ids = HashMap::new();
+
+    let mut galaxy_sectors = vec![];
+    for sector in &cfg.sectors {
+        let sector_id = loader::create_sector(ecs, &sector.name, sector.pos);
+        log::debug!("sector {} id {:?}", sector.name, sector_id);
+        sector_ids.insert(sector.name.clone(), sector_id);
+        galaxy_sectors.push(sector_id);
+    }
+    let galaxy_id = loader::create_galaxy(ecs, galaxy_sectors);
+
+    let find_sector = |name: &String| -> Result<Entity, SceneryError> {
+        sector_ids
+            .get(name)
+            .copied()
+            .ok_or_else(|| SceneryError::UnknownSector(name.clone()))
+    };
+
+    for jump in &cfg.jumps {
+        let jump_id = loader::create_jump(
+            ecs,
+            &jump.name,
+            Location::Sector {
+                sector_id: find_sector(&jump.sector)?,
+                pos: jump.pos,
+            },
+            find_sector(&jump.target_sector)?,
+            jump.target_pos,
+        );
+        log::debug!("jump {} id {:?}", jump.name, jump_id);
+        body_ids.insert(jump.name.clone(), jump_id);
+    }
+
+    for planet in &cfg.planets {
+        let sector_id = find_sector(&planet.sector)?;
+
+        if planet.surface_size * planet.surface_size != planet.zones.len() as i32 {
+            return Err(SceneryError::InvalidSurface(planet.name.clone()));
+        }
+
+        let mut zones = vec![];
+        for (index, zone) in planet.zones.iter().enumerate() {
+            let mut buildings = vec![];
+            for building in &zone.buildings {
+                buildings.push((building.map.clone(), building.pos));
+            }
+
+            let reserved = buildings
+                .iter()
+                .map(|(map, pos)| {
+                    let ast = get_map(map)?;
+                    Ok(RectI::new(pos.x, pos.y, ast.get_width(), ast.get_height()))
+                })
+                .collect::<Result<Vec<_>, SceneryError>>()?;
+            let mut rng = StdRng::seed_from_u64(zone.seed);
+            let layout = zone_gen::generate(
+                &mut rng,
+                zone.kind,
+                planet.zone_size,
+                zone.tile,
+                planet.air,
+                &prefabs,
+                &reserved,
+            );
+
+            let mut grid = layout.grid;
+            for (map, pos) in &buildings {
+                grid.merge(*pos, &loader::new_grid_from_ast(get_map(map)?));
+            }
+            for (prefab_index, pos) in layout.buildings {
+                buildings.push((cfg.prefabs[prefab_index].clone(), pos));
+            }
+
+            let zone_id = loader::create_planet_zone(ecs, index, grid, zone.creatures, zone.seed);
+
+            for (map, pos) in &buildings {
+                loader::parse_map_objects(ecs, *pos, zone_id, get_map(map)?)
+                    .map_err(|e| SceneryError::ParseMap(map.clone(), e))?;
+            }
+
+            if let Some(dungeon) = &zone.dungeon {
+                // entrance at the first free floor of the first building, or next to the landing
+                let mut entrance = None;
+                if let Some((map, pos)) = buildings.first() {
+                    let ast = get_map(map)?;
+                    entrance = ast
+                        .iter()
+                        .position(|c| c.tile == Tile::Floor && c.obj.is_none())
+                        .map(|index| ast.index_to_coords(index as i32).translate(pos.x, pos.y));
+                }
+                let entrance = entrance.unwrap_or_else(|| {
+                    zone_gen::landing_area(planet.zone_size)
+                        .get_top_left()
+                        .translate(-2, -2)
+                });
+
+                let level_ids = dungeon_gen::create_dungeon(
+                    ecs,
+                    &mut rng,
+                    dungeon,
+                    Position {
+                        grid_id: zone_id,
+                        point: entrance,
+                    },
+                )?;
+                log::debug!("zone {:?} dungeon levels {:?}", zone_id, level_ids);
+                dungeon_ids.insert(zone_id, level_ids);
+            }
+
+            zones.push((zone_id, zone.kind));
+        }
+        log::debug!("planet {} zones id {:?}", planet.name, zones);
+
+        let planet_id = loader::create_planet(
+            ecs,
+            &planet.name,
+            Location::Sector {
+                sector_id,
+                pos: planet.pos,
+            },
+            zones.clone(),
+            planet.surface_size,
+        );
+        log::debug!("planet {} id {:?}", planet.name, planet_id);
+
+        body_ids.insert(planet.name.clone(), planet_id);
+        zone_ids.insert(
+            planet.name.clone(),
+            zones.into_iter().map(|(e, _)| e).collect(),
+        );
+    }
+
+    for station in &cfg.stations {
+        let sector_id = find_sector(&station.sector)?;
+
+        let station_ast = get_map(&station.map)?;
+        let station_id = loader::create_station(
+            ecs,
+            &station.name,
+            Location::Sector {
+                sector_id,
+                pos: station.pos,
+            },
+            NGrid::from_grid(loader::new_grid_from_ast(station_ast)),
+        );
+        loader::parse_map_objects(ecs, P2::new(0, 0), station_id, station_ast)
+            .map_err(|e| SceneryError::ParseMap(station.map.clone(), e))?;
+        log::debug!("station {} id {:?}", station.name, station_id);
+
+        body_ids.insert(station.name.clone(), station_id);
+    }
+
+    for ship in &cfg.ships {
+        let location = match &ship.location {
+            LocationCfg::Sector { sector, pos } => Location::Sector {
+                sector_id: find_sector(sector)?,
+                pos: *pos,
+            },
+            LocationCfg::Orbit { body } => Location::Orbit {
+                target_id: *body_ids
+                    .get(body)
+                    .ok_or_else(|| SceneryError::UnknownBody(body.clone()))?,
+            },
+        };
+
+        let ship_ast = get_map(&ship.map)?;
+        let ship_id = loader::create_ship(
+            ecs,
+            &ship.name,
+            Ship {
+                current_command: Command::Idle,
+                move_calm_down: 0,
+                fuel: 0,
+                hull: ship::MAX_HULL,
+            },
+            ship.cargo.clone(),
+            location,
+            NGrid::from_grid(loader::new_grid_from_ast(ship_ast)),
+        );
+        loader::parse_map_objects(ecs, P2::new(0, 0), ship_id, ship_ast)
+            .map_err(|e| SceneryError::ParseMap(ship.map.clone(), e))?;
+        let fuel = ship
+            .fuel
+            .unwrap_or_else(|| ship::get_ship_stats(ecs, ship_id).fuel_capacity());
+        if let Some(ship) = ecs.write_storage::<Ship>().get_mut(ship_id) {
+            ship.fuel = fuel;
+        }
+        if let Some(kind) = ship.ai {
+            ecs.write_storage::<ShipAi>()
+                .insert(ship_id, ShipAi::new(kind))
+                .unwrap();
+        }
+        log::debug!("ship {} id {:?}", ship.name, ship_id);
+
+        ship_ids.insert(ship.name.clone(), ship_id);
+    }
+
+    let avatar_ship_id = *ship_ids
+        .get(&cfg.avatar.ship)
+        .ok_or_else(|| SceneryError::UnknownShip(cfg.avatar.ship.clone()))?;
+    let avatar_pos = Position {
+        grid_id: avatar_ship_id,
+        point: cfg.avatar.pos,
+    };
+    loader::create_spawn_point(ecs, avatar_pos.clone());
+    let avatar_id = loader::create_avatar(ecs, avatar_pos);
+    log::info!("avatar id: {:?}", avatar_id);
+
+    ecs.insert(Player::new(avatar_id));
+
+    let mut crew_ids = HashMap::new();
+    for crew in &cfg.crew {
+        let ship_id = *ship_ids
+            .get(&crew.ship)
+            .ok_or_else(|| SceneryError::UnknownShip(crew.ship.clone()))?;
+        let job = match &crew.job {
+            JobCfg::Patrol { points } => Job::Patrol {
+                points: points.clone(),
+                next: 0,
+            },
+            JobCfg::ManCockpit => Job::ManCockpit,
+            JobCfg::RepairEngines => Job::RepairEngines,
+            JobCfg::FollowAvatar => Job::Follow {
+                target_id: avatar_id,
+            },
+        };
+        let crew_id = loader::create_crew(
+            ecs,
+            &crew.name,
+            ship_id,
+            job,
+            Position {
+                grid_id: ship_id,
+                point: crew.pos,
+            },
+        );
+        log::debug!("crew {} id {:?}", crew.name, crew_id);
+        crew_ids.insert(crew.name.clone(), crew_id);
+    }
+
+    for item in &cfg.items {
+        let ship_id = *ship_ids
+            .get(&item.ship)
+            .ok_or_else(|| SceneryError::UnknownShip(item.ship.clone()))?;
+        loader::create_item(
+            ecs,
+            Item { kind: item.kind },
+            Position {
+                grid_id: ship_id,
+                point: item.pos,
+            },
+        );
+    }
+
+    sectors::update_bodies_list(ecs);
+
+    Ok(Scenery {
+        galaxy_id,
+        sectors: sector_ids,
+        bodies: body_ids,
+        zones: zone_ids,
+        dungeons: dungeon_ids,
+        ships: ship_ids,
+        crew: crew_ids,
+        avatar_id,
+    })
+}
@@ -26,3 +26,19 @@ pub fn update_bodies_list(ecs: &mut World) {
         }
     }
 }
+
+/// add the body to the sector bodies list, if it is not there yet
+pub fn add_body(sectors: &mut WriteStorage<Sector>, sector_id: Entity, body_id: Entity) {
+    if let Some(sector) = sectors.get_mut(sector_id) {
+        if !sector.bodies.contains(&body_id) {
+            sector.bodies.push(body_id);
+        }
+    }
+}
+
+/// remove the body from any sector bodies list
+pub fn remove_body(sectors: &mut WriteStorage<Sector>, body_id: Entity) {
+    for sector in (&mut *sectors).join() {
+        sector.bodies.retain(|e| *e != body_id);
+    }
+}
@@ -1,27 +1,571 @@
+pub mod ai_system;
+pub mod build;
+pub mod damage;
+pub mod encounter;
 pub mod systems;
 
-use crate::models::Player;
+use crate::gridref::GridRef;
+use crate::locations;
+use crate::models::{Location, ObjectsType, Player, Position};
+use crate::save::{EntityNotSaved, EntityNotSaved as NoError};
 use crate::P2;
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
+use specs::storage::MaskedStorage;
 use specs_derive::*;
+use std::ops::Deref;
 
+/// game ticks between each ship move of a standard ship
 pub const FLY_SLEEP_TIME: u32 = 60;
+/// engines of a standard ship
+pub const STANDARD_ENGINES: u32 = 6;
+/// fastest a ship can fly no matter how many engines it has
+pub const MIN_FLY_SLEEP_TIME: u32 = 15;
+pub const FUEL_PER_TANK: u32 = 50;
+/// fuel burned on each sector cell flown
+pub const FUEL_PER_STEP: u32 = 1;
+/// fuel burned to leave a planet surface
+pub const FUEL_PER_LAUNCH: u32 = 10;
+/// fuel harvested each HARVEST_TIME while landed on a planet
+pub const HARVEST_FUEL: u32 = 5;
+pub const HARVEST_TIME: u32 = 100;
+pub const POWER_PER_REACTOR: u32 = 10;
+pub const ENGINE_POWER: u32 = 1;
+pub const LIFE_SUPPORT_POWER: u32 = 2;
+pub const SENSORS_POWER: u32 = 1;
+/// sector cells scanned by each sensor
+pub const SENSOR_RANGE: i32 = 3;
+pub const CREW_PER_LIFE_SUPPORT: u32 = 4;
+pub const MAX_HULL: i32 = 100;
+pub const MODULE_HEALTH: i32 = 50;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Command {
     Idle,
-    FlyTo { target_id: Entity },
-    Land { target_id: Entity, place_coords: P2 },
+    FlyTo {
+        target_id: Entity,
+    },
+    Land {
+        target_id: Entity,
+        place_coords: P2,
+    },
     Launch,
+    Dock {
+        target_id: Entity,
+    },
+    Jump {
+        target_id: Entity,
+    },
+    /// collect fuel from the planet the ship is landed until the tanks are full
+    Harvest,
 }
 
-#[derive(Component, Debug, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
+pub enum CommandData<M> {
+    Idle,
+    FlyTo { target_id: M },
+    Land { target_id: M, place_coords: P2 },
+    Launch,
+    Dock { target_id: M },
+    Jump { target_id: M },
+    Harvest,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Command
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = CommandData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        let data = match self {
+            Command::Idle => CommandData::Idle,
+            Command::FlyTo { target_id } => CommandData::FlyTo {
+                target_id: ids(*target_id).ok_or(EntityNotSaved)?,
+            },
+            Command::Land {
+                target_id,
+                place_coords,
+            } => CommandData::Land {
+                target_id: ids(*target_id).ok_or(EntityNotSaved)?,
+                place_coords: *place_coords,
+            },
+            Command::Launch => CommandData::Launch,
+            Command::Dock { target_id } => CommandData::Dock {
+                target_id: ids(*target_id).ok_or(EntityNotSaved)?,
+            },
+            Command::Jump { target_id } => CommandData::Jump {
+                target_id: ids(*target_id).ok_or(EntityNotSaved)?,
+            },
+            Command::Harvest => CommandData::Harvest,
+        };
+        Ok(data)
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        let command = match data {
+            CommandData::Idle => Command::Idle,
+            CommandData::FlyTo { target_id } => Command::FlyTo {
+                target_id: ids(target_id).ok_or(EntityNotSaved)?,
+            },
+            CommandData::Land {
+                target_id,
+                place_coords,
+            } => Command::Land {
+                target_id: ids(target_id).ok_or(EntityNotSaved)?,
+                place_coords,
+            },
+            CommandData::Launch => Command::Launch,
+            CommandData::Dock { target_id } => Command::Dock {
+                target_id: ids(target_id).ok_or(EntityNotSaved)?,
+            },
+            CommandData::Jump { target_id } => Command::Jump {
+                target_id: ids(target_id).ok_or(EntityNotSaved)?,
+            },
+            CommandData::Harvest => Command::Harvest,
+        };
+        Ok(command)
+    }
+}
+
+#[derive(Component, ConvertSaveload, Debug, Clone)]
 pub struct Ship {
     pub current_command: Command,
     pub move_calm_down: u32,
+    pub fuel: u32,
+    /// hull integrity, a ship without hull can not fly
+    pub hull: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShipAiKind {
+    /// fly around all bodies of the sector
+    Patrol,
+    /// visit the stations and planets of the sector, waiting at each one to trade
+    Trader,
+    /// hunt other ships in sensors range and patrol while there is none
+    Pirate,
+}
+
+impl ShipAiKind {
+    pub const ALL: [ShipAiKind; 3] = [ShipAiKind::Patrol, ShipAiKind::Trader, ShipAiKind::Pirate];
+}
+
+/// NPC ship behaviour, its commands are decided by the ShipAiSystem
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShipAi {
+    pub kind: ShipAiKind,
+    /// index of the next body to visit
+    pub next: usize,
+    /// game ticks until a pirate hunts again after its prey fled or was boarded
+    #[serde(default)]
+    pub chase_calm_down: u32,
+}
+
+impl ShipAi {
+    pub fn new(kind: ShipAiKind) -> Self {
+        ShipAi {
+            kind,
+            next: 0,
+            chase_calm_down: 0,
+        }
+    }
+}
+
+/// Health of a ship module, broken modules do not count into the ship stats
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModuleHealth {
+    pub health: i32,
+    pub max_health: i32,
+}
+
+impl ModuleHealth {
+    pub fn new(max_health: i32) -> Self {
+        ModuleHealth {
+            health: max_health,
+            max_health,
+        }
+    }
+
+    pub fn is_broken(&self) -> bool {
+        self.health <= 0
+    }
+
+    pub fn is_damaged(&self) -> bool {
+        self.health < self.max_health
+    }
+
+    /// damage percent, from 0 when intact to 100 when broken
+    pub fn damage_percent(&self) -> u32 {
+        ((self.max_health - self.health.max(0)) * 100 / self.max_health.max(1)) as u32
+    }
+}
+
+/// Reactor power given to each kind of module, life support is powered first, then engines and
+/// last the sensors
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PowerBudget {
+    pub life_support: u32,
+    pub engines: u32,
+    pub sensors: u32,
+}
+
+/// Capabilities of a ship derived from the modules installed in its grid
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShipStats {
+    pub cockpits: u32,
+    pub engines: u32,
+    pub fuel_tanks: u32,
+    pub reactors: u32,
+    pub sensors: u32,
+    pub life_support: u32,
+    /// average damage percent of the working engines
+    pub engine_damage: u32,
+}
+
+impl ShipStats {
+    /// count the working modules on the ship grid, when landed or docked the ship objects belong
+    /// to the host grid so only the ones on top of the ship layer are counted
+    pub fn compute<G, P, H>(
+        grids: &Storage<GridRef, G>,
+        positions: &Storage<Position, P>,
+        objects: &ReadStorage<ObjectsType>,
+        healths: &Storage<ModuleHealth, H>,
+        ship_id: Entity,
+    ) -> ShipStats
+    where
+        G: Deref<Target = MaskedStorage<GridRef>>,
+        P: Deref<Target = MaskedStorage<Position>>,
+        H: Deref<Target = MaskedStorage<ModuleHealth>>,
+    {
+        let mut grid_id = ship_id;
+        let area = loop {
+            match grids.get(grid_id) {
+                Some(GridRef::Ref(id)) => grid_id = *id,
+                Some(GridRef::GMap(area)) => break area,
+                None => return ShipStats::default(),
+            }
+        };
+
+        let mut stats = ShipStats::default();
+        let mut engines_damage = 0;
+        for (pos, kind, health) in (positions, objects, healths.maybe()).join() {
+            if pos.grid_id != grid_id || area.get_layer_entity_at(&pos.point) != Some(ship_id) {
+                continue;
+            }
+            if health.map(|h| h.is_broken()).unwrap_or(false) {
+                continue;
+            }
+
+            match kind {
+                ObjectsType::Cockpit => stats.cockpits += 1,
+                ObjectsType::Engine => {
+                    stats.engines += 1;
+                    engines_damage += health.map(|h| h.damage_percent()).unwrap_or(0);
+                }
+                ObjectsType::FuelTank => stats.fuel_tanks += 1,
+                ObjectsType::Reactor => stats.reactors += 1,
+                ObjectsType::Sensors => stats.sensors += 1,
+                ObjectsType::LifeSupport => stats.life_support += 1,
+                _ => {}
+            }
+        }
+        stats.engine_damage = engines_damage.checked_div(stats.engines).unwrap_or(0);
+        stats
+    }
+
+    /// ship can be piloted and has engines to move
+    pub fn can_fly(&self) -> Result<(), String> {
+        if self.cockpits == 0 {
+            Err("ship has no cockpit".to_string())
+        } else if self.engines == 0 {
+            Err("ship has no engines".to_string())
+        } else if self.power_budget().engines == 0 {
+            Err("not enough power for the engines".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// game ticks between each ship move, more powered engines fly faster and damaged ones
+    /// slower. None without any
+    pub fn fly_sleep_time(&self) -> Option<u32> {
+        let engines = self.power_budget().engines;
+        if engines == 0 {
+            return None;
+        }
+        let sleep_time = FLY_SLEEP_TIME * STANDARD_ENGINES / engines;
+        let sleep_time = sleep_time * 100 / (100 - self.engine_damage.min(99));
+        Some(sleep_time.max(MIN_FLY_SLEEP_TIME))
+    }
+
+    pub fn fuel_capacity(&self) -> u32 {
+        self.fuel_tanks * FUEL_PER_TANK
+    }
+
+    pub fn power(&self) -> u32 {
+        self.reactors * POWER_PER_REACTOR
+    }
+
+    /// power required to run all modules
+    pub fn power_demand(&self) -> u32 {
+        self.life_support * LIFE_SUPPORT_POWER
+            + self.engines * ENGINE_POWER
+            + self.sensors * SENSORS_POWER
+    }
+
+    /// how many modules of each kind are powered
+    pub fn power_budget(&self) -> PowerBudget {
+        let mut available = self.power();
+        let mut take = |count: u32, cost: u32| {
+            let powered = count.min(available / cost);
+            available -= powered * cost;
+            powered
+        };
+
+        PowerBudget {
+            life_support: take(self.life_support, LIFE_SUPPORT_POWER),
+            engines: take(self.engines, ENGINE_POWER),
+            sensors: take(self.sensors, SENSORS_POWER),
+        }
+    }
+
+    pub fn sensor_range(&self) -> i32 {
+        self.power_budget().sensors as i32 * SENSOR_RANGE
+    }
+
+    /// crew members the ship can keep alive
+    pub fn crew_capacity(&self) -> u32 {
+        self.power_budget().life_support * CREW_PER_LIFE_SUPPORT
+    }
+}
+
+/// sector cells the ship can fly with the fuel
+pub fn fuel_range(fuel: u32) -> u32 {
+    fuel / FUEL_PER_STEP
+}
+
+/// sector cells to fly from the ship to other body of the same sector
+pub fn fly_distance(ecs: &World, ship_id: Entity, target_id: Entity) -> Option<u32> {
+    let locations = ecs.read_storage::<Location>();
+    let (from, from_sector) = locations::resolve_sector_pos(&locations, ship_id)?;
+    let (to, to_sector) = locations::resolve_sector_pos(&locations, target_id)?;
+    if from_sector != to_sector {
+        return None;
+    }
+    Some((to.x - from.x).abs().max((to.y - from.y).abs()) as u32)
+}
+
+fn is_landed(ecs: &World, ship_id: Entity) -> bool {
+    matches!(
+        ecs.read_storage::<Location>().get(ship_id),
+        Some(Location::BodySurface { .. }) | Some(Location::BodySurfacePlace { .. })
+    )
+}
+
+pub fn get_ship_stats(ecs: &World, ship_id: Entity) -> ShipStats {
+    ShipStats::compute(
+        &ecs.read_storage::<GridRef>(),
+        &ecs.read_storage::<Position>(),
+        &ecs.read_storage::<ObjectsType>(),
+        &ecs.read_storage::<ModuleHealth>(),
+        ship_id,
+    )
+}
+
+/// moving commands are refused when the ship can not fly or has not enough fuel
+pub fn set_ship_command(
+    ecs: &mut World,
+    ship_id: Entity,
+    ship_command: Command,
+) -> Result<(), String> {
+    let stats = get_ship_stats(ecs, ship_id);
+    if matches!(
+        ship_command,
+        Command::FlyTo { .. } | Command::Launch | Command::Jump { .. }
+    ) {
+        stats.can_fly()?;
+        let hull = ecs
+            .read_storage::<Ship>()
+            .get(ship_id)
+            .map(|ship| ship.hull);
+        if hull.map(|hull| hull <= 0).unwrap_or(false) {
+            return Err("hull is destroyed".to_string());
+        }
+    }
+
+    let fuel = ecs
+        .read_storage::<Ship>()
+        .get(ship_id)
+        .map(|ship| ship.fuel)
+        .unwrap_or(0);
+    match ship_command {
+        Command::FlyTo { target_id } => {
+            let distance = fly_distance(ecs, ship_id, target_id).unwrap_or(1).max(1);
+            if fuel < distance * FUEL_PER_STEP {
+                return Err(format!("not enough fuel to fly {} cells", distance));
+            }
+        }
+        Command::Launch if is_landed(ecs, ship_id) && fuel < FUEL_PER_LAUNCH => {
+            return Err("not enough fuel to launch".to_string());
+        }
+        Command::Harvest if !is_landed(ecs, ship_id) => {
+            return Err("ship must be landed to harvest fuel".to_string());
+        }
+        Command::Harvest if fuel >= stats.fuel_capacity() => {
+            return Err("fuel tanks are full".to_string());
+        }
+        _ => {}
+    }
+
+    log::info!("update ship {:?} command to {:?}", ship_id, ship_command);
+    ecs.write_storage::<Ship>()
+        .get_mut(ship_id)
+        .ok_or("not a ship")?
+        .current_command = ship_command;
+    Ok(())
 }
 
 pub fn enter_cockpit(_avatar: &mut Player) {
     // change avatar state to be on control o ship from the
     // cockpit
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::models::{Location, ObjectsType, P2};
+    use crate::ship::build::ShipPart;
+    use crate::simulation::Simulation;
+    use crate::test_utils::new_state_default_scenery;
+    use crate::trade::{Cargo, Commodity};
+
+    #[test]
+    fn test_ship_speed_should_depend_on_engines() {
+        let with_engines = |engines| ShipStats {
+            engines,
+            reactors: 10,
+            ..ShipStats::default()
+        };
+        assert_eq!(None, with_engines(0).fly_sleep_time());
+        assert_eq!(
+            Some(FLY_SLEEP_TIME),
+            with_engines(STANDARD_ENGINES).fly_sleep_time()
+        );
+        assert!(with_engines(3).fly_sleep_time() > with_engines(4).fly_sleep_time());
+        assert_eq!(Some(MIN_FLY_SLEEP_TIME), with_engines(100).fly_sleep_time());
+
+        let damaged = ShipStats {
+            engine_damage: 50,
+            ..with_engines(STANDARD_ENGINES)
+        };
+        assert_eq!(Some(FLY_SLEEP_TIME * 2), damaged.fly_sleep_time());
+    }
+
+    #[test]
+    fn test_power_should_be_shared_by_priority() {
+        let stats = ShipStats {
+            engines: 8,
+            reactors: 1,
+            sensors: 2,
+            life_support: 2,
+            ..ShipStats::default()
+        };
+        assert_eq!(14, stats.power_demand());
+        assert_eq!(
+            PowerBudget {
+                life_support: 2,
+                engines: 6,
+                sensors: 0,
+            },
+            stats.power_budget()
+        );
+        assert_eq!(0, stats.sensor_range());
+        assert_eq!(Some(FLY_SLEEP_TIME), stats.fly_sleep_time());
+    }
+
+    #[test]
+    fn test_ship_should_only_fly_with_cockpit_and_engines() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let station_id = scenery.bodies["Station Alpha"];
+
+        assert_eq!(
+            ShipStats {
+                cockpits: 1,
+                engines: 6,
+                fuel_tanks: 1,
+                reactors: 1,
+                sensors: 1,
+                life_support: 1,
+                engine_damage: 0,
+            },
+            get_ship_stats(sim.ecs(), ship_id)
+        );
+
+        let remove_all = |sim: &mut Simulation, kind: ObjectsType| {
+            let ids = (
+                &sim.ecs().entities(),
+                &sim.ecs().read_storage::<ObjectsType>(),
+            )
+                .join()
+                .filter(|(_, k)| **k == kind)
+                .map(|(id, _)| id)
+                .collect::<Vec<_>>();
+            sim.ecs_mut().delete_entities(&ids).unwrap();
+        };
+        let fly_to_station = Command::FlyTo {
+            target_id: station_id,
+        };
+
+        remove_all(&mut sim, ObjectsType::Cockpit);
+        remove_all(&mut sim, ObjectsType::Engine);
+        assert_eq!(
+            Err("ship has no cockpit".to_string()),
+            sim.set_ship_command(ship_id, fly_to_station)
+        );
+        assert_eq!(
+            Err("ship has no cockpit".to_string()),
+            sim.set_ship_command(ship_id, Command::Launch)
+        );
+
+        {
+            let mut cargos = sim.ecs_mut().write_storage::<Cargo>();
+            let cargo = cargos.get_mut(ship_id).unwrap();
+            cargo.goods.insert(Commodity::Ore, 5);
+            cargo.goods.insert(Commodity::Electronics, 5);
+        }
+        sim.build(ship_id, P2::new(7, 9), ShipPart::Cockpit)
+            .unwrap();
+        assert_eq!(
+            Err("ship has no engines".to_string()),
+            sim.set_ship_command(ship_id, fly_to_station)
+        );
+
+        // a single engine flies slower than the standard ship
+        sim.build(ship_id, P2::new(7, 3), ShipPart::Engine).unwrap();
+        sim.set_ship_command(ship_id, fly_to_station).unwrap();
+        sim.run_until(100, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Sector { .. })
+            )
+        })
+        .expect("ship never leave orbit");
+        let calm_down = sim
+            .ecs()
+            .read_storage::<Ship>()
+            .get(ship_id)
+            .unwrap()
+            .move_calm_down;
+        assert!(calm_down > FLY_SLEEP_TIME);
+    }
+}
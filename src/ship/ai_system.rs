@@ -0,0 +1,319 @@
+use crate::game_time::GameTime;
+use crate::gridref::GridRef;
+use crate::models::{ObjectsType, Position};
+use crate::ship::encounter::{Encounter, EncounterAction};
+use crate::ship::{self, Command, ModuleHealth, ShipAi, ShipAiKind, ShipStats};
+use crate::trade::{self, Cargo, Market};
+use crate::{locations, unwrap_or_continue};
+use crate::{Location, Sector, SectorBody, Ship};
+use log::info;
+use specs::prelude::*;
+
+/// game ticks a trader waits at each body it visits
+pub const TRADE_WAIT_TIME: u32 = 200;
+/// game ticks a NPC ship waits on a peaceful encounter before resuming its route
+pub const ENCOUNTER_WAIT_TIME: u64 = 100;
+/// game ticks a pirate leaves other ships alone after one fled or was boarded
+pub const CHASE_CALM_DOWN: u32 = 300;
+
+/// Decide the commands of the NPC ships from its ai, the commands are executed by the
+/// FlyToSystem and the encounter actions by the EncounterSystem
+pub struct ShipAiSystem {}
+
+impl<'a> System<'a> for ShipAiSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Ship>,
+        WriteStorage<'a, ShipAi>,
+        WriteStorage<'a, Encounter>,
+        ReadStorage<'a, Location>,
+        ReadStorage<'a, Sector>,
+        ReadStorage<'a, SectorBody>,
+        ReadStorage<'a, GridRef>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, ObjectsType>,
+        ReadStorage<'a, ModuleHealth>,
+        WriteStorage<'a, Cargo>,
+        ReadStorage<'a, Market>,
+        ReadExpect<'a, GameTime>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut ships,
+            mut ais,
+            mut encounters,
+            locations,
+            sectors,
+            bodies,
+            grids,
+            positions,
+            objects,
+            healths,
+            mut cargos,
+            markets,
+            time,
+        ): Self::SystemData,
+    ) {
+        if time.elapsed == 0 {
+            return;
+        }
+
+        // ships in the sectors, with its hull and if they are pirates
+        let placed = (&entities, &ships, ais.maybe())
+            .join()
+            .filter_map(|(e, ship, ai)| {
+                let is_pirate = ai.map(|ai| ai.kind == ShipAiKind::Pirate).unwrap_or(false);
+                locations::resolve_sector_pos(&locations, e)
+                    .map(|(pos, sector_id)| (e, pos, sector_id, ship.hull, is_pirate))
+            })
+            .collect::<Vec<_>>();
+
+        for (ship_id, ship, ai) in (&entities, &mut ships, &mut ais).join() {
+            if ship.hull <= 0 {
+                continue;
+            }
+            ai.chase_calm_down = ai.chase_calm_down.saturating_sub(time.elapsed);
+            if ai.chase_calm_down > 0 {
+                if let Command::FlyTo { target_id } = ship.current_command {
+                    if let Some(SectorBody::Ship) = bodies.get(target_id) {
+                        ship.current_command = Command::Idle;
+                    }
+                }
+            }
+            let (pos, sector_id) =
+                unwrap_or_continue!(locations::resolve_sector_pos(&locations, ship_id));
+            let stats = ShipStats::compute(&grids, &positions, &objects, &healths, ship_id);
+
+            // fuel is bought from the orbited stations without docking
+            if let Some(Location::Orbit { target_id }) = locations.get(ship_id) {
+                let market = markets.get(*target_id);
+                if let (Some(market), Some(cargo)) = (market, cargos.get_mut(ship_id)) {
+                    if ship.fuel < stats.fuel_capacity() {
+                        let bought =
+                            trade::buy_fuel(ship, cargo, market, stats.fuel_capacity(), time.tick);
+                        if let Ok((amount, credits)) = bought {
+                            info!("ship {:?} bought {} fuel for {}", ship_id, amount, -credits);
+                        }
+                    }
+                }
+            }
+            let can_move = stats.can_fly().is_ok() && ship.fuel >= ship::FUEL_PER_STEP;
+
+            if let Some(encounter) = encounters.get_mut(ship_id) {
+                if encounter.action.is_some() {
+                    continue;
+                }
+                let other_hull = placed
+                    .iter()
+                    .find(|(e, ..)| *e == encounter.other_id)
+                    .map(|(_, _, _, hull, _)| *hull)
+                    .unwrap_or(0);
+
+                match ai.kind {
+                    // board once and resume the route
+                    ShipAiKind::Pirate if other_hull <= 0 && encounter.hostile => {
+                        encounter.hostile = false;
+                        encounter.action = Some(EncounterAction::Board);
+                        ai.chase_calm_down = CHASE_CALM_DOWN;
+                        continue;
+                    }
+                    ShipAiKind::Pirate if other_hull <= 0 || ai.chase_calm_down > 0 => {}
+                    ShipAiKind::Pirate => {
+                        if !encounter.hostile {
+                            encounter.action = Some(EncounterAction::Fight);
+                        }
+                        ship.current_command = Command::Idle;
+                        continue;
+                    }
+                    _ if encounter.hostile => {
+                        if can_move {
+                            encounter.action = Some(EncounterAction::Flee);
+                        }
+                        continue;
+                    }
+                    _ if time.tick < encounter.since + ENCOUNTER_WAIT_TIME => {
+                        ship.current_command = Command::Idle;
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            if ship.move_calm_down > 0 {
+                continue;
+            }
+
+            // pirates hunt the nearest ship in sensors range
+            if ai.kind == ShipAiKind::Pirate && ai.chase_calm_down == 0 {
+                let range = stats.sensor_range();
+                let distance = |at: &crate::P2| (at.x - pos.x).abs().max((at.y - pos.y).abs());
+                let prey = placed
+                    .iter()
+                    .filter(|(e, at, sector, hull, is_pirate)| {
+                        *e != ship_id
+                            && *sector == sector_id
+                            && *hull > 0
+                            && !is_pirate
+                            && distance(at) <= range
+                    })
+                    .min_by_key(|(_, at, ..)| distance(at))
+                    .map(|(e, ..)| *e);
+
+                if let Some(prey_id) = prey {
+                    if can_move {
+                        ship.current_command = Command::FlyTo { target_id: prey_id };
+                    }
+                    continue;
+                }
+            }
+
+            if !matches!(ship.current_command, Command::Idle) {
+                continue;
+            }
+
+            let route = sectors
+                .get(sector_id)
+                .map(|sector| {
+                    sector
+                        .bodies
+                        .iter()
+                        .copied()
+                        .filter(|e| {
+                            !matches!(
+                                (ai.kind, bodies.get(*e)),
+                                (_, None)
+                                    | (_, Some(SectorBody::Ship))
+                                    | (ShipAiKind::Trader, Some(SectorBody::Jump { .. }))
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .unwrap_or_default();
+            if route.is_empty() {
+                continue;
+            }
+
+            ai.next %= route.len();
+            let target_id = route[ai.next];
+            match locations.get(ship_id) {
+                Some(Location::Orbit {
+                    target_id: orbit_id,
+                }) if *orbit_id == target_id => {
+                    ai.next = (ai.next + 1) % route.len();
+                    if ai.kind == ShipAiKind::Trader {
+                        ship.move_calm_down = TRADE_WAIT_TIME;
+                    }
+                }
+                _ if can_move => {
+                    info!("ship {:?} flying to {:?}", ship_id, target_id);
+                    ship.current_command = Command::FlyTo { target_id };
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::Event;
+    use crate::models::Location;
+    use crate::ship::encounter::EncounterAction;
+    use crate::simulation::Simulation;
+    use crate::test_utils::{get_credits, get_encounter_of, get_fuel, new_state_default_scenery};
+
+    #[test]
+    fn test_trader_should_fly_between_bodies_and_meet_the_player() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let trader_id = scenery.ships["trader"];
+        let planet_id = scenery.bodies["Planet X"];
+
+        // without encounter there is nothing to hail
+        assert_eq!(
+            Err("ship is not in an encounter".to_string()),
+            sim.encounter_action(ship_id, EncounterAction::Hail)
+        );
+
+        // trader visits the planet orbited by the player ship
+        sim.run_until(2000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(trader_id),
+                Some(Location::Orbit { target_id }) if *target_id == planet_id
+            )
+        })
+        .expect("trader never arrive at planet");
+        assert_eq!(Some(trader_id), get_encounter_of(&sim, ship_id));
+        assert_eq!(Some(ship_id), get_encounter_of(&sim, trader_id));
+        assert!(sim.events().contains(&Event::EncounterStarted {
+            ship_id,
+            other_id: trader_id
+        }));
+
+        // traders answer back
+        sim.encounter_action(ship_id, EncounterAction::Hail)
+            .unwrap();
+        sim.tick();
+        let events = sim.events();
+        assert!(events.contains(&Event::Hailed {
+            ship_id,
+            other_id: trader_id
+        }));
+        assert!(events.contains(&Event::Hailed {
+            ship_id: trader_id,
+            other_id: ship_id
+        }));
+        assert_eq!(
+            Err("only disabled ships can be boarded".to_string()),
+            sim.encounter_action(ship_id, EncounterAction::Board)
+        );
+
+        // and resume its route, ending the encounter
+        sim.run_until(2000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(trader_id),
+                Some(Location::Sector { .. })
+            )
+        })
+        .expect("trader never leave the planet");
+        sim.tick();
+        assert_eq!(None, get_encounter_of(&sim, ship_id));
+        assert_eq!(None, get_encounter_of(&sim, trader_id));
+    }
+
+    #[test]
+    fn test_npc_ships_should_pay_for_fuel_at_stations() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let trader_id = scenery.ships["trader"];
+        let set_fuel_and_credits = |sim: &mut Simulation, fuel: u32, credits: i32| {
+            sim.ecs_mut()
+                .write_storage::<Ship>()
+                .get_mut(trader_id)
+                .unwrap()
+                .fuel = fuel;
+            sim.ecs_mut()
+                .write_storage::<Cargo>()
+                .get_mut(trader_id)
+                .unwrap()
+                .credits = credits;
+        };
+
+        // without credits the tanks stay empty
+        set_fuel_and_credits(&mut sim, 0, 0);
+        sim.tick();
+        assert_eq!(0, get_fuel(&sim, trader_id));
+
+        // as much as the credits can pay
+        set_fuel_and_credits(&mut sim, 0, 300);
+        sim.tick();
+        assert!(get_fuel(&sim, trader_id) > 0);
+        let credits = get_credits(&sim, trader_id);
+        assert!((0..300).contains(&credits));
+        sim.tick();
+        assert_eq!(credits, get_credits(&sim, trader_id));
+    }
+}
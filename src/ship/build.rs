@@ -0,0 +1,411 @@
+use crate::area::{Area, Cell, Tile};
+use crate::atmosphere;
+use crate::cfg::MapParserCfg;
+use crate::commons::grid::{Coord, Grid, NGrid};
+use crate::commons::grid_string;
+use crate::gridref::GridRef;
+use crate::loader;
+use crate::models::{DoorState, ObjectsType, Position, P2};
+use crate::scenery::MapCfg;
+use crate::trade::{Cargo, Commodity};
+use specs::prelude::*;
+use std::collections::HashSet;
+
+/// file where the ship layout is exported, in the same format of the scenery maps
+#[cfg(not(target_arch = "wasm32"))]
+pub const EXPORT_FILE: &str = "ship_export.json";
+
+/// parts that can be placed on the ship grid
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShipPart {
+    Wall,
+    Floor,
+    Door,
+    Engine,
+    Cockpit,
+    FuelTank,
+    Reactor,
+    Sensors,
+    LifeSupport,
+}
+
+impl ShipPart {
+    pub const ALL: [ShipPart; 9] = [
+        ShipPart::Wall,
+        ShipPart::Floor,
+        ShipPart::Door,
+        ShipPart::Engine,
+        ShipPart::Cockpit,
+        ShipPart::FuelTank,
+        ShipPart::Reactor,
+        ShipPart::Sensors,
+        ShipPart::LifeSupport,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShipPart::Wall => "wall",
+            ShipPart::Floor => "floor",
+            ShipPart::Door => "door",
+            ShipPart::Engine => "engine",
+            ShipPart::Cockpit => "cockpit",
+            ShipPart::FuelTank => "fuel tank",
+            ShipPart::Reactor => "reactor",
+            ShipPart::Sensors => "sensors",
+            ShipPart::LifeSupport => "life support",
+        }
+    }
+
+    /// materials consumed from the cargo hold to build it
+    pub fn cost(&self) -> &'static [(Commodity, u32)] {
+        match self {
+            ShipPart::Wall => &[(Commodity::Ore, 1)],
+            ShipPart::Floor => &[(Commodity::Ore, 1)],
+            ShipPart::Door => &[(Commodity::Ore, 2)],
+            ShipPart::Engine => &[(Commodity::Ore, 3), (Commodity::Electronics, 2)],
+            ShipPart::Cockpit => &[(Commodity::Ore, 2), (Commodity::Electronics, 3)],
+            ShipPart::FuelTank => &[(Commodity::Ore, 3)],
+            ShipPart::Reactor => &[(Commodity::Ore, 3), (Commodity::Electronics, 3)],
+            ShipPart::Sensors => &[(Commodity::Ore, 1), (Commodity::Electronics, 2)],
+            ShipPart::LifeSupport => &[(Commodity::Ore, 2), (Commodity::Electronics, 2)],
+        }
+    }
+
+    /// hull modules are placed on walls, any other on floors
+    fn tile(&self) -> Tile {
+        match self {
+            ShipPart::Wall | ShipPart::Engine | ShipPart::Sensors => Tile::Wall,
+            ShipPart::Door => Tile::Door { open: false },
+            _ => Tile::Floor,
+        }
+    }
+
+    fn module(&self) -> Option<ObjectsType> {
+        match self {
+            ShipPart::Engine => Some(ObjectsType::Engine),
+            ShipPart::Cockpit => Some(ObjectsType::Cockpit),
+            ShipPart::FuelTank => Some(ObjectsType::FuelTank),
+            ShipPart::Reactor => Some(ObjectsType::Reactor),
+            ShipPart::Sensors => Some(ObjectsType::Sensors),
+            ShipPart::LifeSupport => Some(ObjectsType::LifeSupport),
+            ShipPart::Wall | ShipPart::Floor | ShipPart::Door => None,
+        }
+    }
+}
+
+/// place the part at the ship cell consuming its materials from the cargo hold. Changes that would
+/// expose a sealed compartment to the outside are refused
+pub fn place(ecs: &mut World, ship_id: Entity, point: P2, part: ShipPart) -> Result<(), String> {
+    let pos = Position {
+        grid_id: ship_id,
+        point,
+    };
+    let cell = Cell::new(part.tile());
+
+    let vertical_door = {
+        let grids = ecs.read_storage::<GridRef>();
+        let area = ship_area(&grids, ship_id)?;
+        let current = area
+            .get_grid()
+            .get_at(&point)
+            .ok_or("cell is out of the ship")?;
+
+        if is_occupied(ecs, &pos) || !find_objects_at(ecs, &pos).is_empty() {
+            return Err("cell is occupied, remove it first".to_string());
+        }
+        if current.tile == cell.tile && matches!(part, ShipPart::Wall | ShipPart::Floor) {
+            return Err(format!("{} is already built", part.name()));
+        }
+        check_sealed(area, &point, cell)?;
+
+        // doors in a wall running from north to south are vertical
+        area.get_grid()
+            .get_at(&point.translate(0, -1))
+            .map(|cell| cell.tile == Tile::Wall)
+            .unwrap_or(false)
+    };
+
+    {
+        let mut cargos = ecs.write_storage::<Cargo>();
+        let cargo = cargos.get_mut(ship_id).ok_or("ship has no cargo hold")?;
+        consume(cargo, part.cost())?;
+    }
+
+    if let Some(area) = GridRef::find_gmap_mut(&mut ecs.write_storage::<GridRef>(), ship_id) {
+        area.set_cell_at(&point, cell);
+    }
+
+    if part == ShipPart::Door {
+        loader::create_door(ecs, pos, vertical_door, DoorState::Closed);
+    } else if let Some(kind) = part.module() {
+        loader::create_ship_module(ecs, pos, kind);
+    }
+
+    Ok(())
+}
+
+/// scrap the part at the ship cell leaving empty space, its materials are not recovered
+pub fn remove(ecs: &mut World, ship_id: Entity, point: P2) -> Result<(), String> {
+    let pos = Position {
+        grid_id: ship_id,
+        point,
+    };
+    let objects = find_objects_at(ecs, &pos);
+
+    {
+        let grids = ecs.read_storage::<GridRef>();
+        let area = ship_area(&grids, ship_id)?;
+        match area.get_grid().get_at(&point) {
+            None => return Err("cell is out of the ship".to_string()),
+            Some(cell) if cell.tile == Tile::Space => return Err("nothing to remove".to_string()),
+            _ => {}
+        }
+
+        let only_parts = objects
+            .iter()
+            .all(|(_, kind)| kind.is_ship_module() || matches!(kind, ObjectsType::Door { .. }));
+        if is_occupied(ecs, &pos) || !only_parts {
+            return Err("cell is occupied".to_string());
+        }
+        check_sealed(area, &point, Cell::new(Tile::Space))?;
+    }
+
+    for (id, _) in objects {
+        ecs.delete_entity(id).unwrap();
+    }
+
+    if let Some(area) = GridRef::find_gmap_mut(&mut ecs.write_storage::<GridRef>(), ship_id) {
+        area.set_cell_at(&point, Cell::new(Tile::Space));
+    }
+
+    Ok(())
+}
+
+/// true when no inside cell of the ship is exposed to the outside
+pub fn is_sealed(ecs: &World, ship_id: Entity) -> bool {
+    let grids = ecs.read_storage::<GridRef>();
+    GridRef::find_area(&grids, ship_id)
+        .map(|area| exposed_cells(area.get_grid()).is_empty())
+        .unwrap_or(false)
+}
+
+/// ship layout as map lines using the legend chars
+pub fn export(ecs: &World, legend: &MapParserCfg, ship_id: Entity) -> Result<Vec<String>, String> {
+    let grids = ecs.read_storage::<GridRef>();
+    let area = ship_area(&grids, ship_id)?;
+    let (width, height) = (area.get_grid().get_width(), area.get_grid().get_height());
+
+    let mut cells = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        for x in 0..width {
+            let point = Coord::new(x, y);
+            let tile = match area.get_grid().get_at(&point).map(|cell| cell.tile) {
+                Some(Tile::Door { .. }) => Tile::Door { open: false },
+                // breaches are exported already repaired
+                Some(Tile::Rubble) => Tile::Wall,
+                Some(tile) => tile,
+                None => Tile::Space,
+            };
+            let obj = find_objects_at(
+                ecs,
+                &Position {
+                    grid_id: ship_id,
+                    point,
+                },
+            )
+            .into_iter()
+            .map(|(_, kind)| kind)
+            .find(|kind| legend.raw_map_objects.iter().any(|(_, obj)| obj == kind));
+            cells.push((tile, obj));
+        }
+    }
+    let grid = Grid::new_from(width, height, cells);
+
+    grid_string::serialize(
+        |(tile, obj)| {
+            legend
+                .raw_map_tiles
+                .iter()
+                .filter(|(_, legend_tile)| legend_tile == tile)
+                .map(|(ch, _)| *ch)
+                .find(|ch| {
+                    let legend_obj = legend
+                        .raw_map_objects
+                        .iter()
+                        .find(|(obj_ch, _)| obj_ch == ch)
+                        .map(|(_, obj)| *obj);
+                    legend_obj == *obj
+                })
+        },
+        &grid,
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// ship layout as a json scenery map
+pub fn export_json(ecs: &World, legend: &MapParserCfg, ship_id: Entity) -> Result<String, String> {
+    let map = MapCfg {
+        legend: None,
+        lines: export(ecs, legend, ship_id)?,
+    };
+    serde_json::to_string_pretty(&map).map_err(|e| e.to_string())
+}
+
+/// write the ship layout into a file that can be used as a scenery map
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_file(
+    ecs: &World,
+    legend: &MapParserCfg,
+    ship_id: Entity,
+    path: &str,
+) -> Result<(), String> {
+    let json = export_json(ecs, legend, ship_id)?;
+    std::fs::write(path, json).map_err(|e| format!("fail to write {}: {}", path, e))
+}
+
+/// the layout can only be changed while the ship has its own grid, not docked or landed
+fn ship_area<'a>(grids: &'a ReadStorage<'a, GridRef>, ship_id: Entity) -> Result<&'a Area, String> {
+    match grids.get(ship_id) {
+        Some(GridRef::GMap(area)) if area.get_layers().len() == 1 => Ok(area),
+        _ => Err("ship can only be changed in space".to_string()),
+    }
+}
+
+fn find_objects_at(ecs: &World, pos: &Position) -> Vec<(Entity, ObjectsType)> {
+    crate::utils::find_objects_at(
+        &ecs.entities(),
+        &ecs.read_storage::<ObjectsType>(),
+        &ecs.read_storage::<Position>(),
+        pos,
+    )
+}
+
+/// any entity that is not an object, like the crew
+fn is_occupied(ecs: &World, pos: &Position) -> bool {
+    let positions = ecs.read_storage::<Position>();
+    let objects = ecs.read_storage::<ObjectsType>();
+    (&positions, !&objects).join().any(|(p, _)| p == pos)
+}
+
+fn consume(cargo: &mut Cargo, cost: &[(Commodity, u32)]) -> Result<(), String> {
+    if let Some((commodity, amount)) = cost.iter().find(|(c, amount)| cargo.get(*c) < *amount) {
+        return Err(format!("need {} {}", amount, commodity.name()));
+    }
+
+    for (commodity, amount) in cost {
+        let current = cargo.get(*commodity);
+        if current == *amount {
+            cargo.goods.remove(commodity);
+        } else {
+            cargo.goods.insert(*commodity, current - amount);
+        }
+    }
+    Ok(())
+}
+
+/// apply the change into a copy of the area and fail if any inside cell would get exposed, already
+/// exposed cells are ignored so a breached ship can still be repaired
+fn check_sealed(area: &Area, point: &P2, cell: Cell) -> Result<(), String> {
+    let before = exposed_cells(area.get_grid());
+    let mut changed = area.clone();
+    changed.set_cell_at(point, cell);
+    if exposed_cells(changed.get_grid()).is_subset(&before) {
+        Ok(())
+    } else {
+        Err("hull would not be sealed".to_string())
+    }
+}
+
+fn exposed_cells(grid: &NGrid<Cell>) -> HashSet<Coord> {
+    atmosphere::find_compartments(grid)
+        .into_iter()
+        .filter(|compartment| !compartment.is_sealed())
+        .flat_map(|compartment| compartment.cells)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::gridref::GridRef;
+    use crate::models::P2;
+    use crate::scenery;
+    use crate::simulation::Simulation;
+    use crate::test_utils::{get_air_at, get_avatar_position};
+    use crate::trade::{Cargo, Commodity};
+
+    #[test]
+    fn test_ship_should_be_changed_in_build_mode_and_exported() {
+        let scenery_cfg = scenery::load_default_scenery().unwrap();
+        let (mut sim, scenery) = Simulation::from_scenery(&scenery_cfg).unwrap();
+        let ship_id = scenery.ships["ship"];
+
+        // export the same layout it was loaded, airlock doors are exported as plain doors
+        let lines = scenery_cfg.maps["ship"]
+            .lines
+            .iter()
+            .map(|line| line.replace('!', "|"))
+            .collect::<Vec<_>>();
+        assert_eq!(Ok(lines), sim.export_ship(ship_id));
+
+        // parts consume materials from the cargo hold
+        assert_eq!(
+            Err("need 1 ore".to_string()),
+            sim.build(ship_id, P2::new(11, 4), ShipPart::Wall)
+        );
+        {
+            let mut cargos = sim.ecs_mut().write_storage::<Cargo>();
+            let cargo = cargos.get_mut(ship_id).unwrap();
+            cargo.goods.insert(Commodity::Ore, 10);
+            cargo.goods.insert(Commodity::Electronics, 2);
+        }
+
+        // a floor open to space would leak
+        assert_eq!(
+            Err("hull would not be sealed".to_string()),
+            sim.build(ship_id, P2::new(11, 5), ShipPart::Floor)
+        );
+
+        // enclose a new cell and connect it to the corridor
+        sim.build(ship_id, P2::new(11, 4), ShipPart::Wall).unwrap();
+        sim.build(ship_id, P2::new(12, 5), ShipPart::Wall).unwrap();
+        sim.build(ship_id, P2::new(11, 5), ShipPart::Floor).unwrap();
+        sim.build(ship_id, P2::new(10, 5), ShipPart::Door).unwrap();
+        sim.build(ship_id, P2::new(11, 4), ShipPart::Engine)
+            .unwrap();
+        assert_eq!(
+            Err("hull would not be sealed".to_string()),
+            sim.unbuild(ship_id, P2::new(12, 5))
+        );
+        assert_eq!(
+            Err("cell is occupied, remove it first".to_string()),
+            sim.build(ship_id, get_avatar_position(&sim).point, ShipPart::Wall)
+        );
+        assert_eq!(
+            Err("need 3 ore".to_string()),
+            sim.build(ship_id, P2::new(12, 4), ShipPart::Engine)
+        );
+
+        {
+            let cargos = sim.ecs().read_storage::<Cargo>();
+            let cargo = cargos.get(ship_id).unwrap();
+            assert_eq!(2, cargo.get(Commodity::Ore));
+            assert_eq!(0, cargo.get(Commodity::Electronics));
+        }
+
+        let lines = sim.export_ship(ship_id).unwrap();
+        assert_eq!("_______##.#E_______________", lines[4]);
+        assert_eq!("________#.|.#______________", lines[5]);
+
+        // the new cell is pressurized and reachable through the doors
+        sim.tick();
+        assert!(get_air_at(&sim, P2::new(11, 5)).is_breathable());
+        let avatar_pos = get_avatar_position(&sim);
+        let path = {
+            let grids = sim.ecs().read_storage::<GridRef>();
+            let area = GridRef::find_area(&grids, ship_id).unwrap();
+            area.find_path_through_doors(&avatar_pos.point, &P2::new(11, 5))
+        };
+        assert!(path.is_some());
+    }
+}
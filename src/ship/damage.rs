@@ -0,0 +1,371 @@
+use crate::area::{Area, Tile};
+use crate::commons::grid;
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::GameTime;
+use crate::gridref::GridRef;
+use crate::models::{ObjectsType, Position, SurfaceTileKind, P2};
+use crate::ship::{ModuleHealth, Ship, MAX_HULL};
+use crate::unwrap_or_continue;
+use crate::view::Renderable;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rltk::RGB;
+use specs::prelude::*;
+use specs::storage::GenericReadStorage;
+
+/// damage taken landing without enough powered engines to brake
+pub const HARD_LANDING_DAMAGE: i32 = 20;
+/// powered engines required to land softly
+pub const SOFT_LANDING_ENGINES: u32 = 3;
+/// damage taken landing into rough terrain
+pub const ROUGH_LANDING_DAMAGE: i32 = 10;
+/// hits with at least this damage open a breach in the hull
+pub const BREACH_DAMAGE: i32 = 15;
+/// health or hull restored by each repair
+pub const REPAIR_AMOUNT: i32 = 10;
+
+/// Hits taken by ships during the tick, applied by the ShipDamageSystem
+#[derive(Debug, Default)]
+pub struct ShipImpacts {
+    pub impacts: Vec<(Entity, i32)>,
+}
+
+impl ShipImpacts {
+    pub fn push(&mut self, ship_id: Entity, damage: i32) {
+        log::debug!("ship {:?} hit by {}", ship_id, damage);
+        self.impacts.push((ship_id, damage));
+    }
+}
+
+pub fn is_rough_terrain(kind: SurfaceTileKind) -> bool {
+    matches!(
+        kind,
+        SurfaceTileKind::Mountains | SurfaceTileKind::Forest | SurfaceTileKind::Water
+    )
+}
+
+/// damage taken by a ship landing with the powered engines into the zone terrain
+pub fn landing_damage(powered_engines: u32, terrain: Option<SurfaceTileKind>) -> i32 {
+    let mut damage = 0;
+    if powered_engines < SOFT_LANDING_ENGINES {
+        damage += HARD_LANDING_DAMAGE;
+    }
+    if terrain.map(is_rough_terrain).unwrap_or(false) {
+        damage += ROUGH_LANDING_DAMAGE;
+    }
+    damage
+}
+
+/// ship with a damaged hull which has a wall next to the point, patching its walls restore the hull
+pub fn find_damaged_hull_around<S>(area: &Area, ships: &S, point: &P2) -> Option<Entity>
+where
+    S: GenericReadStorage<Component = Ship>,
+{
+    grid::get_4_neighbours(point)
+        .into_iter()
+        .filter(|(_, p)| area.get_grid().get_at(p).map(|cell| cell.tile) == Some(Tile::Wall))
+        .filter_map(|(_, p)| area.get_layer_entity_at(&p))
+        .find(|id| ships.get(*id).map(|s| s.hull < MAX_HULL).unwrap_or(false))
+}
+
+/// walls of the ship layer next to an inside cell, cells with a module on it are not hull
+fn find_hull_cells(area: &Area, ship_id: Entity, occupied: &[P2]) -> Vec<P2> {
+    let is_inside = |point: &P2| {
+        matches!(
+            area.get_grid().get_at(point).map(|cell| cell.tile),
+            Some(Tile::Floor) | Some(Tile::Door { .. })
+        )
+    };
+
+    let size = area.get_grid().get_size();
+    let mut cells = vec![];
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let point = P2::new(x, y);
+            let is_wall = area.get_grid().get_at(&point).map(|cell| cell.tile) == Some(Tile::Wall);
+            if !is_wall
+                || area.get_layer_entity_at(&point) != Some(ship_id)
+                || occupied.contains(&point)
+            {
+                continue;
+            }
+            if grid::get_4_neighbours(&point)
+                .iter()
+                .any(|(_, neighbour)| is_inside(neighbour))
+            {
+                cells.push(point);
+            }
+        }
+    }
+    cells
+}
+
+/// Apply the ship impacts, each hit reduces the hull integrity and damage one working module.
+/// Heavy hits also breach a hull wall venting its compartment.
+pub struct ShipDamageSystem {}
+
+impl<'a> System<'a> for ShipDamageSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Ship>,
+        WriteStorage<'a, GridRef>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, ObjectsType>,
+        WriteStorage<'a, ModuleHealth>,
+        ReadExpect<'a, GameTime>,
+        Write<'a, ShipImpacts>,
+        Write<'a, EntitiesEvents>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut ships,
+            mut grids,
+            positions,
+            objects,
+            mut healths,
+            time,
+            mut impacts,
+            mut events,
+            lazy,
+        ): Self::SystemData,
+    ) {
+        for (ship_id, damage) in impacts.impacts.drain(..) {
+            let ship = unwrap_or_continue!(ships.get_mut(ship_id));
+            let grid_id = unwrap_or_continue!(GridRef::find_gmap_entity(&mut grids, ship_id));
+            let area = unwrap_or_continue!(GridRef::find_gmap_mut(&mut grids, grid_id));
+            let mut rng =
+                StdRng::seed_from_u64(time.tick.wrapping_mul(31).wrapping_add(ship_id.id() as u64));
+
+            ship.hull = (ship.hull - damage).max(0);
+            events.push(Event::ShipDamaged { ship_id, damage });
+
+            // one working module takes the hit
+            let modules = (&entities, &positions, &healths)
+                .join()
+                .filter(|(_, pos, health)| {
+                    pos.grid_id == grid_id
+                        && area.get_layer_entity_at(&pos.point) == Some(ship_id)
+                        && !health.is_broken()
+                })
+                .map(|(e, _, _)| e)
+                .collect::<Vec<_>>();
+            if let Some(module_id) = modules.choose(&mut rng).copied() {
+                let health = healths.get_mut(module_id).unwrap();
+                health.health = (health.health - damage).max(0);
+                events.push(Event::ModuleDamaged { module_id, damage });
+            }
+
+            if damage < BREACH_DAMAGE {
+                continue;
+            }
+
+            let occupied = (&positions, &objects)
+                .join()
+                .filter(|(pos, _)| pos.grid_id == grid_id)
+                .map(|(pos, _)| pos.point)
+                .collect::<Vec<_>>();
+            let point = unwrap_or_continue!(find_hull_cells(area, ship_id, &occupied)
+                .choose(&mut rng)
+                .copied());
+            let mut cell = area.get_grid().get_at(&point).copied().unwrap_or_default();
+            cell.tile = Tile::Rubble;
+            area.set_cell_at(&point, cell);
+
+            lazy.create_entity(&entities)
+                .with(Position { grid_id, point })
+                .with(Renderable {
+                    glyph: rltk::to_cp437('*'),
+                    fg: RGB::named(rltk::RED),
+                    bg: RGB::named(rltk::BLACK),
+                    priority: 0,
+                })
+                .with(ObjectsType::Breach)
+                .build();
+            events.push(Event::HullBreached { ship_id });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Action;
+    use crate::area::Tile;
+    use crate::commons;
+    use crate::events::Event;
+    use crate::gridref::GridRef;
+    use crate::models::{Location, ObjectsType, Position, P2};
+    use crate::ship::build;
+    use crate::ship::{self, damage, Command, ModuleHealth};
+    use crate::simulation::Simulation;
+    use crate::test_utils::{get_air_at, get_hull, new_state_default_scenery};
+
+    #[test]
+    fn test_landing_damage_should_depend_on_engines_and_terrain() {
+        assert_eq!(0, landing_damage(6, Some(SurfaceTileKind::Plain)));
+        assert_eq!(
+            ROUGH_LANDING_DAMAGE,
+            landing_damage(6, Some(SurfaceTileKind::Mountains))
+        );
+        assert_eq!(HARD_LANDING_DAMAGE, landing_damage(1, None));
+        assert_eq!(
+            HARD_LANDING_DAMAGE + ROUGH_LANDING_DAMAGE,
+            landing_damage(0, Some(SurfaceTileKind::Water))
+        );
+    }
+
+    #[test]
+    fn test_ship_should_be_damaged_and_repaired() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let avatar_id = scenery.avatar_id;
+        let objects_of = |sim: &Simulation, kind: ObjectsType| {
+            (
+                &sim.ecs().entities(),
+                &sim.ecs().read_storage::<ObjectsType>(),
+                &sim.ecs().read_storage::<Position>(),
+            )
+                .join()
+                .filter(|(_, k, _)| **k == kind)
+                .map(|(id, _, pos)| (id, pos.clone()))
+                .collect::<Vec<_>>()
+        };
+        // floor cell of the ship where the avatar can reach the object
+        let floor_next_to = |sim: &Simulation, point: P2| {
+            let grids = sim.ecs().read_storage::<GridRef>();
+            let area = GridRef::find_area(&grids, ship_id).unwrap();
+            commons::grid::get_4_neighbours(&point)
+                .into_iter()
+                .map(|(_, p)| p)
+                .find(|p| area.get_grid().get_at(p).map(|c| c.tile) == Some(Tile::Floor))
+        };
+        let place_avatar = |sim: &mut Simulation, point: P2| {
+            sim.ecs_mut()
+                .write_storage::<Position>()
+                .insert(
+                    avatar_id,
+                    Position {
+                        grid_id: ship_id,
+                        point,
+                    },
+                )
+                .unwrap();
+        };
+
+        // heavy hits breach the hull and damage a module
+        sim.damage_ship(ship_id, damage::BREACH_DAMAGE);
+        sim.tick();
+        assert_eq!(
+            ship::MAX_HULL - damage::BREACH_DAMAGE,
+            get_hull(&sim, ship_id)
+        );
+        assert!(sim.events().contains(&Event::HullBreached { ship_id }));
+        assert!(sim.events().iter().any(
+            |e| matches!(e, Event::ModuleDamaged { damage, .. } if *damage == damage::BREACH_DAMAGE)
+        ));
+        assert!(!build::is_sealed(sim.ecs(), ship_id));
+
+        let breaches = objects_of(&sim, ObjectsType::Breach);
+        assert_eq!(1, breaches.len());
+        let (breach_id, breach_pos) = breaches[0].clone();
+        let inside = floor_next_to(&sim, breach_pos.point).unwrap();
+        sim.run_until(10, |_| false);
+        assert!(get_air_at(&sim, inside).is_vacuum());
+
+        // repair the breach from inside
+        place_avatar(&mut sim, inside);
+        sim.tick();
+        sim.set_action(Action::Repair);
+        sim.tick();
+        assert!(!sim.ecs().is_alive(breach_id));
+        assert!(build::is_sealed(sim.ecs(), ship_id));
+        assert_eq!(
+            ship::MAX_HULL - damage::BREACH_DAMAGE + damage::REPAIR_AMOUNT,
+            get_hull(&sim, ship_id)
+        );
+        assert!(sim.events().contains(&Event::Repaired {
+            actor_id: avatar_id,
+            kind: ObjectsType::Breach
+        }));
+
+        // damage below a breach is patched from any hull wall
+        sim.set_action(Action::Repair);
+        sim.tick();
+        assert_eq!(ship::MAX_HULL, get_hull(&sim, ship_id));
+        assert!(sim.events().contains(&Event::HullRepaired {
+            actor_id: avatar_id,
+            ship_id
+        }));
+        sim.set_action(Action::Repair);
+        sim.tick();
+        assert!(sim
+            .events()
+            .iter()
+            .any(|e| matches!(e, Event::ActionFailed { actor_id, .. } if *actor_id == avatar_id)));
+
+        // damaged engines fly slower and broken ones do not count
+        let engines = objects_of(&sim, ObjectsType::Engine);
+        let (engine_id, next_to_engine) = engines
+            .iter()
+            .find_map(|(id, pos)| floor_next_to(&sim, pos.point).map(|p| (*id, p)))
+            .unwrap();
+        {
+            let mut healths = sim.ecs_mut().write_storage::<ModuleHealth>();
+            for (id, _) in &engines {
+                healths.get_mut(*id).unwrap().health = ship::MODULE_HEALTH / 2;
+            }
+            healths.get_mut(engine_id).unwrap().health = 0;
+        }
+        let stats = ship::get_ship_stats(sim.ecs(), ship_id);
+        assert_eq!(5, stats.engines);
+        assert_eq!(50, stats.engine_damage);
+        assert_eq!(Some(ship::FLY_SLEEP_TIME * 12 / 5), stats.fly_sleep_time());
+
+        // repair the broken engine
+        place_avatar(&mut sim, next_to_engine);
+        sim.tick();
+        sim.set_action(Action::Repair);
+        sim.tick();
+        assert_eq!(
+            damage::REPAIR_AMOUNT,
+            sim.ecs()
+                .read_storage::<ModuleHealth>()
+                .get(engine_id)
+                .unwrap()
+                .health
+        );
+        assert_eq!(6, ship::get_ship_stats(sim.ecs(), ship_id).engines);
+
+        // landing into the mountains damage the hull
+        let before = get_hull(&sim, ship_id);
+        sim.set_ship_command(
+            ship_id,
+            Command::Land {
+                target_id: scenery.zones["Planet X"][2],
+                place_coords: P2::new(0, 1),
+            },
+        )
+        .unwrap();
+        sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::BodySurfacePlace { .. })
+            )
+        })
+        .expect("ship never land");
+        sim.tick();
+        assert!(sim.events().contains(&Event::ShipDamaged {
+            ship_id,
+            damage: damage::ROUGH_LANDING_DAMAGE
+        }));
+        assert_eq!(
+            before - damage::ROUGH_LANDING_DAMAGE,
+            get_hull(&sim, ship_id)
+        );
+    }
+}
@@ -0,0 +1,558 @@
+use crate::cfg;
+use crate::commons::grid;
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::GameTime;
+use crate::save::EntityNotSaved;
+use crate::ship::ai_system::CHASE_CALM_DOWN;
+use crate::ship::damage::ShipImpacts;
+use crate::ship::{self, Command, Ship, ShipAi, ShipAiKind};
+use crate::trade::Cargo;
+use crate::{locations, sectors, unwrap_or_continue};
+use crate::{Location, Sector, P2};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs::saveload::{ConvertSaveload, Marker};
+use specs_derive::*;
+
+/// game ticks between each shot of fighting ships
+pub const FIRE_TIME: u32 = 30;
+pub const FIRE_DAMAGE: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EncounterAction {
+    /// greet the other ship, NPC ships other than pirates answer back
+    Hail,
+    /// leave the cell at once ending the encounter
+    Flee,
+    /// both ships fire at each other until one is disabled or leaves the cell
+    Fight,
+    /// take the credits and cargo of the disabled other ship
+    Board,
+}
+
+/// Ships sharing the same sector cell, both ships have an encounter pointing to the other one
+#[derive(Component, Debug, Clone)]
+pub struct Encounter {
+    pub other_id: Entity,
+    pub hostile: bool,
+    /// tick when the encounter started
+    pub since: u64,
+    /// game ticks until the next shot while hostile
+    pub fire_calm_down: u32,
+    /// executed by the EncounterSystem on next tick
+    pub action: Option<EncounterAction>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncounterData<M> {
+    pub other_id: M,
+    pub hostile: bool,
+    pub since: u64,
+    pub fire_calm_down: u32,
+    pub action: Option<EncounterAction>,
+}
+
+impl<M: Marker + Serialize> ConvertSaveload<M> for Encounter
+where
+    for<'de> M: Deserialize<'de>,
+{
+    type Data = EncounterData<M>;
+    type Error = EntityNotSaved;
+
+    fn convert_into<F>(&self, mut ids: F) -> Result<Self::Data, Self::Error>
+    where
+        F: FnMut(Entity) -> Option<M>,
+    {
+        Ok(EncounterData {
+            other_id: ids(self.other_id).ok_or(EntityNotSaved)?,
+            hostile: self.hostile,
+            since: self.since,
+            fire_calm_down: self.fire_calm_down,
+            action: self.action,
+        })
+    }
+
+    fn convert_from<F>(data: Self::Data, mut ids: F) -> Result<Self, Self::Error>
+    where
+        F: FnMut(M) -> Option<Entity>,
+    {
+        Ok(Encounter {
+            other_id: ids(data.other_id).ok_or(EntityNotSaved)?,
+            hostile: data.hostile,
+            since: data.since,
+            fire_calm_down: data.fire_calm_down,
+            action: data.action,
+        })
+    }
+}
+
+impl Encounter {
+    pub fn new(other_id: Entity, since: u64) -> Self {
+        Encounter {
+            other_id,
+            hostile: false,
+            since,
+            fire_calm_down: 0,
+            action: None,
+        }
+    }
+}
+
+/// neighbour cell inside the sector and free of other ships, the nearest to the sector center
+fn flee_pos(pos: P2, occupied: &[P2]) -> Option<P2> {
+    let half = cfg::SECTOR_SIZE / 2;
+    grid::get_8_neighbours(&pos)
+        .into_iter()
+        .filter(|p| p.x.abs() <= half && p.y.abs() <= half && !occupied.contains(p))
+        .min_by_key(|p| p.x.abs() + p.y.abs())
+}
+
+/// the action is refused when the ship is not in a encounter or can not execute it
+pub fn set_encounter_action(
+    ecs: &mut World,
+    ship_id: Entity,
+    action: EncounterAction,
+) -> Result<(), String> {
+    let other_id = ecs
+        .read_storage::<Encounter>()
+        .get(ship_id)
+        .map(|encounter| encounter.other_id)
+        .ok_or("ship is not in an encounter")?;
+
+    let hull = |id: Entity| {
+        ecs.read_storage::<Ship>()
+            .get(id)
+            .map(|ship| ship.hull)
+            .unwrap_or(0)
+    };
+    match action {
+        EncounterAction::Flee | EncounterAction::Fight if hull(ship_id) <= 0 => {
+            return Err("hull is destroyed".to_string());
+        }
+        EncounterAction::Flee => {
+            ship::get_ship_stats(ecs, ship_id).can_fly()?;
+            let fuel = ecs
+                .read_storage::<Ship>()
+                .get(ship_id)
+                .map(|ship| ship.fuel)
+                .unwrap_or(0);
+            if fuel < ship::FUEL_PER_STEP {
+                return Err("ship has no fuel".to_string());
+            }
+        }
+        EncounterAction::Board if hull(other_id) > 0 => {
+            return Err("only disabled ships can be boarded".to_string());
+        }
+        _ => {}
+    }
+
+    info!("ship {:?} encounter action {:?}", ship_id, action);
+    if let Some(encounter) = ecs.write_storage::<Encounter>().get_mut(ship_id) {
+        encounter.action = Some(action);
+    }
+    Ok(())
+}
+
+/// Start encounters between ships sharing a sector cell, execute the encounters actions and
+/// exchange fire between hostile ships
+pub struct EncounterSystem {}
+
+impl<'a> System<'a> for EncounterSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Ship>,
+        WriteStorage<'a, Encounter>,
+        WriteStorage<'a, Location>,
+        WriteStorage<'a, Sector>,
+        WriteStorage<'a, Cargo>,
+        WriteStorage<'a, ShipAi>,
+        ReadExpect<'a, GameTime>,
+        Write<'a, ShipImpacts>,
+        Write<'a, EntitiesEvents>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut ships,
+            mut encounters,
+            mut locations,
+            mut sectors,
+            mut cargos,
+            mut ais,
+            time,
+            mut impacts,
+            mut events,
+        ): Self::SystemData,
+    ) {
+        if time.elapsed == 0 {
+            return;
+        }
+
+        let queued = (&entities, &mut encounters)
+            .join()
+            .filter_map(|(ship_id, encounter)| {
+                encounter
+                    .action
+                    .take()
+                    .map(|action| (ship_id, encounter.other_id, action))
+            })
+            .collect::<Vec<_>>();
+
+        // ships flying or orbiting by its sector position
+        let mut placed = (&entities, &ships)
+            .join()
+            .filter_map(|(e, _)| locations::resolve_sector_pos(&locations, e).map(|at| (e, at)))
+            .collect::<Vec<_>>();
+
+        for (ship_id, other_id, action) in queued {
+            match action {
+                EncounterAction::Hail => {
+                    events.push(Event::Hailed { ship_id, other_id });
+                    let answer = ais
+                        .get(other_id)
+                        .map(|ai| ai.kind != ShipAiKind::Pirate)
+                        .unwrap_or(false);
+                    if answer {
+                        events.push(Event::Hailed {
+                            ship_id: other_id,
+                            other_id: ship_id,
+                        });
+                    }
+                }
+                EncounterAction::Fight => {
+                    for (id, target_id) in [(ship_id, other_id), (other_id, ship_id)] {
+                        if let Some(encounter) = encounters.get_mut(id) {
+                            if encounter.other_id == target_id {
+                                encounter.hostile = true;
+                            }
+                        }
+                    }
+                    events.push(Event::ShipAttacked { ship_id, other_id });
+                }
+                EncounterAction::Flee => {
+                    let ship = unwrap_or_continue!(ships.get_mut(ship_id));
+                    if ship.fuel < ship::FUEL_PER_STEP {
+                        events.push(Event::OutOfFuel { ship_id });
+                        continue;
+                    }
+                    let (pos, sector_id) =
+                        unwrap_or_continue!(locations::resolve_sector_pos(&locations, ship_id));
+                    let occupied = placed
+                        .iter()
+                        .filter(|(e, (_, sector))| *e != ship_id && *sector == sector_id)
+                        .map(|(_, (at, _))| *at)
+                        .collect::<Vec<_>>();
+                    let flee_to = match flee_pos(pos, &occupied) {
+                        Some(flee_to) => flee_to,
+                        None => {
+                            events.push(Event::ActionFailed {
+                                actor_id: ship_id,
+                                reason: "no free cell to flee".to_string(),
+                            });
+                            continue;
+                        }
+                    };
+
+                    ship.fuel -= ship::FUEL_PER_STEP;
+                    ship.current_command = Command::Idle;
+                    ship.move_calm_down = ship::FLY_SLEEP_TIME;
+                    sectors::add_body(&mut sectors, sector_id, ship_id);
+                    locations
+                        .insert(
+                            ship_id,
+                            Location::Sector {
+                                sector_id,
+                                pos: flee_to,
+                            },
+                        )
+                        .expect("fail to update location");
+                    if let Some(entry) = placed.iter_mut().find(|(e, _)| *e == ship_id) {
+                        entry.1 = (flee_to, sector_id);
+                    }
+
+                    // the chaser gives up for a while
+                    if let Some(ai) = ais.get_mut(other_id) {
+                        ai.chase_calm_down = CHASE_CALM_DOWN;
+                    }
+
+                    encounters.remove(ship_id);
+                    if encounters.get(other_id).map(|e| e.other_id) == Some(ship_id) {
+                        encounters.remove(other_id);
+                    }
+                    events.push(Event::Fled { ship_id, other_id });
+                }
+                EncounterAction::Board => {
+                    if ships.get(other_id).map(|s| s.hull > 0).unwrap_or(true) {
+                        warn!("ship {:?} can not board {:?}", ship_id, other_id);
+                        continue;
+                    }
+                    let mut loot = unwrap_or_continue!(cargos.get(other_id).cloned());
+                    let cargo = unwrap_or_continue!(cargos.get_mut(ship_id));
+
+                    let credits = loot.credits.max(0);
+                    cargo.credits += credits;
+                    loot.credits -= credits;
+                    for (commodity, amount) in loot.goods.iter_mut() {
+                        let taken = (*amount).min(cargo.free_space());
+                        *cargo.goods.entry(*commodity).or_insert(0) += taken;
+                        *amount -= taken;
+                    }
+                    loot.goods.retain(|_, amount| *amount > 0);
+                    cargos.insert(other_id, loot).unwrap();
+
+                    events.push(Event::Boarded {
+                        ship_id,
+                        other_id,
+                        credits,
+                    });
+                }
+            }
+        }
+
+        let find_at = |id: Entity| placed.iter().find(|(e, _)| *e == id).map(|(_, at)| *at);
+
+        let ended = (&entities, &encounters)
+            .join()
+            .filter(|(e, encounter)| {
+                let at = find_at(*e);
+                at.is_none() || at != find_at(encounter.other_id)
+            })
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>();
+        for e in ended {
+            info!("ship {:?} encounter ended", e);
+            encounters.remove(e);
+        }
+
+        for (i, (ship_id, ship_at)) in placed.iter().enumerate() {
+            for (other_id, other_at) in &placed[i + 1..] {
+                if ship_at != other_at
+                    || encounters.contains(*ship_id)
+                    || encounters.contains(*other_id)
+                {
+                    continue;
+                }
+
+                info!("ship {:?} encounter with {:?}", ship_id, other_id);
+                encounters
+                    .insert(*ship_id, Encounter::new(*other_id, time.tick))
+                    .unwrap();
+                encounters
+                    .insert(*other_id, Encounter::new(*ship_id, time.tick))
+                    .unwrap();
+                events.push(Event::EncounterStarted {
+                    ship_id: *ship_id,
+                    other_id: *other_id,
+                });
+            }
+        }
+
+        // hostile ships with hull fire at each other
+        for (ship_id, encounter) in (&entities, &mut encounters).join() {
+            if !encounter.hostile {
+                continue;
+            }
+            let has_hull = |id: Entity| ships.get(id).map(|s| s.hull > 0).unwrap_or(false);
+            if !has_hull(ship_id) || !has_hull(encounter.other_id) {
+                continue;
+            }
+            if encounter.fire_calm_down > 0 {
+                encounter.fire_calm_down = encounter.fire_calm_down.saturating_sub(time.elapsed);
+                continue;
+            }
+            impacts.push(encounter.other_id, FIRE_DAMAGE);
+            encounter.fire_calm_down = FIRE_TIME;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Action;
+    use crate::events::Event;
+    use crate::models::{Location, P2};
+    use crate::save::{save_world, SaveError};
+    use crate::scenery::{self, LocationCfg, Scenery, ShipCfg};
+    use crate::ship::{self, Ship, ShipAiKind};
+    use crate::simulation::Simulation;
+    use crate::test_utils::{get_credits, get_hull};
+    use crate::trade::Cargo;
+
+    #[test]
+    fn test_flee_pos_should_move_towards_sector_center() {
+        assert_eq!(Some(P2::new(2, -3)), flee_pos(P2::new(3, -4), &[]));
+        assert_eq!(Some(P2::new(0, 1)), flee_pos(P2::new(0, 2), &[]));
+        assert_eq!(Some(P2::new(0, -1)), flee_pos(P2::new(0, 0), &[]));
+    }
+
+    #[test]
+    fn test_flee_pos_should_avoid_other_ships_and_sector_border() {
+        assert_eq!(
+            Some(P2::new(-1, 2)),
+            flee_pos(P2::new(0, 3), &[P2::new(0, 2), P2::new(1, 2)])
+        );
+        assert_eq!(
+            Some(P2::new(-5, 4)),
+            flee_pos(P2::new(-5, 5), &[P2::new(-4, 4)])
+        );
+
+        let all_around = grid::get_8_neighbours(&P2::new(1, 1));
+        assert_eq!(None, flee_pos(P2::new(1, 1), &all_around));
+    }
+
+    /// default scenery with only the player ship and a pirate near it, return both ships
+    #[test]
+    fn test_save_should_fail_on_encounter_with_deleted_ship() {
+        let (mut sim, _, ship_id, _) = new_state_with_raider();
+        let deleted_id = sim.ecs_mut().create_entity().build();
+        sim.ecs_mut().delete_entity(deleted_id).unwrap();
+        sim.ecs_mut().maintain();
+        sim.ecs_mut()
+            .write_storage::<Encounter>()
+            .insert(ship_id, Encounter::new(deleted_id, 0))
+            .unwrap();
+
+        match save_world(sim.ecs()) {
+            Err(SaveError::Component("Encounter", _)) => {}
+            other => panic!("unexpected result {:?}", other.map(|_| ())),
+        }
+    }
+
+    fn new_state_with_raider() -> (Simulation, Scenery, Entity, Entity) {
+        let mut scenery_cfg = scenery::load_default_scenery().unwrap();
+        scenery_cfg.ships.retain(|ship| ship.name == "ship");
+        scenery_cfg.ships.push(ShipCfg {
+            name: "raider".to_string(),
+            map: "ship".to_string(),
+            location: LocationCfg::Sector {
+                sector: "sector 0".to_string(),
+                pos: P2::new(5, 3),
+            },
+            cargo: Cargo::new(20, 100),
+            fuel: None,
+            ai: Some(ShipAiKind::Pirate),
+        });
+        let (sim, scenery) = Simulation::from_scenery(&scenery_cfg).unwrap();
+        let ship_id = scenery.ships["ship"];
+        let raider_id = scenery.ships["raider"];
+        (sim, scenery, ship_id, raider_id)
+    }
+
+    /// wait the pirate to reach the ship and disable it
+    fn wait_boarded(sim: &mut Simulation, ship_id: Entity) {
+        sim.run_until(1000, |ecs| {
+            ecs.read_storage::<Encounter>().contains(ship_id)
+        })
+        .expect("pirate never reach the ship");
+        sim.ecs_mut()
+            .write_storage::<Ship>()
+            .get_mut(ship_id)
+            .unwrap()
+            .hull = 1;
+        sim.run_until(1000, |ecs| {
+            ecs.read_storage::<Cargo>().get(ship_id).unwrap().credits == 0
+        })
+        .expect("pirate never board the ship");
+    }
+
+    #[test]
+    fn test_pirate_should_attack_and_board_disabled_ships() {
+        let (mut sim, _, ship_id, raider_id) = new_state_with_raider();
+        let in_encounter = |ecs: &World| ecs.read_storage::<Encounter>().contains(ship_id);
+
+        // pirate hunts the player ship in sensors range and attack it
+        sim.run_until(1000, in_encounter)
+            .expect("pirate never reach the ship");
+        sim.run_until(100, |ecs| {
+            ecs.read_storage::<Ship>().get(ship_id).unwrap().hull < ship::MAX_HULL
+        })
+        .expect("pirate never fire");
+        assert!(sim.events().contains(&Event::ShipAttacked {
+            ship_id: raider_id,
+            other_id: ship_id
+        }));
+
+        // hostile ships fire back
+        sim.run(FIRE_TIME + 2);
+        assert!(get_hull(&sim, raider_id) < ship::MAX_HULL);
+
+        // fleeing move the ship out of the cell and the pirate gives up for a while
+        sim.encounter_action(ship_id, EncounterAction::Flee)
+            .unwrap();
+        sim.tick();
+        assert!(!in_encounter(sim.ecs()));
+        assert!(sim.events().contains(&Event::Fled {
+            ship_id,
+            other_id: raider_id
+        }));
+        match sim.ecs().read_storage::<Location>().get(ship_id) {
+            Some(Location::Sector { pos, .. }) => assert_eq!(P2::new(4, 0), *pos),
+            other => panic!("unexpected location {:?}", other),
+        }
+        assert_eq!(
+            CHASE_CALM_DOWN,
+            sim.ecs()
+                .read_storage::<ShipAi>()
+                .get(raider_id)
+                .unwrap()
+                .chase_calm_down
+        );
+        sim.run(CHASE_CALM_DOWN / 2);
+        assert!(!in_encounter(sim.ecs()));
+        sim.run_until(1000, in_encounter)
+            .expect("pirate never reach the ship again");
+
+        // disabled ships are boarded
+        wait_boarded(&mut sim, ship_id);
+        assert_eq!(0, get_hull(&sim, ship_id));
+        assert_eq!(600, get_credits(&sim, raider_id));
+        assert!(sim.events().contains(&Event::Boarded {
+            ship_id: raider_id,
+            other_id: ship_id,
+            credits: 500
+        }));
+        assert_eq!(
+            Err("hull is destroyed".to_string()),
+            sim.encounter_action(ship_id, EncounterAction::Flee)
+        );
+    }
+
+    #[test]
+    fn test_boarded_ship_should_be_repaired_and_fly_again() {
+        let (mut sim, scenery, ship_id, _) = new_state_with_raider();
+        let planet_id = scenery.bodies["Planet X"];
+        let fly_to_planet = Command::FlyTo {
+            target_id: planet_id,
+        };
+        wait_boarded(&mut sim, ship_id);
+        assert_eq!(0, get_hull(&sim, ship_id));
+        assert_eq!(
+            Err("hull is destroyed".to_string()),
+            sim.set_ship_command(ship_id, fly_to_planet)
+        );
+
+        // patch the hull from the wall next to the cockpit
+        sim.move_avatar(-1, 0);
+        while get_hull(&sim, ship_id) < ship::MAX_HULL {
+            let before = get_hull(&sim, ship_id);
+            sim.set_action(Action::Repair);
+            sim.run_until(100, |ecs| {
+                ecs.read_storage::<Ship>().get(ship_id).unwrap().hull > before
+            })
+            .expect("hull never repaired");
+        }
+
+        sim.set_ship_command(ship_id, fly_to_planet).unwrap();
+        sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { target_id }) if *target_id == planet_id
+            )
+        })
+        .expect("ship never arrive at planet");
+    }
+}
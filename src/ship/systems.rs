@@ -1,8 +1,13 @@
 use crate::commons::grid::Coord;
 use crate::commons::recti;
 use crate::commons::v2i::V2I;
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::GameTime;
 use crate::gridref::GridRef;
-use crate::ship::Command;
+use crate::models::ObjectsType;
+use crate::ship::damage::{self, ShipImpacts};
+use crate::ship::{self, Command, ModuleHealth, ShipStats};
+use crate::{locations, sectors};
 use crate::{Location, Position, Sector, SectorBody, Ship, Surface, SurfaceZone, P2};
 use log::{debug, info, warn};
 use specs::prelude::*;
@@ -24,21 +29,44 @@ impl<'a> System<'a> for FlyToSystem {
         Entities<'a>,
         WriteStorage<'a, Ship>,
         WriteStorage<'a, Location>,
-        ReadStorage<'a, Sector>,
+        WriteStorage<'a, Sector>,
         ReadStorage<'a, SectorBody>,
         WriteStorage<'a, GridRef>,
         WriteStorage<'a, Position>,
         ReadStorage<'a, Surface>,
+        ReadStorage<'a, ObjectsType>,
+        ReadStorage<'a, ModuleHealth>,
+        ReadExpect<'a, GameTime>,
+        Write<'a, EntitiesEvents>,
+        Write<'a, ShipImpacts>,
     );
 
     fn run(
         &mut self,
-        (entities, mut ships, mut locations, sectors, bodies, mut grids, mut positions, surfaces): Self::SystemData,
+        (
+            entities,
+            mut ships,
+            mut locations,
+            mut sectors,
+            bodies,
+            mut grids,
+            mut positions,
+            surfaces,
+            objects,
+            healths,
+            time,
+            mut events,
+            mut impacts,
+        ): Self::SystemData,
     ) {
+        if time.elapsed == 0 {
+            return;
+        }
+
         for (ship_id, ship) in (&entities, &mut ships).join() {
             // update calm down
             if ship.move_calm_down > 0 {
-                ship.move_calm_down -= 1;
+                ship.move_calm_down = ship.move_calm_down.saturating_sub(time.elapsed);
                 debug!("calm down {:?}", ship.move_calm_down);
                 continue;
             }
@@ -46,16 +74,52 @@ impl<'a> System<'a> for FlyToSystem {
             // execute command
             match ship.current_command {
                 Command::FlyTo { target_id } => {
-                    do_ship_fly(&mut locations, ship_id, ship, target_id)
+                    // ships flying to other ship stop when they reach its cell
+                    if let Some(SectorBody::Ship) = bodies.get(target_id) {
+                        let at = locations::resolve_sector_pos(&locations, ship_id);
+                        if at.is_some()
+                            && at == locations::resolve_sector_pos(&locations, target_id)
+                        {
+                            info!("ship {:?} reached {:?}", ship_id, target_id);
+                            ship.current_command = Command::Idle;
+                            continue;
+                        }
+                    }
+
+                    let stats = ShipStats::compute(&grids, &positions, &objects, &healths, ship_id);
+                    match stats.fly_sleep_time() {
+                        Some(sleep_time) => do_ship_fly(
+                            &mut locations,
+                            &mut sectors,
+                            &mut events,
+                            ship_id,
+                            ship,
+                            target_id,
+                            sleep_time,
+                        ),
+                        None => {
+                            warn!("ship {:?} can not fly without engines", ship_id);
+                            ship.current_command = Command::Idle;
+                        }
+                    }
                 }
 
                 Command::Land {
                     target_id,
                     place_coords,
                 } => {
+                    // landing without enough thrust or into rough terrain damages the ship
+                    let engines =
+                        ShipStats::compute(&grids, &positions, &objects, &healths, ship_id)
+                            .power_budget()
+                            .engines;
+                    let terrain = Surface::find_zone_kind(&surfaces, target_id);
+                    let landing_damage = damage::landing_damage(engines, terrain);
+
                     do_ship_landing(
                         &entities,
                         &mut locations,
+                        &mut events,
                         &mut grids,
                         &mut positions,
                         ship_id,
@@ -63,43 +127,101 @@ impl<'a> System<'a> for FlyToSystem {
                         target_id,
                         place_coords,
                     );
+                    if landing_damage > 0 {
+                        impacts.push(ship_id, landing_damage);
+                    }
                 }
 
                 Command::Launch => {
-                    // find ship grid
-                    let grid_id = GridRef::find_gmap_entity(&mut grids, ship_id).unwrap();
-
-                    // find what body we are landed
-                    let surface_body_id =
-                        Surface::find_surface_body(&entities, &surfaces, grid_id).unwrap();
-
-                    // update ship command to idle
                     ship.current_command = Command::Idle;
-
-                    // extract ship grid
-                    let (grid, previous_coords) =
-                        GridRef::extract(&mut grids, grid_id, ship_id).unwrap();
-                    (&mut grids).insert(ship_id, GridRef::GMap(grid)).unwrap();
-
-                    // move objects inside ship grid back to ship
-                    // TODO: move only objects in top of the removed grid
-                    move_all_objects(
+                    if let Some(Location::BodySurface { .. })
+                    | Some(Location::BodySurfacePlace { .. }) = locations.get(ship_id)
+                    {
+                        if ship.fuel < ship::FUEL_PER_LAUNCH {
+                            warn!("ship {:?} has not enough fuel to launch", ship_id);
+                            events.push(Event::OutOfFuel { ship_id });
+                            continue;
+                        }
+                        ship.fuel -= ship::FUEL_PER_LAUNCH;
+                    }
+                    do_ship_launch(
                         &entities,
+                        &mut locations,
+                        &mut events,
+                        &mut grids,
                         &mut positions,
-                        grid_id,
+                        &surfaces,
                         ship_id,
-                        &previous_coords.inverse(),
                     );
+                }
 
-                    // change ship state
-                    locations
-                        .insert(
-                            ship_id,
-                            Location::Orbit {
-                                target_id: surface_body_id,
-                            },
-                        )
-                        .expect("fail to insert orbit");
+                Command::Dock { target_id } => {
+                    ship.current_command = Command::Idle;
+                    match (locations.get(ship_id), bodies.get(target_id)) {
+                        (
+                            Some(Location::Orbit {
+                                target_id: orbit_id,
+                            }),
+                            Some(SectorBody::Station),
+                        ) if *orbit_id == target_id => {
+                            do_ship_docking(
+                                &entities,
+                                &mut locations,
+                                &mut events,
+                                &mut grids,
+                                &mut positions,
+                                ship_id,
+                                target_id,
+                            );
+                        }
+                        other => warn!(
+                            "ship {:?} can not dock at {:?}: {:?}",
+                            ship_id, target_id, other
+                        ),
+                    }
+                }
+                Command::Jump { target_id } => {
+                    ship.current_command = Command::Idle;
+                    match (locations.get(ship_id), bodies.get(target_id)) {
+                        (
+                            Some(Location::Orbit {
+                                target_id: orbit_id,
+                            }),
+                            Some(SectorBody::Jump { target_pos, target }),
+                        ) if *orbit_id == target_id => {
+                            let (sector_id, pos) = (*target, *target_pos);
+                            do_ship_jump(
+                                &mut locations,
+                                &mut sectors,
+                                &mut events,
+                                ship_id,
+                                ship,
+                                sector_id,
+                                pos,
+                            );
+                        }
+                        other => warn!(
+                            "ship {:?} can not jump using {:?}: {:?}",
+                            ship_id, target_id, other
+                        ),
+                    }
+                }
+                Command::Harvest => {
+                    let capacity =
+                        ShipStats::compute(&grids, &positions, &objects, &healths, ship_id)
+                            .fuel_capacity();
+                    match locations.get(ship_id) {
+                        Some(Location::BodySurface { .. })
+                        | Some(Location::BodySurfacePlace { .. })
+                            if ship.fuel < capacity =>
+                        {
+                            let amount = ship::HARVEST_FUEL.min(capacity - ship.fuel);
+                            ship.fuel += amount;
+                            ship.move_calm_down = ship::HARVEST_TIME;
+                            events.push(Event::FuelHarvested { ship_id, amount });
+                        }
+                        _ => ship.current_command = Command::Idle,
+                    }
                 }
                 _ => {}
             }
@@ -118,6 +240,7 @@ impl<'a> System<'a> for FlyToSystem {
 fn do_ship_landing(
     entities: &Entities,
     locations: &mut WriteStorage<Location>,
+    events: &mut EntitiesEvents,
     mut grids: &mut WriteStorage<GridRef>,
     positions: &mut WriteStorage<Position>,
     ship_id: Entity,
@@ -173,6 +296,116 @@ fn do_ship_landing(
             },
         )
         .expect("fail to update location");
+
+    events.push(Event::Landed {
+        ship_id,
+        zone_id: target_id,
+    });
+}
+
+/// Launch from a surface or undock from a station, only objects on top of the ship grid are moved
+/// back to the ship
+fn do_ship_launch(
+    entities: &Entities,
+    locations: &mut WriteStorage<Location>,
+    events: &mut EntitiesEvents,
+    grids: &mut WriteStorage<GridRef>,
+    positions: &mut WriteStorage<Position>,
+    surfaces: &ReadStorage<Surface>,
+    ship_id: Entity,
+) {
+    // find ship grid
+    let grid_id = GridRef::find_gmap_entity(grids, ship_id).unwrap();
+
+    // find what body we will orbit
+    let body_id = match locations.get(ship_id) {
+        Some(Location::Docked { station_id, .. }) => *station_id,
+        _ => Surface::find_surface_body(entities, surfaces, grid_id).unwrap(),
+    };
+
+    // collect objects on top of the ship before remove its grid
+    let on_ship = {
+        let area = GridRef::find_gmap_mut(grids, grid_id).unwrap();
+        (entities, &*positions)
+            .join()
+            .filter(|(_, p)| {
+                p.grid_id == grid_id && area.get_layer_entity_at(&p.point) == Some(ship_id)
+            })
+            .map(|(e, _)| e)
+            .collect::<Vec<_>>()
+    };
+
+    // extract ship grid
+    let (grid, previous_coords) = GridRef::extract(grids, grid_id, ship_id).unwrap();
+    grids.insert(ship_id, GridRef::GMap(grid)).unwrap();
+
+    // move objects inside ship grid back to ship
+    let to_pos = previous_coords.inverse();
+    for e in on_ship {
+        let p = positions.get_mut(e).unwrap();
+        p.grid_id = ship_id;
+        p.point = recti::to_global(&to_pos, &p.point);
+    }
+
+    // change ship state
+    locations
+        .insert(ship_id, Location::Orbit { target_id: body_id })
+        .expect("fail to insert orbit");
+
+    events.push(Event::Launched { ship_id, body_id });
+}
+
+/// Docking works like landing, the ship grid is merged into the station grid at its center
+fn do_ship_docking(
+    entities: &Entities,
+    locations: &mut WriteStorage<Location>,
+    events: &mut EntitiesEvents,
+    grids: &mut WriteStorage<GridRef>,
+    positions: &mut WriteStorage<Position>,
+    ship_id: Entity,
+    station_id: Entity,
+) {
+    // replace ship reference to the station
+    let ship_gmap = match GridRef::replace(grids, ship_id, GridRef::Ref(station_id)) {
+        Some(GridRef::GMap(gmap)) => gmap,
+        _ => panic!("unexpected grid_ref for ship_id {:?}", ship_id),
+    };
+
+    let station_gmap = match grids.get_mut(station_id) {
+        Some(GridRef::GMap(gmap)) => gmap,
+        _ => panic!("unexpected grid_ref for station_id {:?}", station_id),
+    };
+
+    let ship_pos = Coord::new(
+        station_gmap.get_grid().get_width() / 2 - ship_gmap.get_grid().get_width() / 2,
+        station_gmap.get_grid().get_height() / 2 - ship_gmap.get_grid().get_height() / 2,
+    );
+
+    move_all_objects(entities, positions, ship_id, station_id, &ship_pos);
+
+    debug!(
+        "docking ship map {:?} into station {:?} on {:?}",
+        ship_id.id(),
+        station_id.id(),
+        ship_pos
+    );
+
+    station_gmap.merge(ship_gmap, &ship_pos);
+
+    locations
+        .insert(
+            ship_id,
+            Location::Docked {
+                station_id,
+                grid_pos: ship_pos,
+            },
+        )
+        .expect("fail to update location");
+
+    events.push(Event::Docked {
+        ship_id,
+        station_id,
+    });
 }
 
 fn move_all_objects(
@@ -199,26 +432,73 @@ fn move_all_objects(
     }
 }
 
+/// Jump into the target sector, the ship arrives flying in the sector at the jump target position
+fn do_ship_jump(
+    locations: &mut WriteStorage<Location>,
+    sectors: &mut WriteStorage<Sector>,
+    events: &mut EntitiesEvents,
+    ship_id: Entity,
+    ship: &mut Ship,
+    sector_id: Entity,
+    pos: P2,
+) {
+    ship.move_calm_down = super::FLY_SLEEP_TIME;
+
+    sectors::remove_body(sectors, ship_id);
+    sectors::add_body(sectors, sector_id, ship_id);
+
+    locations
+        .insert(ship_id, Location::Sector { sector_id, pos })
+        .expect("fail to update location");
+
+    events.push(Event::Jumped { ship_id, sector_id });
+}
+
 fn do_ship_fly(
     locations: &mut WriteStorage<Location>,
+    sectors: &mut WriteStorage<Sector>,
+    events: &mut EntitiesEvents,
     ship_entity: Entity,
     ship: &mut Ship,
     target_id: Entity,
+    sleep_time: u32,
 ) {
-    ship.move_calm_down = super::FLY_SLEEP_TIME;
+    ship.move_calm_down = sleep_time;
 
     // update position
-    let target_pos = {
-        match locations.get(target_id) {
-            Some(Location::Sector { pos, .. }) => pos.clone(),
-            other => {
-                warn!("invalid location for flyto target: {:?}", other);
+    let (target_pos, target_sector_id) = {
+        match locations::resolve_sector_pos(&*locations, target_id) {
+            Some(value) => value,
+            None => {
+                warn!(
+                    "invalid location for flyto target: {:?}",
+                    locations.get(target_id)
+                );
+                ship.current_command = Command::Idle;
                 return;
             }
         }
     };
 
+    // leave orbit into the sector at the orbited body position
+    if let Some(Location::Orbit {
+        target_id: orbit_id,
+    }) = locations.get(ship_entity)
+    {
+        if let Some(Location::Sector { sector_id, pos }) = locations.get(*orbit_id).cloned() {
+            info!("ship leaving orbit of {:?}", orbit_id);
+            sectors::add_body(sectors, sector_id, ship_entity);
+            locations
+                .insert(ship_entity, Location::Sector { sector_id, pos })
+                .unwrap();
+        }
+    }
+
     match locations.get_mut(ship_entity) {
+        Some(Location::Sector { sector_id, .. }) if *sector_id != target_sector_id => {
+            warn!("flyto target {:?} is in another sector", target_id);
+            ship.current_command = Command::Idle;
+        }
         Some(Location::Sector { pos, .. }) if *pos == target_pos => {
             info!("ship arrival, entering in orbit");
             ship.current_command = Command::Idle;
@@ -230,11 +510,24 @@ fn do_ship_fly(
                     },
                 )
                 .unwrap();
+
+            events.push(Event::ArrivedOrbit {
+                ship_id: ship_entity,
+                body_id: target_id,
+            });
+        }
+        Some(Location::Sector { .. }) if ship.fuel < ship::FUEL_PER_STEP => {
+            warn!("ship {:?} is out of fuel", ship_entity);
+            ship.current_command = Command::Idle;
+            events.push(Event::OutOfFuel {
+                ship_id: ship_entity,
+            });
         }
         Some(Location::Sector {
             pos,
             sector_id: _sector,
         }) => {
+            ship.fuel -= ship::FUEL_PER_STEP;
             let delta_x = clamp(target_pos.x - pos.x, -1, 1);
             let delta_y = clamp(target_pos.y - pos.y, -1, 1);
             info!("moving {:?} by {},{}", pos, delta_x, delta_y);
@@ -249,6 +542,252 @@ fn do_ship_fly(
 
 #[cfg(test)]
 mod test {
+    use crate::events::Event;
+    use crate::models::{Galaxy, Location, Sector, P2};
+    use crate::ship::{self, Command, Ship};
+    use crate::test_utils::{assert_orbiting, get_credits, get_fuel, new_state_default_scenery};
+    use crate::trade::{Cargo, Commodity};
+    use specs::prelude::*;
+
     #[test]
     fn test_landing() {}
+
+    #[test]
+    fn test_ship_should_jump_between_sectors() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let sector_0 = scenery.sectors["sector 0"];
+        let sector_1 = scenery.sectors["sector 1"];
+        let gate_id = scenery.bodies["gate to sector 1"];
+        let station_id = scenery.bodies["Station Beta"];
+
+        let galaxy = sim
+            .ecs()
+            .read_storage::<Galaxy>()
+            .get(scenery.galaxy_id)
+            .cloned()
+            .unwrap();
+        assert_eq!(vec![sector_0, sector_1], galaxy.sectors);
+
+        // can not fly directly to other sector
+        sim.set_ship_command(
+            ship_id,
+            Command::FlyTo {
+                target_id: station_id,
+            },
+        )
+        .unwrap();
+        sim.run(200);
+        assert!(!matches!(
+            sim.ecs().read_storage::<Location>().get(ship_id),
+            Some(Location::Orbit { target_id }) if *target_id == station_id
+        ));
+
+        // fly to the gate and jump
+        sim.set_ship_command(ship_id, Command::FlyTo { target_id: gate_id })
+            .unwrap();
+        let ticks = sim.run_until(2000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { target_id }) if *target_id == gate_id
+            )
+        });
+        assert!(ticks.is_some(), "ship never arrive at gate");
+
+        sim.set_ship_command(ship_id, Command::Jump { target_id: gate_id })
+            .unwrap();
+        let ticks = sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Sector { sector_id, .. }) if *sector_id == sector_1
+            )
+        });
+        assert!(ticks.is_some(), "ship never jump");
+        match sim.ecs().read_storage::<Location>().get(ship_id) {
+            Some(Location::Sector { pos, .. }) => assert_eq!(P2::new(-4, -4), *pos),
+            other => panic!("unexpected ship location {:?}", other),
+        }
+        sim.tick();
+        assert!(sim.events().contains(&Event::Jumped {
+            ship_id,
+            sector_id: sector_1
+        }));
+        {
+            let sectors = sim.ecs().read_storage::<Sector>();
+            assert!(!sectors.get(sector_0).unwrap().bodies.contains(&ship_id));
+            assert!(sectors.get(sector_1).unwrap().bodies.contains(&ship_id));
+        }
+
+        // and now can reach the station in the new sector
+        sim.set_ship_command(
+            ship_id,
+            Command::FlyTo {
+                target_id: station_id,
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(2000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { target_id }) if *target_id == station_id
+            )
+        });
+        assert!(ticks.is_some(), "ship never arrive at station");
+    }
+
+    #[test]
+    fn test_ship_should_burn_fuel_flying_and_refuel_at_station() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let station_id = scenery.bodies["Station Alpha"];
+
+        // ships start with full tanks
+        let capacity = ship::get_ship_stats(sim.ecs(), ship_id).fuel_capacity();
+        assert_eq!(capacity, get_fuel(&sim, ship_id));
+
+        let distance = ship::fly_distance(sim.ecs(), ship_id, station_id).unwrap();
+        assert!(distance > 0);
+        sim.set_ship_command(
+            ship_id,
+            Command::FlyTo {
+                target_id: station_id,
+            },
+        )
+        .unwrap();
+        sim.run_until(2000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { target_id }) if *target_id == station_id
+            )
+        })
+        .expect("ship never arrive at station");
+        assert_eq!(
+            capacity - distance * ship::FUEL_PER_STEP,
+            get_fuel(&sim, ship_id)
+        );
+
+        // refuel only when docked
+        assert_eq!(Err("ship is not docked".to_string()), sim.refuel(ship_id));
+        sim.set_ship_command(
+            ship_id,
+            Command::Dock {
+                target_id: station_id,
+            },
+        )
+        .unwrap();
+        sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Docked { .. })
+            )
+        })
+        .expect("ship never dock");
+
+        // fuel in the cargo fill the tanks before buying from the station
+        sim.ecs_mut()
+            .write_storage::<Cargo>()
+            .get_mut(ship_id)
+            .unwrap()
+            .goods
+            .insert(Commodity::Fuel, 1);
+        let before = get_credits(&sim, ship_id);
+        assert_eq!(Ok(distance * ship::FUEL_PER_STEP), sim.refuel(ship_id));
+        assert_eq!(capacity, get_fuel(&sim, ship_id));
+        assert_eq!(
+            0,
+            sim.ecs()
+                .read_storage::<Cargo>()
+                .get(ship_id)
+                .unwrap()
+                .get(Commodity::Fuel)
+        );
+        assert!(get_credits(&sim, ship_id) < before);
+        assert_eq!(Err("fuel tanks are full".to_string()), sim.refuel(ship_id));
+        sim.tick();
+        assert!(sim
+            .events()
+            .iter()
+            .any(|e| matches!(e, Event::Refueled { amount, .. } if *amount == distance)));
+
+        // ships can not fly further than its fuel range
+        sim.set_ship_command(ship_id, Command::Launch).unwrap();
+        sim.tick();
+        let planet_id = scenery.bodies["Planet X"];
+        let distance = ship::fly_distance(sim.ecs(), ship_id, planet_id).unwrap();
+        sim.ecs_mut()
+            .write_storage::<Ship>()
+            .get_mut(ship_id)
+            .unwrap()
+            .fuel = distance * ship::FUEL_PER_STEP - 1;
+        assert_eq!(
+            Err(format!("not enough fuel to fly {} cells", distance)),
+            sim.set_ship_command(
+                ship_id,
+                Command::FlyTo {
+                    target_id: planet_id
+                },
+            )
+        );
+    }
+
+    #[test]
+    fn test_ship_should_harvest_fuel_to_launch_from_planet() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let zone_id = scenery.zones["Planet X"][0];
+
+        assert_eq!(
+            Err("ship must be landed to harvest fuel".to_string()),
+            sim.set_ship_command(ship_id, Command::Harvest)
+        );
+
+        sim.set_ship_command(
+            ship_id,
+            Command::Land {
+                target_id: zone_id,
+                place_coords: P2::new(10, 10),
+            },
+        )
+        .unwrap();
+        sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::BodySurfacePlace { .. })
+            )
+        })
+        .expect("ship never land");
+
+        sim.ecs_mut()
+            .write_storage::<Ship>()
+            .get_mut(ship_id)
+            .unwrap()
+            .fuel = 0;
+        assert_eq!(
+            Err("not enough fuel to launch".to_string()),
+            sim.set_ship_command(ship_id, Command::Launch)
+        );
+
+        sim.set_ship_command(ship_id, Command::Harvest).unwrap();
+        sim.run_until(5000, |ecs| {
+            ecs.read_storage::<Ship>().get(ship_id).unwrap().fuel >= ship::FUEL_PER_LAUNCH
+        })
+        .expect("ship never harvest fuel");
+        sim.tick();
+        assert!(sim.events().contains(&Event::FuelHarvested {
+            ship_id,
+            amount: ship::HARVEST_FUEL
+        }));
+
+        let harvested = get_fuel(&sim, ship_id);
+        sim.set_ship_command(ship_id, Command::Launch).unwrap();
+        sim.run_until(ship::HARVEST_TIME + 1, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { .. })
+            )
+        })
+        .expect("ship never launch");
+        assert_orbiting(&sim, ship_id, scenery.bodies["Planet X"]);
+        assert_eq!(harvested - ship::FUEL_PER_LAUNCH, get_fuel(&sim, ship_id));
+    }
 }
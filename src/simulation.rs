@@ -0,0 +1,249 @@
+use crate::actions::{self, Action};
+use crate::cfg::Cfg;
+use crate::events::{Event, EventsHistory};
+use crate::game_time::{self, Energy, GameTime, TimeMode};
+use crate::models::{Player, P2};
+use crate::scenery::{self, Scenery, SceneryCfg, SceneryError};
+use crate::ship::build::{self, ShipPart};
+use crate::ship::damage::ShipImpacts;
+use crate::ship::encounter::{self, EncounterAction};
+use crate::state::State;
+use crate::trade::{self, Commodity};
+use crate::view::cockpit_window::CockpitWindowState;
+use crate::view::window::Window;
+use crate::{ship, squad, survival_system};
+use specs::prelude::*;
+
+/// max ticks to wait for the avatar energy before giving up
+const MAX_WAIT_TICKS: u32 = 1000;
+
+/// Headless access to the game, player commands are injected directly and the world is advanced
+/// one tick at time without any rltk context
+pub struct Simulation {
+    pub state: State,
+}
+
+impl Simulation {
+    pub fn new(cfg: Cfg) -> Self {
+        let mut state = State::new(cfg);
+        state.ecs.insert(Window::World);
+        state.ecs.insert(CockpitWindowState::default());
+        state.ecs.insert(EventsHistory::default());
+        Simulation { state }
+    }
+
+    /// create a simulation using default cfg files and the given scenery
+    pub fn from_scenery(scenery_cfg: &SceneryCfg) -> Result<(Self, Scenery), SceneryError> {
+        let cfg = Cfg::load().map_err(SceneryError::Legend)?;
+        let mut sim = Simulation::new(cfg);
+        let scenery = scenery::create_scenery(&mut sim.state.ecs, scenery_cfg)?;
+        Ok((sim, scenery))
+    }
+
+    pub fn ecs(&self) -> &World {
+        &self.state.ecs
+    }
+
+    pub fn ecs_mut(&mut self) -> &mut World {
+        &mut self.state.ecs
+    }
+
+    pub fn get_avatar_id(&self) -> Entity {
+        self.state.ecs.fetch::<Player>().get_avatar_id()
+    }
+
+    pub fn set_time_mode(&mut self, mode: TimeMode) {
+        self.state.ecs.write_resource::<GameTime>().mode = mode;
+    }
+
+    pub fn get_time(&self) -> GameTime {
+        (*self.state.ecs.fetch::<GameTime>()).clone()
+    }
+
+    /// advance the time until all controlled avatars have energy to act
+    pub fn wait_avatar_ready(&mut self) {
+        self.run_until(MAX_WAIT_TICKS, |ecs| {
+            let energies = ecs.read_storage::<Energy>();
+            (&ecs.entities(), &ecs.fetch::<Player>().get_controlled())
+                .join()
+                .all(|(e, _)| game_time::is_ready(&energies, e))
+        });
+    }
+
+    /// move the avatar as soon it has energy
+    pub fn move_avatar(&mut self, delta_x: i32, delta_y: i32) {
+        self.wait_avatar_ready();
+        actions::try_move_player(delta_x, delta_y, &mut self.state.ecs);
+    }
+
+    /// fire at the closest hostile in view as soon the avatar has energy, return false if there is
+    /// no target
+    pub fn fire_at_nearest(&mut self) -> bool {
+        self.wait_avatar_ready();
+        actions::fire_at_nearest(&mut self.state.ecs)
+    }
+
+    pub fn interact(&mut self) {
+        self.set_action(Action::Interact);
+    }
+
+    /// start walking to the target, return false if there is no path
+    pub fn walk_to(&mut self, target: P2) -> bool {
+        actions::set_auto_walk(&mut self.state.ecs, target)
+    }
+
+    /// the action is executed on next tick, as soon the avatar has energy
+    pub fn set_action(&mut self, action: Action) {
+        self.wait_avatar_ready();
+        actions::set_current_action(&mut self.state.ecs, action);
+    }
+
+    /// add the crew next to the focused avatar into the squad
+    pub fn join_squad(&mut self) -> Option<Entity> {
+        squad::join_squad(&mut self.state.ecs)
+    }
+
+    /// remove the focused avatar from the squad
+    pub fn leave_squad(&mut self) -> bool {
+        squad::leave_squad(&mut self.state.ecs)
+    }
+
+    pub fn focus_next(&mut self) -> Entity {
+        squad::focus_next(&mut self.state.ecs)
+    }
+
+    pub fn toggle_group(&mut self) {
+        squad::toggle_group(&mut self.state.ecs);
+    }
+
+    pub fn set_ship_command(
+        &mut self,
+        ship_id: Entity,
+        command: ship::Command,
+    ) -> Result<(), String> {
+        ship::set_ship_command(&mut self.state.ecs, ship_id, command)
+    }
+
+    /// buy (positive amount) or sell (negative amount) at the station the ship is docked
+    pub fn trade(
+        &mut self,
+        ship_id: Entity,
+        commodity: Commodity,
+        amount: i32,
+    ) -> Result<i32, String> {
+        trade::trade(&mut self.state.ecs, ship_id, commodity, amount)
+    }
+
+    /// the action is executed on next tick while the ship is in an encounter
+    pub fn encounter_action(
+        &mut self,
+        ship_id: Entity,
+        action: EncounterAction,
+    ) -> Result<(), String> {
+        encounter::set_encounter_action(&mut self.state.ecs, ship_id, action)
+    }
+
+    /// hit the ship, the damage is applied on the next tick
+    pub fn damage_ship(&mut self, ship_id: Entity, damage: i32) {
+        self.state
+            .ecs
+            .write_resource::<ShipImpacts>()
+            .push(ship_id, damage);
+    }
+
+    /// fill the tanks with fuel from the station the ship is docked, return the fuel added
+    pub fn refuel(&mut self, ship_id: Entity) -> Result<u32, String> {
+        trade::refuel(&mut self.state.ecs, ship_id)
+    }
+
+    /// place a part at the ship cell, only while the ship is in space
+    pub fn build(&mut self, ship_id: Entity, point: P2, part: ShipPart) -> Result<(), String> {
+        build::place(&mut self.state.ecs, ship_id, point, part)
+    }
+
+    /// remove the part at the ship cell, only while the ship is in space
+    pub fn unbuild(&mut self, ship_id: Entity, point: P2) -> Result<(), String> {
+        build::remove(&mut self.state.ecs, ship_id, point)
+    }
+
+    /// ship layout as map lines using the default legend
+    pub fn export_ship(&self, ship_id: Entity) -> Result<Vec<String>, String> {
+        let legend = &self.state.ecs.fetch::<Cfg>().map_parser;
+        build::export(&self.state.ecs, legend, ship_id)
+    }
+
+    /// respawn the dead player avatar, return false if there is no place to respawn
+    pub fn respawn(&mut self) -> bool {
+        survival_system::respawn(&mut self.state.ecs)
+    }
+
+    /// all events since the simulation start, older first
+    pub fn events(&self) -> Vec<Event> {
+        self.state.ecs.fetch::<EventsHistory>().events.clone()
+    }
+
+    /// run the systems after a tick of real time
+    pub fn tick(&mut self) {
+        self.state
+            .ecs
+            .write_resource::<GameTime>()
+            .add_real_time(game_time::TICK_MS);
+        crate::run_systems(&mut self.state.ecs);
+    }
+
+    pub fn run(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            self.tick();
+        }
+    }
+
+    /// run until the condition is true, returning the number of ticks executed or None if the
+    /// condition was not satisfied after max_ticks
+    pub fn run_until<F>(&mut self, max_ticks: u32, condition: F) -> Option<u32>
+    where
+        F: Fn(&World) -> bool,
+    {
+        for tick in 0..max_ticks {
+            if condition(&self.state.ecs) {
+                return Some(tick);
+            }
+            self.tick();
+        }
+
+        if condition(&self.state.ecs) {
+            Some(max_ticks)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::{EntitiesEvents, MessageLog, MAX_LOG_ENTRIES};
+    use crate::test_utils::new_state_basic_scenery;
+
+    #[test]
+    fn test_events_should_keep_the_entries_dropped_from_the_log() {
+        let (mut sim, _) = new_state_basic_scenery();
+        let avatar_id = sim.get_avatar_id();
+        let previous = sim.events().len();
+        for _ in 0..MAX_LOG_ENTRIES + 50 {
+            sim.ecs_mut()
+                .write_resource::<EntitiesEvents>()
+                .push(Event::Died {
+                    entity_id: avatar_id,
+                });
+        }
+        sim.tick();
+
+        let log_len = sim.ecs().fetch::<MessageLog>().get_entries().len();
+        assert_eq!(MAX_LOG_ENTRIES, log_len);
+        let died = sim.events()[previous..]
+            .iter()
+            .filter(|event| matches!(event, Event::Died { .. }))
+            .count();
+        assert_eq!(MAX_LOG_ENTRIES + 50, died);
+    }
+}
@@ -0,0 +1,132 @@
+use crate::crew::Crew;
+use crate::events::{EntitiesEvents, Event};
+use crate::models::{Player, Position, Stats};
+use specs::prelude::*;
+
+/// add the crew member next to the focused avatar into the squad, the crew stop doing its job
+/// until it leave the squad
+pub fn join_squad(ecs: &mut World) -> Option<Entity> {
+    let avatar_id = ecs.fetch::<Player>().get_avatar_id();
+
+    let found = {
+        let entities = ecs.entities();
+        let positions = ecs.read_storage::<Position>();
+        let crews = ecs.read_storage::<Crew>();
+        let stats = ecs.read_storage::<Stats>();
+        let player = ecs.fetch::<Player>();
+
+        let avatar_pos = positions.get(avatar_id)?;
+        (&entities, &crews, &positions, stats.maybe())
+            .join()
+            .filter(|(e, _, _, stats)| {
+                !player.is_avatar(*e) && !stats.map(|s| s.is_dead()).unwrap_or(false)
+            })
+            .find(|(_, _, pos, _)| {
+                pos.grid_id == avatar_pos.grid_id
+                    && (pos.point.x - avatar_pos.point.x).abs() <= 1
+                    && (pos.point.y - avatar_pos.point.y).abs() <= 1
+            })
+            .map(|(e, _, _, _)| e)
+    };
+
+    match found {
+        Some(crew_id) => {
+            ecs.write_resource::<Player>().add_avatar(crew_id);
+            ecs.write_resource::<EntitiesEvents>()
+                .push(Event::JoinedSquad { entity_id: crew_id });
+        }
+        None => {
+            ecs.write_resource::<EntitiesEvents>()
+                .push(Event::ActionFailed {
+                    actor_id: avatar_id,
+                    reason: "nobody to join the squad".to_string(),
+                });
+        }
+    }
+
+    found
+}
+
+/// remove the focused avatar from the squad, the last avatar can not leave
+pub fn leave_squad(ecs: &mut World) -> bool {
+    let avatar_id = ecs.fetch::<Player>().get_avatar_id();
+    let removed = ecs.write_resource::<Player>().remove_avatar(avatar_id);
+    let event = if removed {
+        Event::LeftSquad {
+            entity_id: avatar_id,
+        }
+    } else {
+        Event::ActionFailed {
+            actor_id: avatar_id,
+            reason: "can not leave the squad".to_string(),
+        }
+    };
+    ecs.write_resource::<EntitiesEvents>().push(event);
+    removed
+}
+
+pub fn focus_next(ecs: &mut World) -> Entity {
+    ecs.write_resource::<Player>().focus_next()
+}
+
+pub fn toggle_group(ecs: &mut World) {
+    let mut player = ecs.write_resource::<Player>();
+    let group = player.is_group();
+    player.set_group(!group);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::Event;
+    use crate::models::{Position, P2};
+    use crate::test_utils::{get_position, new_state_basic_scenery};
+
+    #[test]
+    fn test_squad_should_move_as_group_or_individually() {
+        let (mut sim, scenery) = new_state_basic_scenery();
+        let avatar_id = scenery.avatar_id;
+        let pilot_id = scenery.crew["pilot"];
+
+        // nobody close enough
+        assert_eq!(None, sim.join_squad());
+
+        // leave the cockpit, wait the pilot come to it and recruit it
+        sim.move_avatar(0, 1);
+        let ticks = sim.run_until(500, |ecs| {
+            ecs.read_storage::<Position>().get(pilot_id).unwrap().point == P2::new(8, 8)
+        });
+        assert!(ticks.is_some());
+        assert_eq!(Some(pilot_id), sim.join_squad());
+        sim.tick();
+        assert!(sim.events().contains(&Event::JoinedSquad {
+            entity_id: pilot_id
+        }));
+
+        // move as group
+        sim.toggle_group();
+        sim.move_avatar(1, 0);
+        assert_eq!(P2::new(9, 9), get_position(&sim, avatar_id).point);
+        assert_eq!(P2::new(9, 8), get_position(&sim, pilot_id).point);
+
+        // move only the focused one
+        sim.toggle_group();
+        assert_eq!(pilot_id, sim.focus_next());
+        sim.move_avatar(0, -1);
+        assert_eq!(P2::new(9, 9), get_position(&sim, avatar_id).point);
+        assert_eq!(P2::new(9, 7), get_position(&sim, pilot_id).point);
+
+        // while in the squad the crew don't do its job
+        sim.run(100);
+        assert_eq!(P2::new(9, 7), get_position(&sim, pilot_id).point);
+
+        // after leave the squad it goes back to the cockpit
+        assert!(sim.leave_squad());
+        assert_eq!(avatar_id, sim.get_avatar_id());
+        assert!(!sim.leave_squad());
+        let ticks = sim.run_until(100, |ecs| {
+            ecs.read_storage::<Position>().get(pilot_id).unwrap().point == P2::new(8, 8)
+        });
+        assert!(ticks.is_some());
+    }
+}
@@ -1,12 +1,26 @@
+use crate::actions::auto_walk_system::AutoWalk;
 use crate::actions::EntityActions;
 use crate::cfg::Cfg;
+use crate::combat::{Combat, Creature, Faction};
+use crate::crew::Crew;
+use crate::events::{EntitiesEvents, MessageLog};
+use crate::game_time::{Energy, GameTime};
 use crate::gridref::GridRef;
+use crate::inventory::{Inventory, Item};
 use crate::models::{
-    Avatar, Label, Location, ObjectsType, Player, Position, Sector, SectorBody, Surface,
+    Avatar, Door, Galaxy, Label, Location, ObjectsType, Player, Position, Sector, SectorBody,
+    SpawnPoint, Stairs, Stats, Surface,
 };
-use crate::ship::Ship;
+use crate::save::{SaveMarker, SaveMarkerAllocator};
+use crate::ship::damage::ShipImpacts;
+use crate::ship::encounter::Encounter;
+use crate::ship::{ModuleHealth, Ship, ShipAi};
+use crate::trade::{Cargo, Market};
 use crate::view;
+use crate::view::build_window::{self, BuildWindowState};
 use crate::view::cockpit_window::CockpitWindowState;
+use crate::view::inventory_window::{self, InventoryWindowState};
+use crate::view::market_window::{self, MarketWindowState};
 use crate::view::window::Window;
 use crate::view::{Renderable, Viewshed};
 use rltk::BTerm as Rltk;
@@ -28,16 +42,43 @@ impl State {
         gs.ecs.register::<EntityActions>();
         gs.ecs.register::<Window>();
         gs.ecs.register::<Ship>();
+        gs.ecs.register::<ModuleHealth>();
+        gs.ecs.register::<ShipAi>();
+        gs.ecs.register::<Encounter>();
         gs.ecs.register::<Avatar>();
         gs.ecs.register::<CockpitWindowState>();
         gs.ecs.register::<Location>();
         gs.ecs.register::<Surface>();
         gs.ecs.register::<Sector>();
+        gs.ecs.register::<Galaxy>();
         gs.ecs.register::<Label>();
         gs.ecs.register::<SectorBody>();
         gs.ecs.register::<GridRef>();
+        gs.ecs.register::<Door>();
+        gs.ecs.register::<Stairs>();
+        gs.ecs.register::<Stats>();
+        gs.ecs.register::<SpawnPoint>();
+        gs.ecs.register::<Energy>();
+        gs.ecs.register::<AutoWalk>();
+        gs.ecs.register::<Crew>();
+        gs.ecs.register::<Faction>();
+        gs.ecs.register::<Combat>();
+        gs.ecs.register::<Creature>();
+        gs.ecs.register::<Item>();
+        gs.ecs.register::<Inventory>();
+        gs.ecs.register::<Cargo>();
+        gs.ecs.register::<Market>();
+        gs.ecs.register::<SaveMarker>();
 
         gs.ecs.insert(cfg);
+        gs.ecs.insert(SaveMarkerAllocator::new());
+        gs.ecs.insert(GameTime::default());
+        gs.ecs.insert(EntitiesEvents::default());
+        gs.ecs.insert(ShipImpacts::default());
+        gs.ecs.insert(MessageLog::default());
+        gs.ecs.insert(InventoryWindowState::default());
+        gs.ecs.insert(MarketWindowState::default());
+        gs.ecs.insert(BuildWindowState::default());
 
         gs
     }
@@ -47,21 +88,49 @@ impl rltk::GameState for State {
     fn tick(&mut self, ctx: &mut Rltk) {
         ctx.cls();
 
+        self.ecs
+            .write_resource::<GameTime>()
+            .add_real_time(ctx.frame_time_ms);
         let window = *self.ecs.fetch::<Window>();
 
         match window {
             Window::World => {
                 view::player_input(self, ctx);
-                crate::run_systems(self, ctx);
+                crate::run_systems(&mut self.ecs);
                 view::draw_map_and_objects(self, ctx);
                 view::draw_gui(self, ctx);
+                view::draw_mouse(self, ctx);
             }
 
             Window::Cockpit => {
-                crate::run_systems(self, ctx);
+                crate::run_systems(&mut self.ecs);
                 view::draw_map_and_objects(self, ctx);
                 view::cockpit_window::draw(self, ctx);
             }
+
+            Window::Inventory => {
+                crate::run_systems(&mut self.ecs);
+                view::draw_map_and_objects(self, ctx);
+                inventory_window::draw(self, ctx);
+            }
+
+            Window::Market => {
+                crate::run_systems(&mut self.ecs);
+                view::draw_map_and_objects(self, ctx);
+                market_window::draw(self, ctx);
+            }
+
+            Window::Build => {
+                crate::run_systems(&mut self.ecs);
+                view::draw_map_and_objects(self, ctx);
+                build_window::draw(self, ctx);
+            }
+
+            Window::GameOver => {
+                crate::run_systems(&mut self.ecs);
+                view::draw_map_and_objects(self, ctx);
+                view::game_over_window::draw(self, ctx);
+            }
         }
     }
 }
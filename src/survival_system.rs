@@ -0,0 +1,231 @@
+use crate::actions::auto_walk_system::AutoWalk;
+use crate::actions::EntityActions;
+use crate::atmosphere::{Air, Gas};
+use crate::combat::{Combat, Creature, Faction};
+use crate::crew::Crew;
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::{Energy, GameTime};
+use crate::gridref::GridRef;
+use crate::models::{ObjectsType, Player, Position, SpawnPoint, Stats};
+use crate::unwrap_or_continue;
+use crate::view::window::Window;
+use crate::view::{Renderable, Viewshed};
+use rltk::RGB;
+use specs::prelude::*;
+
+/// oxygen recovered per tick when breathing
+pub const OXYGEN_REGEN: i32 = 5;
+/// oxygen consumed per tick when there is nothing to breath
+pub const OXYGEN_DRAIN: i32 = 1;
+pub const SUFFOCATION_DAMAGE: i32 = 1;
+pub const TOXIC_DAMAGE: i32 = 1;
+
+/// apply one tick of the air into the stats
+pub fn breath(stats: &mut Stats, air: &Air) {
+    if air.is_breathable() {
+        stats.oxygen = (stats.oxygen + OXYGEN_REGEN).min(stats.max_oxygen);
+    } else {
+        stats.oxygen = (stats.oxygen - OXYGEN_DRAIN).max(0);
+    }
+
+    if stats.oxygen == 0 {
+        stats.health -= SUFFOCATION_DAMAGE;
+    }
+
+    if air.gas == Gas::Toxic && !air.is_vacuum() {
+        stats.health -= TOXIC_DAMAGE;
+    }
+}
+
+/// Turn the dead entity into its own corpse, it keeps its label and position but lose anything
+/// that makes it act
+fn make_corpse(lazy: &LazyUpdate, e: Entity) {
+    lazy.remove::<Stats>(e);
+    lazy.remove::<Viewshed>(e);
+    lazy.remove::<EntityActions>(e);
+    lazy.remove::<Energy>(e);
+    lazy.remove::<AutoWalk>(e);
+    lazy.remove::<Crew>(e);
+    lazy.remove::<Creature>(e);
+    lazy.remove::<Faction>(e);
+    lazy.remove::<Combat>(e);
+    lazy.insert(e, ObjectsType::Corpse);
+    lazy.insert(
+        e,
+        Renderable {
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::RED),
+            bg: RGB::named(rltk::BLACK),
+            priority: 0,
+        },
+    );
+}
+
+pub struct SurvivalSystem {}
+
+impl<'a> System<'a> for SurvivalSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Stats>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, GridRef>,
+        WriteExpect<'a, Player>,
+        ReadExpect<'a, GameTime>,
+        WriteExpect<'a, Window>,
+        Write<'a, EntitiesEvents>,
+        Read<'a, LazyUpdate>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut stats, positions, grids, mut player, time, mut window, mut events, lazy): Self::SystemData,
+    ) {
+        for (stats, pos) in (&mut stats, &positions).join() {
+            if stats.is_dead() {
+                continue;
+            }
+
+            let area = unwrap_or_continue!(GridRef::find_area(&grids, pos.grid_id));
+            let air = area
+                .get_grid()
+                .get_at(&pos.point)
+                .map(|cell| cell.air)
+                .unwrap_or_default();
+
+            for _ in 0..time.actors_elapsed {
+                breath(stats, &air);
+            }
+        }
+
+        // anything can kill, suffocation, combat, etc
+        for (e, stats) in (&entities, &stats).join() {
+            if !stats.is_dead() {
+                continue;
+            }
+
+            // game is over only when the last avatar of the squad die
+            if player.is_avatar(e) && !player.remove_avatar(e) {
+                if !matches!(*window, Window::GameOver) {
+                    log::info!("avatar {:?} is dead", e);
+                    events.push(Event::Died { entity_id: e });
+                    *window = Window::GameOver;
+                }
+            } else {
+                log::info!("{:?} is dead", e);
+                events.push(Event::Died { entity_id: e });
+                make_corpse(&lazy, e);
+            }
+        }
+    }
+}
+
+/// bring the player avatar back to life at the first spawn point, return false if there is
+/// no spawn point
+pub fn respawn(ecs: &mut World) -> bool {
+    let avatar_id = ecs.fetch::<Player>().get_avatar_id();
+
+    let spawn_pos = {
+        let spawns = ecs.read_storage::<SpawnPoint>();
+        let positions = ecs.read_storage::<Position>();
+        match (&spawns, &positions).join().next() {
+            Some((_, pos)) => pos.clone(),
+            None => {
+                log::warn!("no spawn point found to respawn {:?}", avatar_id);
+                return false;
+            }
+        }
+    };
+
+    if let Some(stats) = ecs.write_storage::<Stats>().get_mut(avatar_id) {
+        stats.restore();
+    }
+    ecs.write_storage::<Position>()
+        .insert(avatar_id, spawn_pos)
+        .unwrap();
+    ecs.insert(Window::World);
+
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::actions::Action;
+    use crate::models::{Stats, P2};
+    use crate::test_utils::{get_avatar_position, new_state_basic_scenery, walk_into_airlock};
+    use crate::view::window::Window;
+
+    #[test]
+    fn test_breath_should_drain_oxygen_and_damage_when_empty() {
+        let mut stats = Stats::new(10, 2);
+
+        breath(&mut stats, &Air::VACUUM);
+        assert_eq!(1, stats.oxygen);
+        assert_eq!(10, stats.health);
+
+        breath(&mut stats, &Air::VACUUM);
+        assert_eq!(0, stats.oxygen);
+        assert_eq!(10 - SUFFOCATION_DAMAGE, stats.health);
+
+        breath(&mut stats, &Air::STANDARD);
+        assert_eq!(2, stats.oxygen);
+        assert_eq!(10 - SUFFOCATION_DAMAGE, stats.health);
+    }
+
+    #[test]
+    fn test_breath_toxic_air_should_damage() {
+        let mut stats = Stats::new(10, 100);
+        let air = Air {
+            pressure: 1.0,
+            gas: Gas::Toxic,
+        };
+
+        breath(&mut stats, &air);
+        assert_eq!(99, stats.oxygen);
+        assert_eq!(10 - TOXIC_DAMAGE, stats.health);
+    }
+
+    #[test]
+    fn test_avatar_should_die_in_vacuum_and_respawn() {
+        let (mut sim, _) = new_state_basic_scenery();
+        let avatar_id = sim.get_avatar_id();
+        walk_into_airlock(&mut sim);
+
+        // speed up by starting almost dead
+        {
+            let mut stats = sim.ecs().write_storage::<Stats>();
+            let stats = stats.get_mut(avatar_id).unwrap();
+            stats.oxygen = 3;
+            stats.health = 2;
+        }
+
+        sim.set_action(Action::CycleAirlock);
+        let ticks = sim.run_until(100, |ecs| {
+            matches!(*ecs.fetch::<Window>(), Window::GameOver)
+        });
+        assert!(ticks.is_some(), "avatar never die");
+        assert!(sim
+            .ecs()
+            .read_storage::<Stats>()
+            .get(avatar_id)
+            .unwrap()
+            .is_dead());
+
+        // dead avatars can not move
+        let before = get_avatar_position(&sim).point;
+        sim.move_avatar(-1, 0);
+        assert_eq!(before, get_avatar_position(&sim).point);
+
+        assert!(sim.respawn());
+        assert!(matches!(*sim.ecs().fetch::<Window>(), Window::World));
+        assert_eq!(P2::new(8, 8), get_avatar_position(&sim).point);
+        let stats = sim
+            .ecs()
+            .read_storage::<Stats>()
+            .get(avatar_id)
+            .cloned()
+            .unwrap();
+        assert_eq!(stats.max_health, stats.health);
+        assert_eq!(stats.max_oxygen, stats.oxygen);
+    }
+}
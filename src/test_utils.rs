@@ -0,0 +1,111 @@
+use crate::actions::Action;
+use crate::atmosphere::Air;
+use crate::gridref::GridRef;
+use crate::models::{Location, Position, P2};
+use crate::scenery::{self, LocationCfg, Scenery};
+use crate::ship::encounter::Encounter;
+use crate::ship::Ship;
+use crate::simulation::Simulation;
+use crate::trade::Cargo;
+use specs::prelude::*;
+
+/// from the cockpit walk into the airlock closing the inner door behind
+pub fn walk_into_airlock(sim: &mut Simulation) {
+    walk(sim, 1, 0, 3);
+    sim.set_action(Action::Open(P2::new(12, 8)));
+    sim.tick();
+    walk(sim, 1, 0, 4);
+    walk(sim, 0, 1, 1);
+    sim.set_action(Action::Open(P2::new(16, 9)));
+    sim.tick();
+    walk(sim, 1, 0, 2);
+    sim.set_action(Action::Close(P2::new(16, 9)));
+    sim.tick();
+    walk(sim, 0, -1, 1);
+    walk(sim, 1, 0, 2);
+    assert_eq!(P2::new(19, 8), get_avatar_position(sim).point);
+}
+
+pub fn get_position(sim: &Simulation, entity: Entity) -> Position {
+    sim.ecs()
+        .read_storage::<Position>()
+        .get(entity)
+        .cloned()
+        .unwrap()
+}
+
+pub fn walk(sim: &mut Simulation, delta_x: i32, delta_y: i32, steps: u32) {
+    for _ in 0..steps {
+        sim.move_avatar(delta_x, delta_y);
+    }
+}
+
+pub fn get_air_at(sim: &Simulation, pos: P2) -> Air {
+    let avatar_pos = get_avatar_position(sim);
+    let grids = sim.ecs().read_storage::<GridRef>();
+    let area = GridRef::find_area(&grids, avatar_pos.grid_id).unwrap();
+    area.get_grid().get_at(&pos).unwrap().air
+}
+
+pub fn is_opaque_at(sim: &Simulation, pos: P2) -> bool {
+    let avatar_pos = get_avatar_position(sim);
+    let grids = sim.ecs().read_storage::<GridRef>();
+    let area = GridRef::find_area(&grids, avatar_pos.grid_id).unwrap();
+    area.get_grid().get_at(&pos).unwrap().tile.is_opaque()
+}
+
+pub fn assert_orbiting(sim: &Simulation, ship_id: Entity, body_id: Entity) {
+    match sim.ecs().read_storage::<Location>().get(ship_id) {
+        Some(Location::Orbit { target_id }) => assert_eq!(body_id, *target_id),
+        other => panic!("unexpected ship location {:?}", other),
+    }
+}
+
+pub fn get_avatar_position(sim: &Simulation) -> Position {
+    let avatar_id = sim.get_avatar_id();
+    sim.ecs()
+        .read_storage::<Position>()
+        .get(avatar_id)
+        .cloned()
+        .unwrap()
+}
+
+/// default scenery with the ship drifting in the sector
+pub fn new_state_basic_scenery() -> (Simulation, Scenery) {
+    let mut scenery_cfg = scenery::load_default_scenery().unwrap();
+    scenery_cfg.ships[0].location = LocationCfg::Sector {
+        sector: "sector 0".to_string(),
+        pos: P2::new(0, 0),
+    };
+    Simulation::from_scenery(&scenery_cfg).unwrap()
+}
+
+/// default scenery as it is loaded by the game
+pub fn new_state_default_scenery() -> (Simulation, Scenery) {
+    let scenery_cfg = scenery::load_default_scenery().unwrap();
+    Simulation::from_scenery(&scenery_cfg).unwrap()
+}
+
+pub fn get_hull(sim: &Simulation, ship_id: Entity) -> i32 {
+    sim.ecs().read_storage::<Ship>().get(ship_id).unwrap().hull
+}
+
+pub fn get_fuel(sim: &Simulation, ship_id: Entity) -> u32 {
+    sim.ecs().read_storage::<Ship>().get(ship_id).unwrap().fuel
+}
+
+pub fn get_credits(sim: &Simulation, ship_id: Entity) -> i32 {
+    sim.ecs()
+        .read_storage::<Cargo>()
+        .get(ship_id)
+        .unwrap()
+        .credits
+}
+
+/// the other ship of the encounter
+pub fn get_encounter_of(sim: &Simulation, ship_id: Entity) -> Option<Entity> {
+    sim.ecs()
+        .read_storage::<Encounter>()
+        .get(ship_id)
+        .map(|encounter| encounter.other_id)
+}
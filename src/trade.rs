@@ -0,0 +1,434 @@
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::GameTime;
+use crate::models::Location;
+use crate::ship::{self, Ship};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use specs::prelude::*;
+use specs_derive::*;
+use std::collections::BTreeMap;
+
+/// game ticks between each market prices change
+pub const PRICE_PERIOD: u64 = 1000;
+/// how much a station price can differ from the base price
+const STATION_VARIATION: f32 = 0.3;
+/// how much a price can change from one period to another
+const TIME_VARIATION: f32 = 0.1;
+/// difference between buy and sell prices, stations always profit
+const MARKET_SPREAD: f32 = 0.1;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Commodity {
+    Food,
+    Ore,
+    Fuel,
+    Electronics,
+}
+
+impl Commodity {
+    pub const ALL: [Commodity; 4] = [
+        Commodity::Food,
+        Commodity::Ore,
+        Commodity::Fuel,
+        Commodity::Electronics,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Commodity::Food => "food",
+            Commodity::Ore => "ore",
+            Commodity::Fuel => "fuel",
+            Commodity::Electronics => "electronics",
+        }
+    }
+
+    pub fn base_price(&self) -> i32 {
+        match self {
+            Commodity::Food => 10,
+            Commodity::Ore => 20,
+            Commodity::Fuel => 30,
+            Commodity::Electronics => 80,
+        }
+    }
+}
+
+/// Ship cargo hold, capacity is the max units of all commodities together
+#[derive(Component, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Cargo {
+    pub capacity: u32,
+    pub credits: i32,
+    #[serde(default)]
+    pub goods: BTreeMap<Commodity, u32>,
+}
+
+impl Cargo {
+    pub fn new(capacity: u32, credits: i32) -> Self {
+        Cargo {
+            capacity,
+            credits,
+            goods: BTreeMap::new(),
+        }
+    }
+
+    pub fn get(&self, commodity: Commodity) -> u32 {
+        self.goods.get(&commodity).copied().unwrap_or(0)
+    }
+
+    pub fn total(&self) -> u32 {
+        self.goods.values().sum()
+    }
+
+    pub fn free_space(&self) -> u32 {
+        self.capacity.saturating_sub(self.total())
+    }
+
+    /// remove up to the amount of the commodity from the cargo
+    pub fn remove(&mut self, commodity: Commodity, amount: u32) {
+        let current = self.get(commodity);
+        if current <= amount {
+            self.goods.remove(&commodity);
+        } else {
+            self.goods.insert(commodity, current - amount);
+        }
+    }
+}
+
+/// Station market, prices are derived from the seed so each station has its own prices
+#[derive(Component, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Market {
+    pub seed: u64,
+}
+
+impl Market {
+    /// reference price at the given tick, station variation is fixed and time variation change
+    /// every PRICE_PERIOD
+    pub fn price(&self, commodity: Commodity, tick: u64) -> i32 {
+        let index = commodity as u64;
+        let mut station_rng = StdRng::seed_from_u64(self.seed.wrapping_mul(31).wrapping_add(index));
+        let station_factor = station_rng.gen_range(-STATION_VARIATION..STATION_VARIATION);
+
+        let period = tick / PRICE_PERIOD;
+        let mut time_rng = StdRng::seed_from_u64(
+            self.seed
+                .wrapping_mul(31)
+                .wrapping_add(period)
+                .wrapping_mul(31)
+                .wrapping_add(index),
+        );
+        let time_factor = time_rng.gen_range(-TIME_VARIATION..TIME_VARIATION);
+
+        let price = commodity.base_price() as f32 * (1.0 + station_factor + time_factor);
+        (price.round() as i32).max(1)
+    }
+
+    /// price the station charge to sell a unit
+    pub fn buy_price(&self, commodity: Commodity, tick: u64) -> i32 {
+        (self.price(commodity, tick) as f32 * (1.0 + MARKET_SPREAD)).ceil() as i32
+    }
+
+    /// price the station pay for a unit
+    pub fn sell_price(&self, commodity: Commodity, tick: u64) -> i32 {
+        ((self.price(commodity, tick) as f32 * (1.0 - MARKET_SPREAD)).floor() as i32).max(1)
+    }
+}
+
+/// buy from the market into the cargo, return the total paid
+pub fn buy(
+    cargo: &mut Cargo,
+    market: &Market,
+    commodity: Commodity,
+    amount: u32,
+    tick: u64,
+) -> Result<i32, String> {
+    let total = market.buy_price(commodity, tick) * amount as i32;
+    if amount > cargo.free_space() {
+        return Err("cargo hold is full".to_string());
+    }
+    if total > cargo.credits {
+        return Err("not enough credits".to_string());
+    }
+
+    cargo.credits -= total;
+    *cargo.goods.entry(commodity).or_insert(0) += amount;
+    Ok(total)
+}
+
+/// sell from the cargo to the market, return the total received
+pub fn sell(
+    cargo: &mut Cargo,
+    market: &Market,
+    commodity: Commodity,
+    amount: u32,
+    tick: u64,
+) -> Result<i32, String> {
+    let current = cargo.get(commodity);
+    if amount > current {
+        return Err(format!("not enough {} in cargo", commodity.name()));
+    }
+
+    let total = market.sell_price(commodity, tick) * amount as i32;
+    cargo.credits += total;
+    cargo.remove(commodity, amount);
+    Ok(total)
+}
+
+/// station where the ship is docked
+pub fn find_docked_station(ecs: &World, ship_id: Entity) -> Option<Entity> {
+    match ecs.read_storage::<Location>().get(ship_id) {
+        Some(Location::Docked { station_id, .. }) => Some(*station_id),
+        _ => None,
+    }
+}
+
+/// trade between the ship cargo and the market of the station it is docked, positive amounts
+/// buy and negative ones sell. Return the credits change
+pub fn trade(
+    ecs: &mut World,
+    ship_id: Entity,
+    commodity: Commodity,
+    amount: i32,
+) -> Result<i32, String> {
+    let station_id = find_docked_station(ecs, ship_id).ok_or("ship is not docked")?;
+    let tick = ecs.fetch::<GameTime>().tick;
+
+    let markets = ecs.read_storage::<Market>();
+    let market = markets.get(station_id).ok_or("station has no market")?;
+    let mut cargos = ecs.write_storage::<Cargo>();
+    let cargo = cargos.get_mut(ship_id).ok_or("ship has no cargo hold")?;
+
+    let credits = if amount >= 0 {
+        -buy(cargo, market, commodity, amount as u32, tick)?
+    } else {
+        sell(cargo, market, commodity, amount.unsigned_abs(), tick)?
+    };
+
+    drop(markets);
+    drop(cargos);
+    ecs.write_resource::<EntitiesEvents>().push(Event::Traded {
+        ship_id,
+        station_id,
+        commodity,
+        amount,
+        credits,
+    });
+    Ok(credits)
+}
+
+/// fill the tanks up to the capacity, first with the fuel carried in the cargo and then with fuel
+/// bought from the market, as much as the credits can pay. Return the fuel added and the credits
+/// change
+pub fn buy_fuel(
+    ship: &mut Ship,
+    cargo: &mut Cargo,
+    market: &Market,
+    capacity: u32,
+    tick: u64,
+) -> Result<(u32, i32), String> {
+    let missing = capacity.saturating_sub(ship.fuel);
+    if missing == 0 {
+        return Err("fuel tanks are full".to_string());
+    }
+    let from_cargo = missing.min(cargo.get(Commodity::Fuel));
+    if from_cargo > 0 {
+        cargo.remove(Commodity::Fuel, from_cargo);
+    }
+
+    let price = market.buy_price(Commodity::Fuel, tick);
+    let bought = (missing - from_cargo).min((cargo.credits.max(0) / price) as u32);
+    let amount = from_cargo + bought;
+    if amount == 0 {
+        return Err("not enough credits".to_string());
+    }
+
+    let credits = -price * bought as i32;
+    cargo.credits += credits;
+    ship.fuel += amount;
+    Ok((amount, credits))
+}
+
+/// fill the ship tanks with the fuel in its cargo and fuel bought from the station it is docked,
+/// as much as the credits can pay. Return the fuel added
+pub fn refuel(ecs: &mut World, ship_id: Entity) -> Result<u32, String> {
+    let station_id = find_docked_station(ecs, ship_id).ok_or("ship is not docked")?;
+    let tick = ecs.fetch::<GameTime>().tick;
+    let capacity = ship::get_ship_stats(ecs, ship_id).fuel_capacity();
+
+    let markets = ecs.read_storage::<Market>();
+    let market = markets.get(station_id).ok_or("station has no market")?;
+    let mut cargos = ecs.write_storage::<Cargo>();
+    let cargo = cargos.get_mut(ship_id).ok_or("ship has no cargo hold")?;
+    let mut ships = ecs.write_storage::<Ship>();
+    let ship = ships.get_mut(ship_id).ok_or("not a ship")?;
+    let (amount, credits) = buy_fuel(ship, cargo, market, capacity, tick)?;
+
+    drop(markets);
+    drop(cargos);
+    drop(ships);
+    ecs.write_resource::<EntitiesEvents>()
+        .push(Event::Refueled {
+            ship_id,
+            station_id,
+            amount,
+            credits,
+        });
+    Ok(amount)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::events::Event;
+    use crate::gridref::GridRef;
+    use crate::inventory::{Item, ItemKind};
+    use crate::loader;
+    use crate::models::{Location, Position, Stats, P2};
+    use crate::ship::{self, Command};
+    use crate::test_utils::{
+        assert_orbiting, get_avatar_position, get_credits, get_position, new_state_default_scenery,
+    };
+
+    #[test]
+    fn test_prices_should_vary_per_station_and_over_time() {
+        let a = Market { seed: 1 };
+        let b = Market { seed: 2 };
+
+        let prices = |market: &Market, tick: u64| {
+            Commodity::ALL
+                .iter()
+                .map(|c| market.price(*c, tick))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(prices(&a, 0), prices(&a, PRICE_PERIOD - 1));
+        assert_ne!(prices(&a, 0), prices(&b, 0));
+        assert_ne!(prices(&a, 0), prices(&a, PRICE_PERIOD * 5));
+
+        for c in &Commodity::ALL {
+            assert!(a.buy_price(*c, 0) > a.sell_price(*c, 0));
+        }
+    }
+
+    #[test]
+    fn test_buy_and_sell_should_respect_capacity_and_credits() {
+        let market = Market { seed: 1 };
+        let mut cargo = Cargo::new(10, 1000);
+
+        let paid = buy(&mut cargo, &market, Commodity::Food, 8, 0).unwrap();
+        assert_eq!(1000 - paid, cargo.credits);
+        assert_eq!(8, cargo.get(Commodity::Food));
+
+        assert!(buy(&mut cargo, &market, Commodity::Ore, 3, 0).is_err());
+        assert!(sell(&mut cargo, &market, Commodity::Ore, 1, 0).is_err());
+
+        let received = sell(&mut cargo, &market, Commodity::Food, 8, 0).unwrap();
+        assert!(received < paid);
+        assert_eq!(0, cargo.total());
+
+        let mut poor = Cargo::new(10, 0);
+        assert_eq!(
+            Err("not enough credits".to_string()),
+            buy(&mut poor, &market, Commodity::Food, 1, 0)
+        );
+    }
+
+    #[test]
+    fn test_ship_should_dock_at_station_trade_and_undock() {
+        let (mut sim, scenery) = new_state_default_scenery();
+        let ship_id = scenery.ships["ship"];
+        let station_id = scenery.bodies["Station Alpha"];
+        let avatar_id = scenery.avatar_id;
+
+        // leave the planet orbit and fly to the station
+        sim.set_ship_command(
+            ship_id,
+            Command::FlyTo {
+                target_id: station_id,
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(2000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Orbit { target_id }) if *target_id == station_id
+            )
+        });
+        assert!(ticks.is_some(), "ship never arrive at station");
+
+        // can not trade before dock
+        assert!(sim.trade(ship_id, Commodity::Food, 1).is_err());
+
+        sim.set_ship_command(
+            ship_id,
+            Command::Dock {
+                target_id: station_id,
+            },
+        )
+        .unwrap();
+        let ticks = sim.run_until(1000, |ecs| {
+            matches!(
+                ecs.read_storage::<Location>().get(ship_id),
+                Some(Location::Docked { .. })
+            )
+        });
+        assert!(ticks.is_some(), "ship never dock");
+        sim.tick();
+        assert!(sim.events().contains(&Event::Docked {
+            ship_id,
+            station_id
+        }));
+        let avatar_pos = get_avatar_position(&sim);
+        assert_eq!(station_id, avatar_pos.grid_id);
+        assert_eq!(6, ship::get_ship_stats(sim.ecs(), ship_id).engines);
+
+        // the avatar can walk into the station through the doors
+        {
+            let grids = sim.ecs().read_storage::<GridRef>();
+            let area = GridRef::find_area(&grids, station_id).unwrap();
+            let inside = P2::new(34, 12);
+            assert_eq!(Some(station_id), area.get_layer_entity_at(&inside));
+            assert!(area
+                .find_path_through_doors(&avatar_pos.point, &inside)
+                .is_some());
+        }
+
+        // trade
+        let spent = sim.trade(ship_id, Commodity::Food, 5).unwrap();
+        assert!(spent < 0);
+        assert_eq!(500 + spent, get_credits(&sim, ship_id));
+        assert_eq!(
+            Err("cargo hold is full".to_string()),
+            sim.trade(ship_id, Commodity::Ore, 16)
+        );
+        let received = sim.trade(ship_id, Commodity::Food, -5).unwrap();
+        assert!(received > 0 && received < -spent);
+        assert_eq!(
+            Err("not enough food in cargo".to_string()),
+            sim.trade(ship_id, Commodity::Food, -1)
+        );
+
+        // undock leaving station objects behind
+        let item_id = loader::create_item(
+            sim.ecs_mut(),
+            Item {
+                kind: ItemKind::Scrap,
+            },
+            Position {
+                grid_id: station_id,
+                point: P2::new(34, 12),
+            },
+        );
+        sim.set_ship_command(ship_id, Command::Launch).unwrap();
+        sim.tick();
+        assert_orbiting(&sim, ship_id, station_id);
+        assert_eq!(ship_id, get_avatar_position(&sim).grid_id);
+        assert_eq!(
+            Position {
+                grid_id: station_id,
+                point: P2::new(34, 12)
+            },
+            get_position(&sim, item_id)
+        );
+        assert!(sim.ecs().read_storage::<Stats>().get(avatar_id).is_some());
+    }
+}
@@ -1,12 +1,18 @@
+use crate::commons::grid;
 use crate::models::{ObjectsType, Position};
 use specs::prelude::*;
+use specs::storage::MaskedStorage;
+use std::ops::Deref;
 
-pub fn find_objects_at<'a>(
+pub fn find_objects_at<'a, D>(
     entities: &Entities<'a>,
     objects: &ReadStorage<'a, ObjectsType>,
-    positions: &ReadStorage<'a, Position>,
+    positions: &Storage<'a, Position, D>,
     pos: &Position,
-) -> Vec<(Entity, ObjectsType)> {
+) -> Vec<(Entity, ObjectsType)>
+where
+    D: Deref<Target = MaskedStorage<Position>>,
+{
     let mut result = vec![];
     for (e, o, p) in (entities, objects, positions).join() {
         if p == pos {
@@ -15,3 +21,24 @@ pub fn find_objects_at<'a>(
     }
     result
 }
+
+/// find objects in the 4 cells around the position
+pub fn find_objects_around<'a, D>(
+    entities: &Entities<'a>,
+    objects: &ReadStorage<'a, ObjectsType>,
+    positions: &Storage<'a, Position, D>,
+    pos: &Position,
+) -> Vec<(Entity, ObjectsType)>
+where
+    D: Deref<Target = MaskedStorage<Position>>,
+{
+    let mut result = vec![];
+    for (_, point) in grid::get_4_neighbours(&pos.point) {
+        let neighbour = Position {
+            grid_id: pos.grid_id,
+            point,
+        };
+        result.extend(find_objects_at(entities, objects, positions, &neighbour));
+    }
+    result
+}
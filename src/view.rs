@@ -1,29 +1,37 @@
+pub mod build_window;
 pub mod camera;
 pub mod cockpit_window;
+pub mod game_over_window;
+pub mod inventory_window;
+pub mod market_window;
+pub mod message_log;
 pub mod window;
 
 use crate::actions::{Action, EntityActions};
-use crate::area::{Area, Tile};
+use crate::area::{Area, Cell, Tile};
+use crate::events::MessageLog;
+use crate::game_time::{GameTime, TimeMode};
 use crate::gridref::GridRef;
-use crate::models::{ObjectsType, Player, Position};
+use crate::models::{Door, DoorState, ObjectsType, Player, Position, Stats};
 use crate::state::State;
 use crate::utils::find_objects_at;
 use crate::view::camera::Camera;
 use crate::P2;
-use crate::{actions, cfg};
+use crate::{actions, cfg, save, squad};
 use rltk::{Rltk, VirtualKeyCode, RGB};
+use serde::{Deserialize, Serialize};
 use specs::prelude::*;
 use specs_derive::*;
 use std::collections::HashSet;
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Viewshed {
     pub visible_tiles: Vec<rltk::Point>,
     pub know_tiles: HashSet<rltk::Point>,
     pub range: i32,
 }
 
-#[derive(Component)]
+#[derive(Component, Clone, Serialize, Deserialize)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
     pub fg: RGB,
@@ -32,6 +40,32 @@ pub struct Renderable {
 }
 
 pub fn player_input(gs: &mut State, ctx: &mut Rltk) {
+    if ctx.left_click {
+        let target = {
+            let positions = gs.ecs.read_storage::<Position>();
+            let player = gs.ecs.fetch::<Player>();
+            positions.get(player.get_avatar_id()).map(|pos| {
+                let (x, y) = ctx.mouse_pos();
+                Camera::from_center(pos.point).screen_to_global(P2::new(x, y))
+            })
+        };
+        // shift click fire at the cell instead of walking
+        match target {
+            Some(target) if ctx.shift => {
+                actions::set_current_action(&mut gs.ecs, Action::Fire(target))
+            }
+            Some(target) => {
+                actions::set_auto_walk(&mut gs.ecs, target);
+            }
+            None => {}
+        }
+    }
+
+    // any key interrupt the auto walk
+    if ctx.key.is_some() {
+        actions::cancel_auto_walk(&mut gs.ecs);
+    }
+
     match ctx.key {
         None => {} // Nothing happened
         Some(key) => match key {
@@ -49,6 +83,46 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) {
             VirtualKeyCode::Numpad2 => actions::try_move_player(0, 1, &mut gs.ecs),
             VirtualKeyCode::Numpad3 => actions::try_move_player(1, 1, &mut gs.ecs),
             VirtualKeyCode::I => actions::set_current_action(&mut gs.ecs, Action::Interact),
+            VirtualKeyCode::O => actions::set_door_action(&mut gs.ecs, true),
+            VirtualKeyCode::C => actions::set_door_action(&mut gs.ecs, false),
+            VirtualKeyCode::A => actions::set_current_action(&mut gs.ecs, Action::CycleAirlock),
+            VirtualKeyCode::R => actions::set_current_action(&mut gs.ecs, Action::Repair),
+            VirtualKeyCode::Comma if ctx.shift => {
+                actions::set_current_action(&mut gs.ecs, Action::Ascend)
+            }
+            VirtualKeyCode::Period if ctx.shift => {
+                actions::set_current_action(&mut gs.ecs, Action::Descend)
+            }
+            VirtualKeyCode::Comma => actions::set_current_action(&mut gs.ecs, Action::Pickup),
+            VirtualKeyCode::B => gs.ecs.insert(window::Window::Inventory),
+            VirtualKeyCode::F => {
+                actions::fire_at_nearest(&mut gs.ecs);
+            }
+            VirtualKeyCode::Tab => {
+                squad::focus_next(&mut gs.ecs);
+            }
+            VirtualKeyCode::G => squad::toggle_group(&mut gs.ecs),
+            VirtualKeyCode::J => {
+                squad::join_squad(&mut gs.ecs);
+            }
+            VirtualKeyCode::L => {
+                squad::leave_squad(&mut gs.ecs);
+            }
+            VirtualKeyCode::T => gs.ecs.write_resource::<GameTime>().toggle_turn_based(),
+            VirtualKeyCode::P => gs.ecs.write_resource::<GameTime>().toggle_pause(),
+            VirtualKeyCode::PageUp => gs.ecs.write_resource::<MessageLog>().scroll_back(),
+            VirtualKeyCode::PageDown => gs.ecs.write_resource::<MessageLog>().scroll_forward(),
+            VirtualKeyCode::F5 => match save::save_game(&gs.ecs, save::SAVE_FILE) {
+                Ok(()) => log::info!("game saved into {}", save::SAVE_FILE),
+                Err(e) => log::warn!("fail to save game: {:?}", e),
+            },
+            VirtualKeyCode::F9 => {
+                let cfg = (*gs.ecs.fetch::<cfg::Cfg>()).clone();
+                match save::load_game(cfg, save::SAVE_FILE) {
+                    Ok(state) => *gs = state,
+                    Err(e) => log::warn!("fail to load game: {:?}", e),
+                }
+            }
             // VirtualKeyCode::W => gs.camera.y -= 1,
             // VirtualKeyCode::A => gs.camera.x -= 1,
             // VirtualKeyCode::D => gs.camera.x += 1,
@@ -86,22 +160,29 @@ pub fn draw_mouse(_state: &mut State, ctx: &mut Rltk) {
 }
 
 pub fn draw_map_and_objects(state: &mut State, ctx: &mut Rltk) {
-    // merge all visible and know tiles from player
     let viewshed = state.ecs.read_storage::<Viewshed>();
-    let avatars = state.ecs.fetch::<Player>();
+    let player = state.ecs.fetch::<Player>();
     let positions = state.ecs.read_storage::<Position>();
-    let views = (&viewshed, avatars.get_avatarset(), &positions)
+    let pos = positions.get(player.get_avatar_id()).unwrap();
+
+    // merge all visible and know tiles from avatars in the same area of the focused one
+    let mut visible_tiles = vec![];
+    let mut know_tiles = HashSet::new();
+    for (v, _, _) in (&viewshed, player.get_avatarset(), &positions)
         .join()
-        .collect::<Vec<_>>();
-    let (v, _, pos) = views.iter().next().unwrap();
+        .filter(|(_, _, p)| p.grid_id == pos.grid_id)
+    {
+        visible_tiles.extend(v.visible_tiles.iter().cloned());
+        know_tiles.extend(v.know_tiles.iter().cloned());
+    }
 
     let camera = Camera::from_center(pos.point);
 
     // draw
     let grids = &state.ecs.read_storage::<GridRef>();
     let map = GridRef::find_area(grids, pos.grid_id).unwrap();
-    draw_map(&camera, &v.visible_tiles, &v.know_tiles, map, ctx);
-    draw_objects(&camera, &v.visible_tiles, &state.ecs, ctx);
+    draw_map(&camera, &visible_tiles, &know_tiles, map, ctx);
+    draw_objects(&camera, &visible_tiles, &state.ecs, ctx);
 }
 
 impl Into<rltk::Point> for P2 {
@@ -125,13 +206,7 @@ fn draw_map(
         let tile = cell.unwrap_or_default().tile;
 
         // calculate real tile
-        let (mut fg, mut bg, mut ch) = match tile {
-            Tile::Ground => (rltk::LIGHT_GRAY, rltk::BLACK, '.'),
-            Tile::Floor => (rltk::LIGHT_GREEN, rltk::BLACK, '.'),
-            Tile::Wall => (rltk::GREEN, rltk::BLACK, '#'),
-            Tile::Space => (rltk::BLACK, rltk::BLACK, ' '),
-            Tile::OutOfMap => (rltk::BLACK, rltk::GRAY, ' '),
-        };
+        let (mut fg, mut bg, mut ch) = tile_glyph(tile);
 
         // replace non visible tiles
         if visible_cells
@@ -160,13 +235,42 @@ fn draw_map(
     }
 }
 
+/// foreground, background and char of a tile
+pub fn tile_glyph(tile: Tile) -> ((u8, u8, u8), (u8, u8, u8), char) {
+    match tile {
+        Tile::Ground => (rltk::LIGHT_GRAY, rltk::BLACK, '.'),
+        Tile::Floor => (rltk::LIGHT_GREEN, rltk::BLACK, '.'),
+        Tile::Wall => (rltk::GREEN, rltk::BLACK, '#'),
+        Tile::Rock => (rltk::SANDY_BROWN, rltk::BLACK, '^'),
+        Tile::Water => (rltk::BLUE, rltk::BLACK, '~'),
+        Tile::Vegetation => (rltk::DARK_GREEN, rltk::BLACK, '"'),
+        Tile::Rubble => (rltk::GRAY, rltk::BLACK, ','),
+        Tile::Space => (rltk::BLACK, rltk::BLACK, ' '),
+        Tile::Door { open: true } => (rltk::CYAN, rltk::BLACK, '\''),
+        Tile::Door { open: false } => (rltk::CYAN, rltk::BLACK, '+'),
+        Tile::OutOfMap => (rltk::BLACK, rltk::GRAY, ' '),
+    }
+}
+
+/// doors glyph depends on its state
+fn door_glyph(render: &Renderable, door: &Door) -> (RGB, rltk::FontCharType) {
+    match door.state {
+        DoorState::Open => (render.fg, rltk::to_cp437('\'')),
+        DoorState::Closed => (render.fg, render.glyph),
+        DoorState::Locked => (RGB::named(rltk::RED), render.glyph),
+    }
+}
+
 fn draw_objects(camera: &Camera, visible_cells: &Vec<rltk::Point>, ecs: &World, ctx: &mut Rltk) {
     let positions = ecs.read_storage::<Position>();
     let renderables = ecs.read_storage::<Renderable>();
-    let mut objects = (&positions, &renderables).join().collect::<Vec<_>>();
+    let doors = ecs.read_storage::<Door>();
+    let mut objects = (&positions, &renderables, doors.maybe())
+        .join()
+        .collect::<Vec<_>>();
     objects.sort_by(|&a, &b| a.1.priority.cmp(&b.1.priority));
 
-    for (pos, render) in objects {
+    for (pos, render, door) in objects {
         let point = &pos.point;
         let screen_point = camera.global_to_screen(*point);
 
@@ -176,13 +280,11 @@ fn draw_objects(camera: &Camera, visible_cells: &Vec<rltk::Point>, ecs: &World,
                 .find(|p| p.x == point.x && p.y == point.y)
                 .is_some()
             {
-                ctx.set(
-                    screen_point.x,
-                    screen_point.y,
-                    render.fg,
-                    render.bg,
-                    render.glyph,
-                );
+                let (fg, glyph) = match door {
+                    Some(door) => door_glyph(render, door),
+                    None => (render.fg, render.glyph),
+                };
+                ctx.set(screen_point.x, screen_point.y, fg, render.bg, glyph);
             }
         }
     }
@@ -194,9 +296,13 @@ pub fn draw_gui(state: &State, ctx: &mut Rltk) {
     let positions = &state.ecs.read_storage::<Position>();
     let actions_st = &state.ecs.read_storage::<EntityActions>();
     let grids = &state.ecs.read_storage::<GridRef>();
+    let doors = &state.ecs.read_storage::<Door>();
+    let stats = &state.ecs.read_storage::<Stats>();
+    let time = state.ecs.fetch::<GameTime>();
     let player = state.ecs.fetch::<Player>();
 
-    for (_avatar_id, position, actions) in (player.get_avatarset(), positions, actions_st).join() {
+    let avatar_id = player.get_avatar_id();
+    if let (Some(position), Some(actions)) = (positions.get(avatar_id), actions_st.get(avatar_id)) {
         let gmap = GridRef::find_area(grids, position.grid_id).unwrap();
 
         let tile = gmap.get_grid().get_at(&position.point).unwrap_or_default();
@@ -204,14 +310,42 @@ pub fn draw_gui(state: &State, ctx: &mut Rltk) {
 
         draw_gui_bottom_box(
             ctx,
-            tile.tile,
+            tile,
             &objects_at,
+            doors,
+            stats.get(avatar_id),
+            &time,
             &map_actions_to_keys(&actions.actions)
                 .iter()
                 .map(ViewAction::to_tuple)
                 .collect::<Vec<_>>(),
         );
     }
+
+    if player.get_avatars().len() > 1 {
+        let index = player
+            .get_avatars()
+            .iter()
+            .position(|e| *e == avatar_id)
+            .unwrap_or(0);
+        let squad_str = format!(
+            "squad {}/{}{}",
+            index + 1,
+            player.get_avatars().len(),
+            if player.is_group() { " group" } else { "" }
+        );
+        ctx.print_color(
+            cfg::SCREEN_W - 21,
+            cfg::SCREEN_H - 4,
+            rltk::GRAY,
+            rltk::BLACK,
+            squad_str,
+        );
+    }
+
+    // message log between actions and stats columns of the bottom box
+    let log_h = 5;
+    message_log::draw(&state.ecs, ctx, 24, cfg::SCREEN_H - log_h - 2, 34, log_h);
 }
 
 struct ViewAction {
@@ -227,6 +361,13 @@ impl ViewAction {
     fn map_to_keys(action: &Action) -> (char, &'static str) {
         match action {
             Action::Interact => ('i', "check cockpit"),
+            Action::Open(_) => ('o', "open door"),
+            Action::Close(_) => ('c', "close door"),
+            Action::CycleAirlock => ('a', "cycle airlock"),
+            Action::Pickup => (',', "pick up"),
+            Action::Repair => ('r', "repair"),
+            Action::Descend => ('>', "descend stairs"),
+            Action::Ascend => ('<', "ascend stairs"),
             _ => ('?', "unknown"),
         }
     }
@@ -245,8 +386,11 @@ fn map_actions_to_keys(actions: &Vec<Action>) -> Vec<ViewAction> {
 
 fn draw_gui_bottom_box(
     ctx: &mut Rltk,
-    current_tile: Tile,
+    current_cell: &Cell,
     objects: &Vec<(Entity, ObjectsType)>,
+    doors: &ReadStorage<Door>,
+    stats: Option<&Stats>,
+    time: &GameTime,
     actions: &Vec<(char, &str)>,
 ) {
     let box_h = 6;
@@ -264,21 +408,89 @@ fn draw_gui_bottom_box(
 
     let inner_box_x = box_x + 1;
     let inner_box_y = box_y + 1;
-    let tile_str = match current_tile {
+    let tile_str = match current_cell.tile {
         Tile::Ground => "ground",
         Tile::Floor => "floor",
         Tile::Wall => "?",
+        Tile::Rock => "rock",
+        Tile::Water => "water",
+        Tile::Vegetation => "vegetation",
+        Tile::Rubble => "rubble",
         Tile::Space => "space",
+        Tile::Door { .. } => "doorway",
         Tile::OutOfMap => "oom",
     };
-    ctx.print_color(inner_box_x, inner_box_y, rltk::GRAY, rltk::BLACK, tile_str);
+    let air_str = if current_cell.air.is_vacuum() {
+        "vacuum".to_string()
+    } else {
+        format!(
+            "{:.0}% {:?}",
+            current_cell.air.pressure * 100.0,
+            current_cell.air.gas
+        )
+    };
+    ctx.print_color(
+        inner_box_x,
+        inner_box_y,
+        rltk::GRAY,
+        rltk::BLACK,
+        format!("{} - {}", tile_str, air_str),
+    );
+
+    if let Some(stats) = stats {
+        let x = box_x + box_w - 20;
+        let low = |value: i32, max: i32| {
+            if value * 4 < max {
+                rltk::RED
+            } else {
+                rltk::GRAY
+            }
+        };
+        ctx.print_color(
+            x,
+            inner_box_y,
+            low(stats.health, stats.max_health),
+            rltk::BLACK,
+            format!("health {}/{}", stats.health, stats.max_health),
+        );
+        ctx.print_color(
+            x,
+            inner_box_y + 1,
+            low(stats.oxygen, stats.max_oxygen),
+            rltk::BLACK,
+            format!("oxygen {}/{}", stats.oxygen, stats.max_oxygen),
+        );
+    }
+
+    {
+        let mode_str = match time.mode {
+            TimeMode::RealTime => "real time",
+            TimeMode::Paused => "paused",
+            TimeMode::TurnBased => "turn based",
+        };
+        ctx.print_color(
+            box_x + box_w - 20,
+            inner_box_y + 3,
+            rltk::GRAY,
+            rltk::BLACK,
+            format!("tick {}", time.tick),
+        );
+        ctx.print_color(
+            box_x + box_w - 20,
+            inner_box_y + 4,
+            rltk::GRAY,
+            rltk::BLACK,
+            mode_str,
+        );
+    }
 
     let mut j = inner_box_y + 1;
-    for (_, k) in objects {
-        let obj_str = match k {
-            ObjectsType::Door { .. } => "door",
-            ObjectsType::Cockpit => "cockpit",
-            _ => continue,
+    for (id, k) in objects {
+        let obj_str = match (k, doors.get(*id).map(|door| door.state)) {
+            (ObjectsType::Door { .. }, Some(DoorState::Open)) => "door (open)",
+            (ObjectsType::Door { .. }, Some(DoorState::Locked)) => "door (locked)",
+            (ObjectsType::Stairs { .. }, _) => continue,
+            (kind, _) => kind.name(),
         };
 
         ctx.print_color(inner_box_x, j, rltk::GRAY, rltk::BLACK, obj_str);
@@ -0,0 +1,247 @@
+use crate::cfg::{self, Cfg, MapParserCfg};
+use crate::events::{EntitiesEvents, Event};
+use crate::gridref::GridRef;
+use crate::models::{Label, Player, Position, P2};
+use crate::ship::build::{self, ShipPart};
+use crate::state::State;
+use crate::trade::{Cargo, Commodity};
+use crate::view::cockpit_window::get_key_index;
+use crate::view::window::Window;
+use crate::view::{self, message_log, Renderable};
+use rltk::{Rltk, VirtualKeyCode, RGB};
+use specs::prelude::*;
+
+/// ship being changed, the cell under the cursor and the selected part
+#[derive(Debug, Default)]
+pub struct BuildWindowState {
+    pub ship_id: Option<Entity>,
+    pub cursor: P2,
+    pub selected: usize,
+}
+
+impl BuildWindowState {
+    pub fn new(ship_id: Entity, cursor: P2) -> Self {
+        BuildWindowState {
+            ship_id: Some(ship_id),
+            cursor,
+            selected: 0,
+        }
+    }
+}
+
+pub fn draw(state: &mut State, ctx: &mut Rltk) {
+    let (ship_id, cursor, selected) = {
+        let window_state = state.ecs.fetch::<BuildWindowState>();
+        (
+            window_state.ship_id,
+            window_state.cursor,
+            window_state.selected,
+        )
+    };
+
+    let ship_id = match ship_id {
+        Some(id) => id,
+        None => {
+            state.ecs.insert(Window::Cockpit);
+            return;
+        }
+    };
+
+    // frame
+    let border = 4;
+    ctx.draw_box(
+        border,
+        border,
+        cfg::SCREEN_W - border * 2,
+        cfg::SCREEN_H - border * 2,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+
+    let x = border + 2;
+    let mut y = border + 2;
+
+    let size = {
+        let labels = state.ecs.read_storage::<Label>();
+        let ship_name = labels
+            .get(ship_id)
+            .map(|l| l.name.as_str())
+            .unwrap_or("ship");
+        ctx.print_color(
+            x,
+            y,
+            rltk::GRAY,
+            rltk::BLACK,
+            format!("Building {}", ship_name),
+        );
+        y += 2;
+
+        let grids = state.ecs.read_storage::<GridRef>();
+        let area = match GridRef::find_area(&grids, ship_id) {
+            Some(area) => area,
+            None => return,
+        };
+
+        // ship grid and its objects
+        let size = area.get_grid().get_size();
+        for cy in 0..size.y {
+            for cx in 0..size.x {
+                let tile = area
+                    .get_grid()
+                    .get_at(&P2::new(cx, cy))
+                    .copied()
+                    .unwrap_or_default()
+                    .tile;
+                let (fg, bg, ch) = view::tile_glyph(tile);
+                ctx.set(x + cx, y + cy, fg, bg, rltk::to_cp437(ch));
+            }
+        }
+
+        let positions = state.ecs.read_storage::<Position>();
+        let renderables = state.ecs.read_storage::<Renderable>();
+        let mut objects = (&positions, &renderables)
+            .join()
+            .filter(|(pos, _)| pos.grid_id == ship_id)
+            .collect::<Vec<_>>();
+        objects.sort_by_key(|(_, render)| render.priority);
+        for (pos, render) in objects {
+            ctx.set(
+                x + pos.point.x,
+                y + pos.point.y,
+                render.fg,
+                render.bg,
+                render.glyph,
+            );
+        }
+        ctx.set_bg(x + cursor.x, y + cursor.y, RGB::named(rltk::MAGENTA));
+
+        size
+    };
+
+    // parts and materials
+    let px = x + size.x + 2;
+    let mut py = y;
+    for (i, part) in ShipPart::ALL.iter().enumerate() {
+        let color = if i == selected {
+            rltk::YELLOW
+        } else {
+            rltk::GRAY
+        };
+        let cost = part
+            .cost()
+            .iter()
+            .map(|(commodity, amount)| format!("{} {}", amount, commodity.name()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        ctx.print_color(
+            px,
+            py,
+            color,
+            rltk::BLACK,
+            format!("{}) {:<14}{}", i, part.name(), cost),
+        );
+        py += 1;
+    }
+    py += 1;
+
+    {
+        let cargos = state.ecs.read_storage::<Cargo>();
+        let cargo = cargos.get(ship_id).cloned().unwrap_or_default();
+        for commodity in &[Commodity::Ore, Commodity::Electronics] {
+            ctx.print_color(
+                px,
+                py,
+                rltk::GRAY,
+                rltk::BLACK,
+                format!("{:<12}{:>4}", commodity.name(), cargo.get(*commodity)),
+            );
+            py += 1;
+        }
+    }
+    py += 1;
+
+    let (hull_color, hull_str) = if build::is_sealed(&state.ecs, ship_id) {
+        (rltk::GREEN, "hull sealed")
+    } else {
+        (rltk::RED, "hull breached")
+    };
+    ctx.print_color(px, py, hull_color, rltk::BLACK, hull_str);
+    py += 2;
+
+    for help in &[
+        "arrows - move".to_string(),
+        format!("0-{} - select part", ShipPart::ALL.len() - 1),
+        "space - place".to_string(),
+        "delete - remove".to_string(),
+        "x - export".to_string(),
+        "esc - back".to_string(),
+    ] {
+        ctx.print_color(px, py, rltk::GRAY, rltk::BLACK, help);
+        py += 1;
+    }
+
+    // draw messages
+    let h = 5;
+    message_log::draw(
+        &state.ecs,
+        ctx,
+        x,
+        cfg::SCREEN_H - border - h - 1,
+        cfg::SCREEN_W - x * 2,
+        h,
+    );
+
+    // process inputs
+    let move_cursor = |state: &mut State, dx: i32, dy: i32| {
+        let mut window_state = state.ecs.write_resource::<BuildWindowState>();
+        let next = window_state.cursor.translate(dx, dy);
+        window_state.cursor = P2::new(next.x.clamp(0, size.x - 1), next.y.clamp(0, size.y - 1));
+        Ok(())
+    };
+
+    let result = match (ctx.key, get_key_index(ctx.key)) {
+        (_, Some(index)) if index < ShipPart::ALL.len() => {
+            state.ecs.write_resource::<BuildWindowState>().selected = index;
+            Ok(())
+        }
+        (Some(VirtualKeyCode::Up), _) => move_cursor(state, 0, -1),
+        (Some(VirtualKeyCode::Down), _) => move_cursor(state, 0, 1),
+        (Some(VirtualKeyCode::Left), _) => move_cursor(state, -1, 0),
+        (Some(VirtualKeyCode::Right), _) => move_cursor(state, 1, 0),
+        (Some(VirtualKeyCode::Space), _) | (Some(VirtualKeyCode::Return), _) => {
+            build::place(&mut state.ecs, ship_id, cursor, ShipPart::ALL[selected])
+        }
+        (Some(VirtualKeyCode::Delete), _) | (Some(VirtualKeyCode::Back), _) => {
+            build::remove(&mut state.ecs, ship_id, cursor)
+        }
+        (Some(VirtualKeyCode::X), _) => {
+            let legend = state.ecs.fetch::<Cfg>().map_parser.clone();
+            export_ship(&state.ecs, &legend, ship_id)
+        }
+        (Some(VirtualKeyCode::Escape), _) => {
+            state.ecs.insert(Window::Cockpit);
+            Ok(())
+        }
+        _ => Ok(()),
+    };
+
+    if let Err(reason) = result {
+        let actor_id = state.ecs.fetch::<Player>().get_avatar_id();
+        state
+            .ecs
+            .write_resource::<EntitiesEvents>()
+            .push(Event::ActionFailed { actor_id, reason });
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn export_ship(ecs: &World, legend: &MapParserCfg, ship_id: Entity) -> Result<(), String> {
+    build::export_file(ecs, legend, ship_id, build::EXPORT_FILE)
+        .map(|_| log::info!("ship exported into {}", build::EXPORT_FILE))
+}
+
+/// without a file system the exported map goes to the browser console
+#[cfg(target_arch = "wasm32")]
+fn export_ship(ecs: &World, legend: &MapParserCfg, ship_id: Entity) -> Result<(), String> {
+    build::export_json(ecs, legend, ship_id).map(|json| log::info!("ship exported:\n{}", json))
+}
@@ -1,9 +1,18 @@
+use crate::events::{EntitiesEvents, Event};
 use crate::gridref::GridRef;
 use crate::models::SurfaceTileKind;
+use crate::ship::encounter::{self, Encounter, EncounterAction};
+use crate::ship::{ShipAi, ShipAiKind};
 use crate::state::State;
+use crate::view::build_window::BuildWindowState;
+use crate::view::market_window::MarketWindowState;
+use crate::view::message_log;
 use crate::view::window::Window;
-use crate::{cfg, ship, Dir, Label, Location, Player, Position, Sector, Ship, Surface, P2};
-use log::{info, warn};
+use crate::{
+    cfg, ship, trade, Dir, Galaxy, Label, Location, Player, Position, Sector, SectorBody, Ship,
+    Surface, P2,
+};
+use log::warn;
 use rltk::{BTerm, Rltk, VirtualKeyCode, RGB};
 use specs::prelude::*;
 use specs_derive::*;
@@ -63,20 +72,23 @@ enum MenuOption {
     Land,
     FlyTo { target_id: Entity },
     Launch,
+    Dock { target_id: Entity },
+    Jump { target_id: Entity },
+    Trade,
+    Refuel,
+    Harvest,
+    Build,
+    Encounter { action: EncounterAction },
 }
 
 #[derive(Component, Debug)]
 pub struct CockpitWindowState {
     pub sub_window: SubWindow,
-    pub last_msg: Option<String>,
 }
 
 impl CockpitWindowState {
     pub fn new(sub_window: SubWindow) -> Self {
-        CockpitWindowState {
-            sub_window,
-            last_msg: None,
-        }
+        CockpitWindowState { sub_window }
     }
 }
 
@@ -84,7 +96,6 @@ impl Default for CockpitWindowState {
     fn default() -> Self {
         CockpitWindowState {
             sub_window: SubWindow::Main,
-            last_msg: None,
         }
     }
 }
@@ -138,7 +149,7 @@ fn draw_main(state: &mut State, ctx: &mut Rltk, info: LocalInfo) {
             y = draw_orbiting_map(state, ctx, *ship_id, x, y, None);
             // actions
             commands = list_commands(&state.ecs, *ship_id);
-            y = draw_actions(state, ctx, x, y, &commands);
+            y = draw_actions(state, ctx, *ship_id, x, y, &commands);
         }
         _ => {}
     }
@@ -159,47 +170,96 @@ fn draw_main(state: &mut State, ctx: &mut Rltk, info: LocalInfo) {
 
     match executed {
         Err(msg) => {
-            let mut window_state = state.ecs.fetch_mut::<CockpitWindowState>();
-            window_state.last_msg = Some(msg);
+            state
+                .ecs
+                .write_resource::<EntitiesEvents>()
+                .push(Event::ActionFailed {
+                    actor_id: info.avatar_id,
+                    reason: msg,
+                });
         }
         _ => {}
     }
 }
 
 fn draw_msg(state: &State, ctx: &mut BTerm, border: i32, x: i32, y: i32) -> i32 {
-    let window_state = state.ecs.fetch::<CockpitWindowState>();
-    if let Some(msg) = &window_state.last_msg {
-        ctx.print_color(x, cfg::SCREEN_H - border - 1, rltk::GRAY, rltk::RED, msg);
-    }
+    let h = 5;
+    message_log::draw(
+        &state.ecs,
+        ctx,
+        x,
+        cfg::SCREEN_H - border - h - 1,
+        cfg::SCREEN_W - x * 2,
+        h,
+    );
     y
 }
 
 fn draw_actions(
     state: &mut State,
     ctx: &mut BTerm,
+    ship_id: Entity,
     x: i32,
     mut y: i32,
     commands: &Vec<MenuOption>,
 ) -> i32 {
     let labels = state.ecs.read_storage::<Label>();
+    let bodies = state.ecs.read_storage::<SectorBody>();
+    let encounters = state.ecs.read_storage::<Encounter>();
+    let fuel = state
+        .ecs
+        .read_storage::<Ship>()
+        .get(ship_id)
+        .map(|ship| ship.fuel)
+        .unwrap_or(0);
     for (i, command) in commands.iter().enumerate() {
+        let mut color = rltk::GRAY;
         let command_str = match command {
             MenuOption::Land => "land".to_string(),
             MenuOption::FlyTo { target_id } => {
                 let label = labels.get(*target_id);
                 let name = label.map(|i| i.name.as_str()).unwrap_or("unknown");
-                format!("fly to {}", name)
+                match ship::fly_distance(&state.ecs, ship_id, *target_id) {
+                    Some(distance) if distance <= ship::fuel_range(fuel) => {
+                        format!("fly to {} ({} cells)", name, distance)
+                    }
+                    Some(distance) => {
+                        color = rltk::RED;
+                        format!("fly to {} ({} cells, out of range)", name, distance)
+                    }
+                    None => format!("fly to {}", name),
+                }
             }
             MenuOption::Launch => "launch".to_string(),
+            MenuOption::Dock { .. } => "dock".to_string(),
+            MenuOption::Jump { target_id } => match bodies.get(*target_id) {
+                Some(SectorBody::Jump { target, .. }) => {
+                    let label = labels.get(*target);
+                    let name = label.map(|i| i.name.as_str()).unwrap_or("unknown");
+                    format!("jump to {}", name)
+                }
+                _ => "jump".to_string(),
+            },
+            MenuOption::Trade => "trade".to_string(),
+            MenuOption::Refuel => "refuel".to_string(),
+            MenuOption::Harvest => "harvest fuel".to_string(),
+            MenuOption::Build => "build".to_string(),
+            MenuOption::Encounter { action } => {
+                let other_name = encounters
+                    .get(ship_id)
+                    .and_then(|encounter| labels.get(encounter.other_id))
+                    .map(|label| label.name.as_str())
+                    .unwrap_or("unknown");
+                match action {
+                    EncounterAction::Hail => format!("hail {}", other_name),
+                    EncounterAction::Flee => format!("flee from {}", other_name),
+                    EncounterAction::Fight => format!("fight {}", other_name),
+                    EncounterAction::Board => format!("board {}", other_name),
+                }
+            }
         };
 
-        ctx.print_color(
-            x,
-            y,
-            rltk::GRAY,
-            rltk::BLACK,
-            format!("{}) {}", i, command_str),
-        );
+        ctx.print_color(x, y, color, rltk::BLACK, format!("{}) {}", i, command_str));
         y += 1;
     }
 
@@ -238,12 +298,104 @@ fn draw_status(state: &mut State, ctx: &mut Rltk, ship_id: Entity, x: i32, mut y
             ctx.print_color(x, y, rltk::GRAY, rltk::BLACK, "Ship landed");
             y += 1;
         }
+        (Some(Location::Docked { .. }), _) => {
+            ctx.print_color(x, y, rltk::GRAY, rltk::BLACK, "Ship docked");
+            y += 1;
+        }
         _ => {
             ctx.print_color(x, y, rltk::GRAY, rltk::BLACK, "Ship is the unknown");
             y += 1;
         }
     }
 
+    if let Some(encounter) = state.ecs.read_storage::<Encounter>().get(ship_id) {
+        let labels = state.ecs.read_storage::<Label>();
+        let name = labels
+            .get(encounter.other_id)
+            .map(|label| label.name.as_str())
+            .unwrap_or("unknown");
+        let disabled = ship_storage
+            .get(encounter.other_id)
+            .map(|other| other.hull <= 0)
+            .unwrap_or(false);
+        let (color, status) = match (encounter.hostile, disabled) {
+            (_, true) => (rltk::ORANGE, " (disabled)"),
+            (true, _) => (rltk::RED, " (hostile)"),
+            _ => (rltk::YELLOW, ""),
+        };
+        ctx.print_color(
+            x,
+            y,
+            color,
+            rltk::BLACK,
+            format!("Encounter with {}{}", name, status),
+        );
+        y += 1;
+    }
+
+    let stats = ship::get_ship_stats(&state.ecs, ship_id);
+    let fuel = ship.map(|ship| ship.fuel).unwrap_or(0);
+    ctx.print_color(
+        x,
+        y,
+        rltk::GRAY,
+        rltk::BLACK,
+        format!(
+            "fuel {}/{}  range {}  sensors {}  crew {}",
+            fuel,
+            stats.fuel_capacity(),
+            ship::fuel_range(fuel),
+            stats.sensor_range(),
+            stats.crew_capacity()
+        ),
+    );
+    y += 1;
+
+    let hull = ship.map(|ship| ship.hull).unwrap_or(0);
+    let hull_color = if hull < ship::MAX_HULL {
+        rltk::RED
+    } else {
+        rltk::GRAY
+    };
+    let engine_str = if stats.engine_damage > 0 {
+        format!("  engines {}% damaged", stats.engine_damage)
+    } else {
+        String::new()
+    };
+    ctx.print_color(
+        x,
+        y,
+        hull_color,
+        rltk::BLACK,
+        format!("hull {}/{}{}", hull, ship::MAX_HULL, engine_str),
+    );
+    y += 1;
+
+    let budget = stats.power_budget();
+    let power_color = if stats.power_demand() > stats.power() {
+        rltk::RED
+    } else {
+        rltk::GRAY
+    };
+    ctx.print_color(
+        x,
+        y,
+        power_color,
+        rltk::BLACK,
+        format!(
+            "power {}/{}  engines {}/{}  life support {}/{}  sensors {}/{}",
+            stats.power_demand(),
+            stats.power(),
+            budget.engines,
+            stats.engines,
+            budget.life_support,
+            stats.life_support,
+            budget.sensors,
+            stats.sensors
+        ),
+    );
+    y += 1;
+
     y
 }
 
@@ -254,11 +406,14 @@ fn try_do_command(
     command: Option<&MenuOption>,
 ) -> Result<(), String> {
     match command {
-        Some(MenuOption::Land) => state.ecs.insert(CockpitWindowState::new(SubWindow::Land {
-            selected: P2::new(0, 0),
-        })),
+        Some(MenuOption::Land) => {
+            state.ecs.insert(CockpitWindowState::new(SubWindow::Land {
+                selected: P2::new(0, 0),
+            }));
+            Ok(())
+        }
 
-        Some(MenuOption::FlyTo { target_id }) => set_ship_command(
+        Some(MenuOption::FlyTo { target_id }) => ship::set_ship_command(
             &mut state.ecs,
             ship_id,
             ship::Command::FlyTo {
@@ -267,14 +422,60 @@ fn try_do_command(
         ),
 
         Some(MenuOption::Launch) => {
-            set_ship_command(&mut state.ecs, ship_id, ship::Command::Launch)
+            ship::set_ship_command(&mut state.ecs, ship_id, ship::Command::Launch)
+        }
+
+        Some(MenuOption::Dock { target_id }) => ship::set_ship_command(
+            &mut state.ecs,
+            ship_id,
+            ship::Command::Dock {
+                target_id: *target_id,
+            },
+        ),
+
+        Some(MenuOption::Jump { target_id }) => ship::set_ship_command(
+            &mut state.ecs,
+            ship_id,
+            ship::Command::Jump {
+                target_id: *target_id,
+            },
+        ),
+
+        Some(MenuOption::Trade) => {
+            state.ecs.insert(MarketWindowState::new(ship_id));
+            state.ecs.insert(Window::Market);
+            Ok(())
+        }
+
+        Some(MenuOption::Refuel) => trade::refuel(&mut state.ecs, ship_id).map(|_| ()),
+
+        Some(MenuOption::Harvest) => {
+            ship::set_ship_command(&mut state.ecs, ship_id, ship::Command::Harvest)
+        }
+
+        Some(MenuOption::Encounter { action }) => {
+            encounter::set_encounter_action(&mut state.ecs, ship_id, *action)
+        }
+
+        Some(MenuOption::Build) => {
+            // start from the avatar cell when it is inside the ship
+            let cursor = state
+                .ecs
+                .read_storage::<Position>()
+                .get(state.ecs.fetch::<Player>().get_avatar_id())
+                .filter(|pos| pos.grid_id == ship_id)
+                .map(|pos| pos.point)
+                .unwrap_or_default();
+            state.ecs.insert(BuildWindowState::new(ship_id, cursor));
+            state.ecs.insert(Window::Build);
+            Ok(())
         }
 
         _ => {
             log::warn!("unknown command {:?}", command);
+            Ok(())
         }
     }
-    Ok(())
 }
 
 /// return ne y value
@@ -283,6 +484,8 @@ fn draw_sector_map(state: &mut State, ctx: &mut Rltk, x: i32, y: i32, ship_id: E
     let sectors = state.ecs.read_storage::<Sector>();
     let locations = state.ecs.read_storage::<Location>();
     let labels = state.ecs.read_storage::<Label>();
+    let bodies = state.ecs.read_storage::<SectorBody>();
+    let ais = state.ecs.read_storage::<ShipAi>();
 
     // get ship location
     let (_ship_pos, ship_sector_id) = match locations.get(ship_id) {
@@ -316,11 +519,15 @@ fn draw_sector_map(state: &mut State, ctx: &mut Rltk, x: i32, y: i32, ship_id: E
     });
 
     for (e, _loc, _lab) in (&entities, &locations, &labels).join() {
-        let (pos, _) = match crate::locations::resolve_sector_pos(&locations, e) {
+        let (pos, sector_id) = match crate::locations::resolve_sector_pos(&locations, e) {
             Some(value) => value,
             _ => continue,
         };
 
+        if sector_id != ship_sector_id {
+            continue;
+        }
+
         let index_x = pos.x + cfg::SECTOR_SIZE / 2;
         let index_y = pos.y + cfg::SECTOR_SIZE / 2;
 
@@ -335,18 +542,96 @@ fn draw_sector_map(state: &mut State, ctx: &mut Rltk, x: i32, y: i32, ship_id: E
             continue;
         }
 
+        let mut ch = '*';
         if e == ship_id {
             fg = rltk::BLUE;
+            ch = '@';
+        } else if let Some(SectorBody::Ship) = bodies.get(e) {
+            fg = match ais.get(e).map(|ai| ai.kind) {
+                Some(ShipAiKind::Pirate) => rltk::RED,
+                _ => rltk::YELLOW,
+            };
+            ch = 's';
+        } else if let Some(SectorBody::Jump { .. }) = bodies.get(e) {
+            fg = rltk::MAGENTA;
         } else {
             fg = rltk::GREEN;
         }
 
-        ctx.set(x + index_x, y + index_y, fg, bg, '*' as rltk::FontCharType);
+        ctx.set(x + index_x, y + index_y, fg, bg, ch as rltk::FontCharType);
     }
 
+    draw_galaxy_map(state, ctx, x + cfg::SECTOR_SIZE + 2, y, ship_sector_id);
+
     y + 1 + cfg::SECTOR_SIZE
 }
 
+/// all sectors at its galaxy positions, followed by its names
+fn draw_galaxy_map(state: &State, ctx: &mut Rltk, x: i32, y: i32, current_sector_id: Entity) {
+    let galaxies = state.ecs.read_storage::<Galaxy>();
+    let sectors = state.ecs.read_storage::<Sector>();
+    let labels = state.ecs.read_storage::<Label>();
+
+    let galaxy = match galaxies.join().next() {
+        Some(galaxy) => galaxy,
+        None => return,
+    };
+
+    for ix in 0..cfg::GALAXY_SIZE {
+        for iy in 0..cfg::GALAXY_SIZE {
+            ctx.set(
+                x + ix,
+                y + iy,
+                rltk::GRAY,
+                rltk::GRAY,
+                ' ' as rltk::FontCharType,
+            );
+        }
+    }
+
+    let mut names_y = y;
+    for (i, sector_id) in galaxy.sectors.iter().enumerate() {
+        let sector = match sectors.get(*sector_id) {
+            Some(sector) => sector,
+            None => continue,
+        };
+
+        let fg = if *sector_id == current_sector_id {
+            rltk::BLUE
+        } else {
+            rltk::GREEN
+        };
+        let ch = std::char::from_digit(i as u32 % 10, 10).unwrap_or('?');
+
+        if sector.pos.x >= 0
+            && sector.pos.y >= 0
+            && sector.pos.x < cfg::GALAXY_SIZE
+            && sector.pos.y < cfg::GALAXY_SIZE
+        {
+            ctx.set(
+                x + sector.pos.x,
+                y + sector.pos.y,
+                fg,
+                rltk::GRAY,
+                ch as rltk::FontCharType,
+            );
+        }
+
+        let name = labels
+            .get(*sector_id)
+            .map(|l| l.name.as_str())
+            .unwrap_or("unknown");
+        ctx.print_color(
+            x + cfg::GALAXY_SIZE + 2,
+            names_y,
+            fg,
+            rltk::BLACK,
+            format!("{} {}", ch, name),
+        );
+        names_y += 1;
+    }
+}
+
 fn draw_orbiting_map(
     state: &mut State,
     ctx: &mut Rltk,
@@ -382,6 +667,10 @@ fn draw_orbiting_map(
 
             let ch = match surface.get_tile(sx, sy) {
                 Some(SurfaceTileKind::Structure) => '$',
+                Some(SurfaceTileKind::Mountains) => '^',
+                Some(SurfaceTileKind::Water) => '~',
+                Some(SurfaceTileKind::Forest) => '"',
+                Some(SurfaceTileKind::Ruins) => '%',
                 _ => '#',
             };
 
@@ -459,14 +748,16 @@ fn draw_land_menu(state: &mut State, ctx: &mut Rltk, ship_id: Entity, orbiting_i
             let target_id = surface.zones[selected_index as usize];
 
             drop(surfaces_storage);
-            set_ship_command(
+            if let Err(reason) = ship::set_ship_command(
                 &mut state.ecs,
                 ship_id,
                 ship::Command::Land {
                     target_id: target_id,
                     place_coords: place_coords,
                 },
-            );
+            ) {
+                warn!("ship {:?} can not land: {}", ship_id, reason);
+            }
             // reset cockipt window
             state.ecs.insert(CockpitWindowState::new(SubWindow::Main));
             // close
@@ -514,14 +805,6 @@ fn set_selected_land_position(ecs: &mut World, surface_size: P2, mut current: P2
     ecs.fetch_mut::<CockpitWindowState>().sub_window = SubWindow::Land { selected: current };
 }
 
-fn set_ship_command(ecs: &mut World, ship_id: Entity, ship_command: ship::Command) {
-    info!("update ship {:?} command to {:?}", ship_id, ship_command);
-    ecs.write_storage::<Ship>()
-        .get_mut(ship_id)
-        .unwrap()
-        .current_command = ship_command;
-}
-
 pub fn get_key_index(key: Option<VirtualKeyCode>) -> Option<usize> {
     match key {
         Some(VirtualKeyCode::Key0) => Some(0),
@@ -541,6 +824,7 @@ pub fn get_key_index(key: Option<VirtualKeyCode>) -> Option<usize> {
 fn list_commands(ecs: &World, ship_id: Entity) -> Vec<MenuOption> {
     let locations = ecs.read_storage::<Location>();
     let sectors = ecs.read_storage::<Sector>();
+    let bodies = ecs.read_storage::<SectorBody>();
 
     // TODO: currently can be none when ship is landed
     let location = match locations.get(ship_id) {
@@ -552,11 +836,30 @@ fn list_commands(ecs: &World, ship_id: Entity) -> Vec<MenuOption> {
 
     let mut commands = vec![];
 
+    if ecs.read_storage::<Encounter>().contains(ship_id) {
+        for action in [
+            EncounterAction::Hail,
+            EncounterAction::Flee,
+            EncounterAction::Fight,
+            EncounterAction::Board,
+        ] {
+            commands.push(MenuOption::Encounter { action });
+        }
+    }
+
+    // ships that left the sector or landed can not be followed
+    let is_reachable = |body_id: Entity, sector_id: Entity| match bodies.get(body_id) {
+        Some(SectorBody::Ship) => crate::locations::resolve_sector_pos(&locations, body_id)
+            .map(|(_, id)| id == sector_id)
+            .unwrap_or(false),
+        _ => true,
+    };
+
     match location {
         Location::Sector { sector_id, .. } => {
             let sector = sectors.get(*sector_id).unwrap();
             for body_id in &sector.bodies {
-                if *body_id == ship_id {
+                if *body_id == ship_id || !is_reachable(*body_id, *sector_id) {
                     continue;
                 }
 
@@ -564,15 +867,51 @@ fn list_commands(ecs: &World, ship_id: Entity) -> Vec<MenuOption> {
                     target_id: *body_id,
                 });
             }
-        }
-        Location::Orbit { .. } => {
-            commands.push(MenuOption::Land);
+            commands.push(MenuOption::Build);
+        }
+        Location::Orbit { target_id } => {
+            match bodies.get(*target_id) {
+                Some(SectorBody::Station) => commands.push(MenuOption::Dock {
+                    target_id: *target_id,
+                }),
+                Some(SectorBody::Jump { .. }) => commands.push(MenuOption::Jump {
+                    target_id: *target_id,
+                }),
+                _ => commands.push(MenuOption::Land),
+            }
+
+            // leave orbit to other bodies of the sector
+            if let Some((_, sector_id)) =
+                crate::locations::resolve_sector_pos(&locations, *target_id)
+            {
+                let sector = sectors.get(sector_id).unwrap();
+                for body_id in &sector.bodies {
+                    if *body_id == ship_id
+                        || body_id == target_id
+                        || !is_reachable(*body_id, sector_id)
+                    {
+                        continue;
+                    }
+
+                    commands.push(MenuOption::FlyTo {
+                        target_id: *body_id,
+                    });
+                }
+            }
+            commands.push(MenuOption::Build);
         }
         Location::BodySurface { .. } => {
             commands.push(MenuOption::Launch);
+            commands.push(MenuOption::Harvest);
         }
         Location::BodySurfacePlace { .. } => {
             commands.push(MenuOption::Launch);
+            commands.push(MenuOption::Harvest);
+        }
+        Location::Docked { .. } => {
+            commands.push(MenuOption::Trade);
+            commands.push(MenuOption::Refuel);
+            commands.push(MenuOption::Launch);
         }
     }
 
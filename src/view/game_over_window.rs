@@ -0,0 +1,31 @@
+use crate::state::State;
+use crate::{cfg, save, survival_system};
+use rltk::{Rltk, VirtualKeyCode, RGB};
+
+pub fn draw(state: &mut State, ctx: &mut Rltk) {
+    let w = 30;
+    let h = 6;
+    let x = (cfg::SCREEN_W - w) / 2;
+    let y = (cfg::SCREEN_H - h) / 2;
+    ctx.draw_box(x, y, w, h, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+
+    ctx.print_color(x + 2, y + 1, rltk::RED, rltk::BLACK, "You are dead");
+    ctx.print_color(x + 2, y + 3, rltk::RED, rltk::BLACK, "r");
+    ctx.print_color(x + 3, y + 3, rltk::GRAY, rltk::BLACK, " - respawn");
+    ctx.print_color(x + 2, y + 4, rltk::RED, rltk::BLACK, "F9");
+    ctx.print_color(x + 4, y + 4, rltk::GRAY, rltk::BLACK, " - load last save");
+
+    match ctx.key {
+        Some(VirtualKeyCode::R) => {
+            survival_system::respawn(&mut state.ecs);
+        }
+        Some(VirtualKeyCode::F9) => {
+            let cfg = (*state.ecs.fetch::<cfg::Cfg>()).clone();
+            match save::load_game(cfg, save::SAVE_FILE) {
+                Ok(loaded) => *state = loaded,
+                Err(e) => log::warn!("fail to load game: {:?}", e),
+            }
+        }
+        _ => {}
+    }
+}
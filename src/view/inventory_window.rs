@@ -0,0 +1,95 @@
+use crate::actions::{self, Action};
+use crate::cfg;
+use crate::inventory::{Inventory, Item};
+use crate::models::Player;
+use crate::state::State;
+use crate::view::window::Window;
+use rltk::{Rltk, VirtualKeyCode, RGB};
+use specs::prelude::*;
+
+/// selected line of the inventory window
+#[derive(Debug, Default)]
+pub struct InventoryWindowState {
+    pub selected: usize,
+}
+
+/// list the focused avatar items, actions are executed by the avatar as soon it has energy
+pub fn draw(state: &mut State, ctx: &mut Rltk) {
+    let names = {
+        let player = state.ecs.fetch::<Player>();
+        let inventories = state.ecs.read_storage::<Inventory>();
+        let items = state.ecs.read_storage::<Item>();
+        inventories
+            .get(player.get_avatar_id())
+            .map(|inventory| {
+                inventory
+                    .items
+                    .iter()
+                    .map(|id| items.get(*id).map(|i| i.name()).unwrap_or("?"))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+
+    let selected = {
+        let mut window_state = state.ecs.write_resource::<InventoryWindowState>();
+        window_state.selected = window_state.selected.min(names.len().saturating_sub(1));
+        window_state.selected
+    };
+
+    let border = 10;
+    let x = border + 2;
+    let mut y = border + 2;
+    ctx.draw_box(
+        border,
+        border,
+        cfg::SCREEN_W - border * 2,
+        cfg::SCREEN_H - border * 2,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+    ctx.print_color(x, y, rltk::GRAY, rltk::BLACK, "Inventory");
+    y += 2;
+
+    if names.is_empty() {
+        ctx.print_color(x, y, rltk::GRAY, rltk::BLACK, "nothing");
+    }
+    for (i, name) in names.iter().enumerate() {
+        let color = if i == selected {
+            rltk::YELLOW
+        } else {
+            rltk::GRAY
+        };
+        ctx.print_color(x, y, color, rltk::BLACK, name);
+        y += 1;
+    }
+
+    let help_y = cfg::SCREEN_H - border - 2;
+    ctx.print_color(
+        x,
+        help_y,
+        rltk::GRAY,
+        rltk::BLACK,
+        "u - use, d - drop, esc - close",
+    );
+
+    match ctx.key {
+        Some(VirtualKeyCode::Up) => {
+            let mut window_state = state.ecs.write_resource::<InventoryWindowState>();
+            window_state.selected = window_state.selected.saturating_sub(1);
+        }
+        Some(VirtualKeyCode::Down) => {
+            state.ecs.write_resource::<InventoryWindowState>().selected += 1;
+        }
+        Some(VirtualKeyCode::U) if !names.is_empty() => {
+            actions::set_focused_action(&mut state.ecs, Action::Use(selected));
+        }
+        Some(VirtualKeyCode::D) if !names.is_empty() => {
+            actions::set_focused_action(&mut state.ecs, Action::Drop(selected));
+        }
+        Some(VirtualKeyCode::Escape) | Some(VirtualKeyCode::B) => {
+            state.ecs.insert(Window::World);
+        }
+        _ => {}
+    }
+}
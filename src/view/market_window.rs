@@ -0,0 +1,173 @@
+use crate::cfg;
+use crate::events::{EntitiesEvents, Event};
+use crate::game_time::GameTime;
+use crate::models::{Label, Player};
+use crate::state::State;
+use crate::trade::{self, Cargo, Commodity, Market};
+use crate::view::message_log;
+use crate::view::window::Window;
+use rltk::{Rltk, VirtualKeyCode, RGB};
+use specs::prelude::*;
+
+/// ship that is trading and the selected commodity
+#[derive(Debug, Default)]
+pub struct MarketWindowState {
+    pub ship_id: Option<Entity>,
+    pub selected: usize,
+}
+
+impl MarketWindowState {
+    pub fn new(ship_id: Entity) -> Self {
+        MarketWindowState {
+            ship_id: Some(ship_id),
+            selected: 0,
+        }
+    }
+}
+
+pub fn draw(state: &mut State, ctx: &mut Rltk) {
+    let (ship_id, selected) = {
+        let window_state = state.ecs.fetch::<MarketWindowState>();
+        (window_state.ship_id, window_state.selected)
+    };
+
+    let station_id = match ship_id.and_then(|id| trade::find_docked_station(&state.ecs, id)) {
+        Some(id) => id,
+        None => {
+            state.ecs.insert(Window::Cockpit);
+            return;
+        }
+    };
+    let ship_id = ship_id.unwrap();
+
+    // frame
+    let border = 4;
+    ctx.draw_box(
+        border,
+        border,
+        cfg::SCREEN_W - border * 2,
+        cfg::SCREEN_H - border * 2,
+        RGB::named(rltk::WHITE),
+        RGB::named(rltk::BLACK),
+    );
+
+    let x = border + 2;
+    let mut y = border + 2;
+
+    {
+        let tick = state.ecs.fetch::<GameTime>().tick;
+        let labels = state.ecs.read_storage::<Label>();
+        let markets = state.ecs.read_storage::<Market>();
+        let cargos = state.ecs.read_storage::<Cargo>();
+        let cargo = cargos.get(ship_id).cloned().unwrap_or_default();
+
+        let station_name = labels
+            .get(station_id)
+            .map(|l| l.name.as_str())
+            .unwrap_or("station");
+        ctx.print_color(
+            x,
+            y,
+            rltk::GRAY,
+            rltk::BLACK,
+            format!("Market of {}", station_name),
+        );
+        y += 2;
+        ctx.print_color(
+            x,
+            y,
+            rltk::GRAY,
+            rltk::BLACK,
+            format!(
+                "credits {}  cargo {}/{}",
+                cargo.credits,
+                cargo.total(),
+                cargo.capacity
+            ),
+        );
+        y += 2;
+
+        ctx.print_color(
+            x,
+            y,
+            rltk::GRAY,
+            rltk::BLACK,
+            format!("{:<14}{:>6}{:>6}{:>6}", "", "buy", "sell", "hold"),
+        );
+        y += 1;
+
+        if let Some(market) = markets.get(station_id) {
+            for (i, commodity) in Commodity::ALL.iter().enumerate() {
+                let color = if i == selected {
+                    rltk::YELLOW
+                } else {
+                    rltk::GRAY
+                };
+                ctx.print_color(
+                    x,
+                    y,
+                    color,
+                    rltk::BLACK,
+                    format!(
+                        "{:<14}{:>6}{:>6}{:>6}",
+                        commodity.name(),
+                        market.buy_price(*commodity, tick),
+                        market.sell_price(*commodity, tick),
+                        cargo.get(*commodity)
+                    ),
+                );
+                y += 1;
+            }
+        }
+
+        y += 1;
+        ctx.print_color(
+            x,
+            y,
+            rltk::GRAY,
+            rltk::BLACK,
+            "b - buy, s - sell, esc - back",
+        );
+    }
+
+    // draw messages
+    let h = 5;
+    message_log::draw(
+        &state.ecs,
+        ctx,
+        x,
+        cfg::SCREEN_H - border - h - 1,
+        cfg::SCREEN_W - x * 2,
+        h,
+    );
+
+    // process inputs
+    let commodity = Commodity::ALL[selected];
+    let result = match ctx.key {
+        Some(VirtualKeyCode::Up) => {
+            let mut window_state = state.ecs.write_resource::<MarketWindowState>();
+            window_state.selected = window_state.selected.saturating_sub(1);
+            Ok(0)
+        }
+        Some(VirtualKeyCode::Down) => {
+            let mut window_state = state.ecs.write_resource::<MarketWindowState>();
+            window_state.selected = (window_state.selected + 1).min(Commodity::ALL.len() - 1);
+            Ok(0)
+        }
+        Some(VirtualKeyCode::B) => trade::trade(&mut state.ecs, ship_id, commodity, 1),
+        Some(VirtualKeyCode::S) => trade::trade(&mut state.ecs, ship_id, commodity, -1),
+        Some(VirtualKeyCode::Escape) => {
+            state.ecs.insert(Window::Cockpit);
+            Ok(0)
+        }
+        _ => Ok(0),
+    };
+
+    if let Err(reason) = result {
+        let actor_id = state.ecs.fetch::<Player>().get_avatar_id();
+        state
+            .ecs
+            .write_resource::<EntitiesEvents>()
+            .push(Event::ActionFailed { actor_id, reason });
+    }
+}
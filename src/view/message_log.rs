@@ -0,0 +1,221 @@
+use crate::events::{Event, MessageLog};
+use crate::inventory::Item;
+use crate::models::{Label, Player};
+use rltk::Rltk;
+use specs::prelude::*;
+
+type Color = (u8, u8, u8);
+
+fn name_of(labels: &ReadStorage<Label>, player: &Player, id: Entity) -> String {
+    if id == player.get_avatar_id() {
+        return "you".to_string();
+    }
+
+    labels
+        .get(id)
+        .map(|label| label.name.clone())
+        .unwrap_or_else(|| "someone".to_string())
+}
+
+fn describe(labels: &ReadStorage<Label>, player: &Player, event: &Event) -> (String, Color) {
+    let name = |id: &Entity| name_of(labels, player, *id);
+
+    match event {
+        Event::ArrivedOrbit { ship_id, body_id } => (
+            format!("{} entered in orbit of {}", name(ship_id), name(body_id)),
+            rltk::GREEN,
+        ),
+        Event::Landed { ship_id, zone_id } => (
+            format!("{} landed at {}", name(ship_id), name(zone_id)),
+            rltk::GREEN,
+        ),
+        Event::Launched { ship_id, body_id } => (
+            format!("{} launched to orbit of {}", name(ship_id), name(body_id)),
+            rltk::GREEN,
+        ),
+        Event::Docked {
+            ship_id,
+            station_id,
+        } => (
+            format!("{} docked at {}", name(ship_id), name(station_id)),
+            rltk::GREEN,
+        ),
+        Event::Jumped { ship_id, sector_id } => (
+            format!("{} jumped to {}", name(ship_id), name(sector_id)),
+            rltk::GREEN,
+        ),
+        Event::Traded {
+            ship_id,
+            commodity,
+            amount,
+            credits,
+            ..
+        } => {
+            let verb = if *amount >= 0 { "bought" } else { "sold" };
+            (
+                format!(
+                    "{} {} {} {} for {}",
+                    name(ship_id),
+                    verb,
+                    amount.abs(),
+                    commodity.name(),
+                    credits.abs()
+                ),
+                rltk::YELLOW,
+            )
+        }
+        Event::Refueled {
+            ship_id,
+            amount,
+            credits,
+            ..
+        } => (
+            format!(
+                "{} refueled {} for {}",
+                name(ship_id),
+                amount,
+                credits.abs()
+            ),
+            rltk::YELLOW,
+        ),
+        Event::FuelHarvested { ship_id, amount } => (
+            format!("{} harvested {} fuel", name(ship_id), amount),
+            rltk::GREEN,
+        ),
+        Event::OutOfFuel { ship_id } => (format!("{} is out of fuel", name(ship_id)), rltk::RED),
+        Event::ShipDamaged { ship_id, damage } => (
+            format!("{} took {} damage", name(ship_id), damage),
+            rltk::RED,
+        ),
+        Event::ModuleDamaged { damage, .. } => {
+            (format!("a module took {} damage", damage), rltk::ORANGE)
+        }
+        Event::HullBreached { ship_id } => {
+            (format!("{} hull is breached", name(ship_id)), rltk::RED)
+        }
+        Event::Repaired { actor_id, kind } => (
+            format!("{} repaired the {}", name(actor_id), kind.name()),
+            rltk::GREEN,
+        ),
+        Event::HullRepaired { actor_id, ship_id } => (
+            format!("{} patched the {} hull", name(actor_id), name(ship_id)),
+            rltk::GREEN,
+        ),
+        Event::EncounterStarted { ship_id, other_id } => (
+            format!("{} encountered {}", name(ship_id), name(other_id)),
+            rltk::YELLOW,
+        ),
+        Event::Hailed { ship_id, other_id } => (
+            format!("{} hailed {}", name(ship_id), name(other_id)),
+            rltk::GRAY,
+        ),
+        Event::ShipAttacked { ship_id, other_id } => (
+            format!("{} attacked {}", name(ship_id), name(other_id)),
+            rltk::RED,
+        ),
+        Event::Fled { ship_id, other_id } => (
+            format!("{} fled from {}", name(ship_id), name(other_id)),
+            rltk::YELLOW,
+        ),
+        Event::Boarded {
+            ship_id,
+            other_id,
+            credits,
+        } => (
+            format!(
+                "{} boarded {} taking {} credits",
+                name(ship_id),
+                name(other_id),
+                credits
+            ),
+            rltk::ORANGE,
+        ),
+        Event::DoorOpened { actor_id, .. } => {
+            (format!("{} opened a door", name(actor_id)), rltk::GRAY)
+        }
+        Event::DoorClosed { actor_id, .. } => {
+            (format!("{} closed a door", name(actor_id)), rltk::GRAY)
+        }
+        Event::AirlockCycled { air, .. } if air.is_vacuum() => {
+            ("airlock depressurized".to_string(), rltk::CYAN)
+        }
+        Event::AirlockCycled { .. } => ("airlock pressurized".to_string(), rltk::CYAN),
+        Event::ActionFailed { reason, .. } => (reason.clone(), rltk::RED),
+        Event::WalkInterrupted { actor_id, seen_id } => (
+            format!(
+                "{} stop walking, {} is in view",
+                name(actor_id),
+                name(seen_id)
+            ),
+            rltk::YELLOW,
+        ),
+        Event::Died { entity_id } => (format!("{} died", name(entity_id)), rltk::RED),
+        Event::Attacked {
+            attacker_id,
+            target_id,
+            damage,
+            ranged,
+        } => {
+            let verb = if *ranged { "shot" } else { "hit" };
+            let color = if player.is_avatar(*target_id) {
+                rltk::RED
+            } else {
+                rltk::WHITE
+            };
+            (
+                format!(
+                    "{} {} {} for {}",
+                    name(attacker_id),
+                    verb,
+                    name(target_id),
+                    damage
+                ),
+                color,
+            )
+        }
+        Event::PickedUp { actor_id, item_id } => (
+            format!("{} picked up {}", name(actor_id), name(item_id)),
+            rltk::GRAY,
+        ),
+        Event::Dropped { actor_id, item_id } => (
+            format!("{} dropped {}", name(actor_id), name(item_id)),
+            rltk::GRAY,
+        ),
+        Event::ItemUsed { actor_id, kind } => (
+            format!("{} used {}", name(actor_id), Item { kind: *kind }.name()),
+            rltk::GREEN,
+        ),
+        Event::ClimbedStairs { actor_id, down } => {
+            let dir = if *down { "down" } else { "up" };
+            (
+                format!("{} climbed {} the stairs", name(actor_id), dir),
+                rltk::GRAY,
+            )
+        }
+        Event::JoinedSquad { entity_id } => {
+            (format!("{} joined the squad", name(entity_id)), rltk::GREEN)
+        }
+        Event::LeftSquad { entity_id } => {
+            (format!("{} left the squad", name(entity_id)), rltk::GRAY)
+        }
+    }
+}
+
+/// draw the last messages that fit into the box, newer at the bottom
+pub fn draw(ecs: &World, ctx: &mut Rltk, x: i32, y: i32, w: i32, h: i32) {
+    let log = ecs.fetch::<MessageLog>();
+    let labels = ecs.read_storage::<Label>();
+    let player = ecs.fetch::<Player>();
+
+    let entries = log.visible_entries(h as usize);
+    let start_y = y + h - entries.len() as i32;
+    for (i, entry) in entries.iter().enumerate() {
+        let (text, color) = describe(&labels, &player, &entry.event);
+        let text = text.chars().take(w as usize).collect::<String>();
+        ctx.print_color(x, start_y + i as i32, color, rltk::BLACK, text);
+    }
+
+    if log.scroll > 0 {
+        ctx.print_color(x + w - 1, y, rltk::YELLOW, rltk::BLACK, "^");
+    }
+}
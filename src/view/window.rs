@@ -5,4 +5,12 @@ use specs_derive::*;
 pub enum Window {
     World,
     Cockpit,
+    /// list of carried items
+    Inventory,
+    /// station market, opened from the cockpit when docked
+    Market,
+    /// place and remove the ship parts, opened from the cockpit when in space
+    Build,
+    /// the player avatar is dead, waiting to respawn
+    GameOver,
 }
@@ -0,0 +1,294 @@
+use crate::area::{Cell, Tile};
+use crate::atmosphere::Air;
+use crate::commons::grid::{Coord, Grid};
+use crate::commons::recti::RectI;
+use crate::models::{SurfaceTileKind, P2};
+use rand::Rng;
+
+/// area at the zone center kept clear for ship landing, big enough for the ship and some margin
+/// around its airlock
+pub const LANDING_WIDTH: i32 = 31;
+pub const LANDING_HEIGHT: i32 = 21;
+
+/// width of the paths carved from landing area to the zone borders
+const PATH_WIDTH: i32 = 3;
+const BUILDING_ATTEMPTS: u32 = 30;
+const RUIN_WALL_DAMAGE_PROB: f32 = 0.5;
+const RUBBLE_PROB: f32 = 0.03;
+
+/// Generated zone grid and the intact prefabs placed into it, by prefab index and position. Ruins
+/// are not listed as its objects are destroyed.
+pub struct ZoneLayout {
+    pub grid: Grid<Cell>,
+    pub buildings: Vec<(usize, P2)>,
+}
+
+/// Generate the zone content for its kind, reserved areas are kept free of prefabs
+pub fn generate<R: Rng>(
+    rng: &mut R,
+    kind: SurfaceTileKind,
+    size: i32,
+    tile: Tile,
+    air: Air,
+    prefabs: &[Grid<Cell>],
+    reserved: &[RectI],
+) -> ZoneLayout {
+    let ground = Cell::with_air(tile, air);
+    let mut grid = Grid::new_square(size, || ground);
+
+    match kind {
+        SurfaceTileKind::Mountains => cellular_automata(rng, &mut grid, Tile::Rock, air, 0.5, 4),
+        SurfaceTileKind::Water => cellular_automata(rng, &mut grid, Tile::Water, air, 0.5, 5),
+        SurfaceTileKind::Forest => {
+            cellular_automata(rng, &mut grid, Tile::Vegetation, air, 0.45, 3)
+        }
+        SurfaceTileKind::Ruins => scatter(rng, &mut grid, Tile::Rubble, air, RUBBLE_PROB),
+        SurfaceTileKind::Plain | SurfaceTileKind::Structure => {}
+    }
+
+    let landing = landing_area(size);
+    if !matches!(kind, SurfaceTileKind::Plain | SurfaceTileKind::Structure) {
+        let center = landing.center();
+        for target in &[
+            Coord::new(center.x, 0),
+            Coord::new(center.x, size - 1),
+            Coord::new(0, center.y),
+            Coord::new(size - 1, center.y),
+        ] {
+            carve_path(rng, &mut grid, ground, center, *target);
+        }
+    }
+    fill(&mut grid, &landing, ground);
+
+    let mut buildings = vec![];
+    let count = match kind {
+        SurfaceTileKind::Structure => rng.gen_range(1..=3),
+        SurfaceTileKind::Ruins => rng.gen_range(2..=4),
+        _ => 0,
+    };
+    if !prefabs.is_empty() {
+        let mut occupied = reserved.to_vec();
+        occupied.push(landing);
+
+        for _ in 0..count {
+            let index = rng.gen_range(0..prefabs.len());
+            let prefab = &prefabs[index];
+            let pos = match find_free_place(rng, size, prefab, &occupied) {
+                Some(pos) => pos,
+                None => continue,
+            };
+            occupied.push(RectI::new(
+                pos.x - 1,
+                pos.y - 1,
+                prefab.get_width() + 1,
+                prefab.get_height() + 1,
+            ));
+
+            if matches!(kind, SurfaceTileKind::Ruins) {
+                grid.merge(pos, &ruin(rng, prefab, air));
+            } else {
+                grid.merge(pos, prefab);
+                buildings.push((index, pos));
+            }
+        }
+    }
+
+    ZoneLayout { grid, buildings }
+}
+
+pub fn landing_area(size: i32) -> RectI {
+    RectI::new(
+        size / 2 - LANDING_WIDTH / 2,
+        size / 2 - LANDING_HEIGHT / 2,
+        LANDING_WIDTH - 1,
+        LANDING_HEIGHT - 1,
+    )
+}
+
+/// random noise smoothed into blobs of the tile, cells out of the grid count as filled
+fn cellular_automata<R: Rng>(
+    rng: &mut R,
+    grid: &mut Grid<Cell>,
+    tile: Tile,
+    air: Air,
+    fill_prob: f32,
+    steps: u32,
+) {
+    let (width, height) = (grid.get_width(), grid.get_height());
+    let mut filled: Vec<bool> = (0..width * height)
+        .map(|_| rng.gen::<f32>() < fill_prob)
+        .collect();
+
+    for _ in 0..steps {
+        let mut next = filled.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let mut count = 0;
+                for ny in y - 1..=y + 1 {
+                    for nx in x - 1..=x + 1 {
+                        if nx == x && ny == y {
+                            continue;
+                        }
+                        let outside = nx < 0 || ny < 0 || nx >= width || ny >= height;
+                        if outside || filled[(nx + ny * width) as usize] {
+                            count += 1;
+                        }
+                    }
+                }
+                next[(x + y * width) as usize] = count >= 5;
+            }
+        }
+        filled = next;
+    }
+
+    for (index, is_filled) in filled.into_iter().enumerate() {
+        if is_filled {
+            grid.set(index as i32, Cell::with_air(tile, air));
+        }
+    }
+}
+
+fn scatter<R: Rng>(rng: &mut R, grid: &mut Grid<Cell>, tile: Tile, air: Air, prob: f32) {
+    for index in 0..grid.len() as i32 {
+        if rng.gen::<f32>() < prob {
+            grid.set(index, Cell::with_air(tile, air));
+        }
+    }
+}
+
+fn fill(grid: &mut Grid<Cell>, rect: &RectI, cell: Cell) {
+    let (tl, br) = (rect.get_top_left(), rect.get_bottom_right());
+    for y in tl.y.max(0)..=br.y.min(grid.get_height() - 1) {
+        for x in tl.x.max(0)..=br.x.min(grid.get_width() - 1) {
+            grid.set_at(Coord::new(x, y), cell);
+        }
+    }
+}
+
+/// random walk biased to the target, clearing everything on the way
+fn carve_path<R: Rng>(rng: &mut R, grid: &mut Grid<Cell>, cell: Cell, from: Coord, to: Coord) {
+    let mut pos = from;
+    while pos != to {
+        fill(
+            grid,
+            &RectI::new(
+                pos.x - PATH_WIDTH / 2,
+                pos.y - PATH_WIDTH / 2,
+                PATH_WIDTH - 1,
+                PATH_WIDTH - 1,
+            ),
+            cell,
+        );
+
+        let (dx, dy) = ((to.x - pos.x).signum(), (to.y - pos.y).signum());
+        pos = if rng.gen::<f32>() < 0.7 {
+            Coord::new(pos.x + dx, pos.y + dy)
+        } else if dx == 0 {
+            Coord::new(pos.x + rng.gen_range(-1..=1), pos.y)
+        } else {
+            Coord::new(pos.x, pos.y + rng.gen_range(-1..=1))
+        };
+        pos.x = pos.x.clamp(0, grid.get_width() - 1);
+        pos.y = pos.y.clamp(0, grid.get_height() - 1);
+    }
+    grid.set_at(to, cell);
+}
+
+fn find_free_place<R: Rng>(
+    rng: &mut R,
+    size: i32,
+    prefab: &Grid<Cell>,
+    occupied: &[RectI],
+) -> Option<P2> {
+    let max_x = size - prefab.get_width();
+    let max_y = size - prefab.get_height();
+    if max_x <= 0 || max_y <= 0 {
+        return None;
+    }
+
+    (0..BUILDING_ATTEMPTS)
+        .map(|_| P2::new(rng.gen_range(0..max_x), rng.gen_range(0..max_y)))
+        .find(|pos| {
+            let rect = RectI::new(
+                pos.x,
+                pos.y,
+                prefab.get_width() - 1,
+                prefab.get_height() - 1,
+            );
+            occupied.iter().all(|other| !rect.intersects(other))
+        })
+}
+
+/// damaged copy of a prefab, doors and part of the walls are turned into rubble
+fn ruin<R: Rng>(rng: &mut R, prefab: &Grid<Cell>, air: Air) -> Grid<Cell> {
+    let mut grid = prefab.clone();
+    for index in 0..grid.len() as i32 {
+        let damaged = match grid.get(index).tile {
+            Tile::Door { .. } => true,
+            Tile::Wall => rng.gen::<f32>() < RUIN_WALL_DAMAGE_PROB,
+            _ => false,
+        };
+        if damaged {
+            grid.set(index, Cell::with_air(Tile::Rubble, air));
+        }
+    }
+    grid
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn count(grid: &Grid<Cell>, tile: Tile) -> usize {
+        grid.iter().filter(|cell| cell.tile == tile).count()
+    }
+
+    #[test]
+    fn test_zone_content_should_match_its_kind() {
+        let prefabs = vec![Grid::new(5, 4, || Cell::new(Tile::Wall))];
+        let air = Air::STANDARD;
+
+        for (kind, tile) in &[
+            (SurfaceTileKind::Mountains, Tile::Rock),
+            (SurfaceTileKind::Water, Tile::Water),
+            (SurfaceTileKind::Forest, Tile::Vegetation),
+            (SurfaceTileKind::Ruins, Tile::Rubble),
+        ] {
+            let mut rng = StdRng::seed_from_u64(0);
+            let layout = generate(&mut rng, *kind, 60, Tile::Ground, air, &prefabs, &[]);
+            assert!(
+                count(&layout.grid, *tile) > 0,
+                "no {:?} in {:?}",
+                tile,
+                kind
+            );
+            assert!(layout.buildings.is_empty());
+
+            // landing area and its exits are clear
+            let landing = landing_area(60);
+            for y in 0..LANDING_HEIGHT {
+                for x in 0..LANDING_WIDTH {
+                    let coord = landing.to_global(&Coord::new(x, y));
+                    assert_eq!(Tile::Ground, layout.grid.get_at(coord).tile);
+                }
+            }
+            assert_eq!(Tile::Ground, layout.grid.get_at(Coord::new(30, 0)).tile);
+            assert_eq!(Tile::Ground, layout.grid.get_at(Coord::new(59, 30)).tile);
+        }
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let layout = generate(
+            &mut rng,
+            SurfaceTileKind::Structure,
+            60,
+            Tile::Ground,
+            air,
+            &prefabs,
+            &[],
+        );
+        assert!(!layout.buildings.is_empty());
+        assert_eq!(20 * layout.buildings.len(), count(&layout.grid, Tile::Wall));
+    }
+}